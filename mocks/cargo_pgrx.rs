@@ -0,0 +1,10 @@
+// Cargo app with pgrx that reports its version and fails all other commands.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args[1..] == ["pgrx", "--version"] {
+        println!("cargo-pgrx 0.12.6");
+        return;
+    }
+    eprintln!("DED: {}", &args[1..].join(" "));
+    std::process::exit(2)
+}
@@ -0,0 +1,11 @@
+// Simple app that prints the environment variables named by its arguments
+// and returns an error.
+fn main() {
+    for name in std::env::args().skip(1) {
+        match std::env::var(&name) {
+            Ok(val) => eprintln!("{name}={val}"),
+            Err(_) => eprintln!("{name} unset"),
+        }
+    }
+    std::process::exit(2)
+}
@@ -0,0 +1,19 @@
+// GNU make-like app that reports its version, recording each version check
+// in a `probes` file next to the executable, and echoes other arguments.
+use std::io::Write;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args == ["--version"] {
+        let exe = std::env::current_exe().unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(exe.with_file_name("probes"))
+            .unwrap();
+        writeln!(file, "--version").unwrap();
+        println!("GNU Make 4.4.1");
+        return;
+    }
+    println!("{}", &args.join(" "));
+}
@@ -0,0 +1,12 @@
+// Simple app that prints invalid UTF-8 to stdout and stderr and returns an
+// error if its first argument is `fail`.
+use std::io::Write;
+
+fn main() {
+    // "café" in Latin-1.
+    std::io::stdout().write_all(b"caf\xe9\nok\n").unwrap();
+    std::io::stderr().write_all(b"caf\xe9\n").unwrap();
+    if std::env::args().nth(1).as_deref() == Some("fail") {
+        std::process::exit(2)
+    }
+}
@@ -3,9 +3,9 @@
 //! [Dist API]: https://github.com/pgxn/pgxn-api/wiki/dist-api
 
 use chrono::{DateTime, Utc};
-use semver::Version;
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
-use std::{borrow::Borrow, io};
+use std::{borrow::Borrow, fmt, io};
 
 use crate::error::BuildError;
 
@@ -26,6 +26,41 @@ impl Release {
     pub fn date(&self) -> &DateTime<Utc> {
         self.date.borrow()
     }
+
+    /// Returns true if `other` has the same version as this Release,
+    /// regardless of date. Useful for reconciling release lists from mirrors
+    /// that record different dates for the same release.
+    pub fn same_version(&self, other: &Release) -> bool {
+        self.version == other.version
+    }
+}
+
+/// The maturity of a [`Release`], as recorded by the lists in
+/// [`Releases`]. Ordered from least to most mature, so that
+/// `Maturity::Testing < Maturity::Stable`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum Maturity {
+    /// An unstable release, such as an alpha or development release.
+    Unstable,
+    /// A testing release, such as a beta or release candidate.
+    Testing,
+    /// A stable release.
+    Stable,
+}
+
+impl Maturity {
+    /// All maturities, from most to least mature.
+    pub const ALL: [Maturity; 3] = [Maturity::Stable, Maturity::Testing, Maturity::Unstable];
+}
+
+impl fmt::Display for Maturity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Maturity::Unstable => "unstable",
+            Maturity::Testing => "testing",
+            Maturity::Stable => "stable",
+        })
+    }
 }
 
 /// Represents all the releases for a [`Dist`].
@@ -54,6 +89,26 @@ impl Releases {
     pub fn testing(&self) -> Option<&[Release]> {
         self.testing.as_deref()
     }
+
+    /// Borrows the releases with `maturity`.
+    pub fn get(&self, maturity: Maturity) -> Option<&[Release]> {
+        match maturity {
+            Maturity::Stable => self.stable(),
+            Maturity::Testing => self.testing(),
+            Maturity::Unstable => self.unstable(),
+        }
+    }
+
+    /// Returns an iterator over all the releases and their maturities,
+    /// sorted from newest to oldest version.
+    pub fn iter(&self) -> impl Iterator<Item = (Maturity, &Release)> {
+        let mut all: Vec<(Maturity, &Release)> = Maturity::ALL
+            .into_iter()
+            .flat_map(|m| self.get(m).unwrap_or_default().iter().map(move |r| (m, r)))
+            .collect();
+        all.sort_by(|a, b| b.1.version.cmp(&a.1.version));
+        all.into_iter()
+    }
 }
 
 /// Represents the release data for a distribution name. Loaded from the PGXN
@@ -69,8 +124,7 @@ pub struct Dist {
 impl Dist {
     /// Loads a [`Dist`] from an [`std::io::Read`].
     pub fn from_reader<R: io::Read>(rdr: R) -> Result<Dist, BuildError> {
-        let dist: Dist = serde_json::from_reader(rdr)?;
-        Ok(dist)
+        serde_json::from_reader(rdr).map_err(|e| BuildError::parse("dist listing", e))
     }
 
     /// Borrows the Dist name
@@ -83,46 +137,92 @@ impl Dist {
         self.releases.borrow()
     }
 
+    /// Returns all of the stable, testing, and unstable versions, sorted
+    /// from newest to oldest.
+    pub fn versions(&self) -> Vec<&Version> {
+        self.releases.iter().map(|(_, r)| r.version()).collect()
+    }
+
     /// Finds and returns the best version to install, preferring the latest
     /// stable version. If there are no stable versions, it tries to return
     /// the latest testing version. If there are no testing versions, it
     /// returns the latest unstable versions. Returns an error if there are no
-    /// versions at all.
+    /// versions at all. Use [`best_version_at_least`](Self::best_version_at_least)
+    /// to avoid falling back on less mature versions, e.g., for production
+    /// installs.
     pub fn best_version(&self) -> Result<&Version, BuildError> {
-        if let Some(v) = self.latest_stable_version() {
-            return Ok(v);
-        }
-        if let Some(v) = self.latest_testing_version() {
+        self.best_version_at_least(Maturity::Unstable)
+    }
+
+    /// Finds and returns the best version to install that is at least as
+    /// mature as `min`, preferring the latest stable version, then the
+    /// latest testing version, then the latest unstable version. Pass
+    /// [`Maturity::Stable`] for stable versions only, or
+    /// [`Maturity::Testing`] to accept testing versions when there are no
+    /// stable versions. Returns [`BuildError::Immature`] rather than
+    /// descending below `min`, or an error if there are no versions at all.
+    pub fn best_version_at_least(&self, min: Maturity) -> Result<&Version, BuildError> {
+        if let Some(v) = Maturity::ALL
+            .into_iter()
+            .filter(|m| *m >= min)
+            .find_map(|m| self.latest_version(m))
+        {
             return Ok(v);
         }
-        if let Some(v) = self.latest_unstable_version() {
-            return Ok(v);
+        if self.releases.iter().next().is_some() {
+            return Err(BuildError::Immature(self.name.clone(), min));
         }
-
         Err(BuildError::Invalid("missing release data"))
     }
 
-    /// Finds and returns the latest stable version.
+    /// Finds and returns the highest version that satisfies `req`, such as
+    /// the latest `0.1.x` release for `^0.1`, preferring stable releases,
+    /// then testing releases, then unstable releases. Returns [`None`] if no
+    /// release satisfies `req`. Per semantic versioning, `req` matches
+    /// pre-release versions only if it names a pre-release of the same
+    /// major, minor, and patch version, e.g., `>=1.0.0-beta`.
+    pub fn find_version(&self, req: &VersionReq) -> Option<&Version> {
+        Maturity::ALL.into_iter().find_map(|m| {
+            self.releases
+                .get(m)?
+                .iter()
+                .map(Release::version)
+                .filter(|v| req.matches(v))
+                .max()
+        })
+    }
+
+    /// Finds and returns the latest version with `maturity`, the highest by
+    /// semantic version precedence, or [`None`] if there are no such
+    /// versions.
+    pub fn latest_version(&self, maturity: Maturity) -> Option<&Version> {
+        latest_version(self.releases.get(maturity))
+    }
+
+    /// Finds and returns the latest stable version, the highest by semantic
+    /// version precedence, or [`None`] if there are no stable versions.
     pub fn latest_stable_version(&self) -> Option<&Version> {
         latest_version(self.releases.stable())
     }
 
-    /// Finds and returns the latest unstable version.
+    /// Finds and returns the latest unstable version, the highest by
+    /// semantic version precedence, or [`None`] if there are no unstable
+    /// versions.
     pub fn latest_unstable_version(&self) -> Option<&Version> {
         latest_version(self.releases.unstable())
     }
 
-    /// Finds and returns the latest testing version.
+    /// Finds and returns the latest testing version, the highest by semantic
+    /// version precedence, or [`None`] if there are no testing versions.
     pub fn latest_testing_version(&self) -> Option<&Version> {
         latest_version(self.releases.testing())
     }
 }
 
+/// Returns the highest version in `releases`, regardless of their order,
+/// or [`None`] if there are none.
 fn latest_version(releases: Option<&[Release]>) -> Option<&Version> {
-    match releases {
-        None => None,
-        Some(list) => Some(list[0].version()),
-    }
+    releases?.iter().map(Release::version).max()
 }
 
 #[cfg(test)]
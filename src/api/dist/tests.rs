@@ -108,6 +108,32 @@ fn dist() -> Result<(), BuildError> {
     Ok(())
 }
 
+#[test]
+fn dist_err() {
+    for (name, json, err) in [
+        (
+            "not JSON",
+            "PGXN FTW!",
+            "cannot parse dist listing: expected value at line 1 column 1",
+        ),
+        (
+            "missing releases",
+            r#"{"name": "pair"}"#,
+            "cannot parse dist listing: missing field `releases` at line 1 column 16",
+        ),
+        (
+            "invalid version",
+            r#"{"name": "x", "releases": {"stable": [{"version": "1", "date": "2024-07-20T20:34:34Z"}]}}"#,
+            "cannot parse dist listing: unexpected end of input while parsing major version number at line 1 column 53",
+        ),
+    ] {
+        match Dist::from_reader(json.as_bytes()) {
+            Ok(_) => panic!("{name} unexpectedly succeeded"),
+            Err(e) => assert_eq!(err, e.to_string(), "{name}"),
+        }
+    }
+}
+
 fn mk_rel(v: &str) -> Release {
     let date = Utc.with_ymd_and_hms(2024, 7, 20, 20, 34, 34).unwrap();
     Release {
@@ -116,6 +142,55 @@ fn mk_rel(v: &str) -> Release {
     }
 }
 
+#[test]
+fn same_version() {
+    let rel = mk_rel("1.2.3");
+    let later = Release {
+        date: Utc.with_ymd_and_hms(2024, 7, 21, 8, 0, 0).unwrap(),
+        version: Version::new(1, 2, 3),
+    };
+    assert!(rel.same_version(&rel));
+    assert!(rel.same_version(&later));
+    assert!(later.same_version(&rel));
+    assert_ne!(rel, later);
+    assert!(!rel.same_version(&mk_rel("1.2.4")));
+    assert!(!rel.same_version(&mk_rel("1.2.3-beta1")));
+}
+
+#[test]
+fn all_versions() {
+    let dist = Dist {
+        name: "pair".to_string(),
+        releases: Releases {
+            stable: Some(vec![mk_rel("0.1.3"), mk_rel("0.1.2")]),
+            unstable: Some(vec![mk_rel("0.2.0-alpha"), mk_rel("0.1.0")]),
+            testing: Some(vec![mk_rel("0.2.0-beta"), mk_rel("0.1.4")]),
+        },
+    };
+    let exp: Vec<Version> = [
+        "0.2.0-beta",
+        "0.2.0-alpha",
+        "0.1.4",
+        "0.1.3",
+        "0.1.2",
+        "0.1.0",
+    ]
+    .iter()
+    .map(|v| Version::parse(v).unwrap())
+    .collect();
+    assert_eq!(exp.iter().collect::<Vec<_>>(), dist.versions());
+
+    let dist = Dist {
+        name: "pair".to_string(),
+        releases: Releases {
+            stable: None,
+            unstable: None,
+            testing: None,
+        },
+    };
+    assert!(dist.versions().is_empty());
+}
+
 #[test]
 fn versions() -> Result<(), BuildError> {
     for (name, releases) in [
@@ -218,3 +293,226 @@ fn versions() -> Result<(), BuildError> {
 
     Ok(())
 }
+
+#[test]
+fn latest_unordered() {
+    let v = |s: &str| Version::parse(s).unwrap();
+
+    // Sort by version rather than trusting the order.
+    let dist = Dist {
+        name: "pair".to_string(),
+        releases: Releases {
+            stable: Some(vec![mk_rel("0.1.2"), mk_rel("0.1.10"), mk_rel("0.1.3")]),
+            unstable: Some(vec![mk_rel("0.2.0-alpha"), mk_rel("0.2.0-beta")]),
+            testing: Some(vec![mk_rel("0.1.4-rc1"), mk_rel("0.1.4-rc2")]),
+        },
+    };
+    assert_eq!(Some(&v("0.1.10")), dist.latest_stable_version());
+    assert_eq!(Some(&v("0.2.0-beta")), dist.latest_unstable_version());
+    assert_eq!(Some(&v("0.1.4-rc2")), dist.latest_testing_version());
+    assert_eq!(&v("0.1.10"), dist.best_version().unwrap());
+
+    // Skip empty lists.
+    let dist = Dist {
+        name: "pair".to_string(),
+        releases: Releases {
+            stable: Some(vec![]),
+            unstable: Some(vec![mk_rel("0.2.0")]),
+            testing: Some(vec![]),
+        },
+    };
+    assert!(dist.latest_stable_version().is_none());
+    assert!(dist.latest_testing_version().is_none());
+    assert_eq!(&v("0.2.0"), dist.best_version().unwrap());
+
+    let dist = Dist {
+        name: "pair".to_string(),
+        releases: Releases {
+            stable: Some(vec![]),
+            unstable: Some(vec![]),
+            testing: Some(vec![]),
+        },
+    };
+    assert_eq!(
+        "missing release data",
+        dist.best_version().unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn find_version() {
+    let v = |s: &str| Version::parse(s).unwrap();
+    let req = |s: &str| VersionReq::parse(s).unwrap();
+    let dist = Dist {
+        name: "pair".to_string(),
+        releases: Releases {
+            stable: Some(vec![
+                mk_rel("1.0.0"),
+                mk_rel("0.1.2"),
+                mk_rel("0.1.10"),
+                mk_rel("0.0.9"),
+            ]),
+            testing: Some(vec![mk_rel("1.1.0-rc1"), mk_rel("0.2.1")]),
+            unstable: Some(vec![
+                mk_rel("2.0.0-alpha"),
+                mk_rel("0.2.5"),
+                mk_rel("0.3.0"),
+            ]),
+        },
+    };
+
+    for (name, r, exp) in [
+        ("caret excludes newest", "^0.1", Some("0.1.10")),
+        ("tilde", "~0.1.2", Some("0.1.10")),
+        ("exact", "=0.1.2", Some("0.1.2")),
+        ("any", "*", Some("1.0.0")),
+        ("stable preferred", ">=0.1", Some("1.0.0")),
+        ("testing only", "^0.2", Some("0.2.1")),
+        ("unstable only", "^0.3", Some("0.3.0")),
+        ("testing pre-release", ">=1.1.0-rc1", Some("1.1.0-rc1")),
+        ("unstable pre-release", ">=2.0.0-alpha", Some("2.0.0-alpha")),
+        ("pre-release excluded", "^1.1", None),
+        ("no match", "^3", None),
+    ] {
+        assert_eq!(exp.map(v).as_ref(), dist.find_version(&req(r)), "{name}");
+    }
+
+    // No releases.
+    let dist = Dist {
+        name: "pair".to_string(),
+        releases: Releases {
+            stable: None,
+            unstable: None,
+            testing: None,
+        },
+    };
+    assert!(dist.find_version(&req("*")).is_none());
+}
+
+#[test]
+fn maturity() {
+    assert!(Maturity::Unstable < Maturity::Testing);
+    assert!(Maturity::Testing < Maturity::Stable);
+    assert_eq!(
+        vec!["stable", "testing", "unstable"],
+        Maturity::ALL
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn releases_iter() {
+    let releases = Releases {
+        stable: Some(vec![mk_rel("0.1.2"), mk_rel("0.1.3")]),
+        unstable: Some(vec![mk_rel("0.2.0-alpha")]),
+        testing: Some(vec![mk_rel("0.1.4")]),
+    };
+    let dist = Dist {
+        name: "pair".to_string(),
+        releases,
+    };
+    let releases = dist.releases();
+    assert_eq!(
+        vec![
+            (Maturity::Unstable, Version::parse("0.2.0-alpha").unwrap()),
+            (Maturity::Testing, Version::new(0, 1, 4)),
+            (Maturity::Stable, Version::new(0, 1, 3)),
+            (Maturity::Stable, Version::new(0, 1, 2)),
+        ],
+        releases
+            .iter()
+            .map(|(m, r)| (m, r.version().clone()))
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(releases.stable(), releases.get(Maturity::Stable));
+    assert_eq!(releases.testing(), releases.get(Maturity::Testing));
+    assert_eq!(releases.unstable(), releases.get(Maturity::Unstable));
+    assert_eq!(
+        Some(&Version::new(0, 1, 3)),
+        dist.latest_version(Maturity::Stable)
+    );
+    assert_eq!(
+        Some(&Version::new(0, 1, 4)),
+        dist.latest_version(Maturity::Testing)
+    );
+
+    // No releases.
+    let releases = Releases {
+        stable: None,
+        unstable: None,
+        testing: Some(vec![]),
+    };
+    assert_eq!(0, releases.iter().count());
+    assert_eq!(None, releases.get(Maturity::Stable));
+}
+
+#[test]
+fn best_version_at_least() {
+    let v = |s: &str| Version::parse(s).unwrap();
+    let dist = |stable: Option<Vec<Release>>, testing, unstable| Dist {
+        name: "pair".to_string(),
+        releases: Releases {
+            stable,
+            testing,
+            unstable,
+        },
+    };
+
+    for (name, dist, stable, testing, unstable) in [
+        (
+            "all",
+            dist(
+                Some(vec![mk_rel("0.1.2")]),
+                Some(vec![mk_rel("0.1.3")]),
+                Some(vec![mk_rel("0.2.0")]),
+            ),
+            Ok("0.1.2"),
+            Ok("0.1.2"),
+            Ok("0.1.2"),
+        ),
+        (
+            "no stable",
+            dist(
+                None,
+                Some(vec![mk_rel("0.1.3")]),
+                Some(vec![mk_rel("0.2.0")]),
+            ),
+            Err("no release of pair is at least stable"),
+            Ok("0.1.3"),
+            Ok("0.1.3"),
+        ),
+        (
+            "unstable only",
+            dist(None, Some(vec![]), Some(vec![mk_rel("0.2.0")])),
+            Err("no release of pair is at least stable"),
+            Err("no release of pair is at least testing"),
+            Ok("0.2.0"),
+        ),
+        (
+            "none",
+            dist(None, None, Some(vec![])),
+            Err("missing release data"),
+            Err("missing release data"),
+            Err("missing release data"),
+        ),
+    ] {
+        for (min, exp) in [
+            (Maturity::Stable, stable),
+            (Maturity::Testing, testing),
+            (Maturity::Unstable, unstable),
+        ] {
+            match (dist.best_version_at_least(min), exp) {
+                (Ok(got), Ok(exp)) => assert_eq!(&v(exp), got, "{name} {min}"),
+                (Err(e), Err(exp)) => assert_eq!(exp, e.to_string(), "{name} {min}"),
+                (got, exp) => panic!("{name} {min}: expected {exp:?} but got {got:?}"),
+            }
+        }
+        match (dist.best_version(), unstable) {
+            (Ok(got), Ok(exp)) => assert_eq!(&v(exp), got, "{name}"),
+            (Err(e), Err(exp)) => assert_eq!(exp, e.to_string(), "{name}"),
+            (got, exp) => panic!("{name}: expected {exp:?} but got {got:?}"),
+        }
+    }
+}
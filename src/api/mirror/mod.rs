@@ -0,0 +1,89 @@
+//! PGXN [Mirror API].
+//!
+//! [Mirror API]: https://github.com/pgxn/pgxn-api/wiki/mirrors-api
+
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::error::BuildError;
+
+/// Represents a single PGXN mirror. Loaded from the PGXN [Mirror API].
+///
+///  [Mirror API]: https://github.com/pgxn/pgxn-api/wiki/mirrors-api
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Mirror {
+    uri: String,
+    frequency: String,
+    location: String,
+    organization: String,
+    timezone: String,
+    email: String,
+    src: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bandwidth: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rsync: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+}
+
+impl Mirror {
+    /// Loads a list of [`Mirror`]s from an [`std::io::Read`].
+    pub fn list_from_reader<R: io::Read>(rdr: R) -> Result<Vec<Mirror>, BuildError> {
+        serde_json::from_reader(rdr).map_err(|e| BuildError::parse("mirror list", e))
+    }
+
+    /// Borrows the base URI of the mirror.
+    pub fn uri(&self) -> &str {
+        self.uri.as_str()
+    }
+
+    /// Borrows the frequency with which the mirror syncs, e.g., `hourly`.
+    pub fn frequency(&self) -> &str {
+        self.frequency.as_str()
+    }
+
+    /// Borrows the geographic location of the mirror.
+    pub fn location(&self) -> &str {
+        self.location.as_str()
+    }
+
+    /// Borrows the name of the organization hosting the mirror.
+    pub fn organization(&self) -> &str {
+        self.organization.as_str()
+    }
+
+    /// Borrows the time zone of the mirror.
+    pub fn timezone(&self) -> &str {
+        self.timezone.as_str()
+    }
+
+    /// Borrows the contact email address for the mirror, obfuscated with `|`
+    /// in place of `@`.
+    pub fn email(&self) -> &str {
+        self.email.as_str()
+    }
+
+    /// Borrows the URI of the mirror's source, from which it syncs.
+    pub fn src(&self) -> &str {
+        self.src.as_str()
+    }
+
+    /// Borrows the mirror's bandwidth.
+    pub fn bandwidth(&self) -> Option<&str> {
+        self.bandwidth.as_deref()
+    }
+
+    /// Borrows the rsync URI other mirrors may sync from.
+    pub fn rsync(&self) -> Option<&str> {
+        self.rsync.as_deref()
+    }
+
+    /// Borrows notes about the mirror.
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,76 @@
+use super::*;
+use serde_json::json;
+
+#[test]
+fn mirrors() -> Result<(), BuildError> {
+    let json = json!([
+        {
+            "uri": "https://master.pgxn.org/",
+            "frequency": "every minute",
+            "location": "Portland, OR, USA",
+            "bandwidth": "1Gbps",
+            "organization": "PGXN",
+            "timezone": "America/Los_Angeles",
+            "email": "pgxn|pgexperts.com",
+            "src": "rsync://master.pgxn.org/pgxn/",
+            "rsync": "rsync://master.pgxn.org/pgxn/",
+            "notes": "This is the master mirror.",
+        },
+        {
+            "uri": "https://pgxn.example.org/",
+            "frequency": "hourly",
+            "location": "Amsterdam, Netherlands",
+            "organization": "Example",
+            "timezone": "Europe/Amsterdam",
+            "email": "mirrors|example.org",
+            "src": "rsync://master.pgxn.org/pgxn/",
+        },
+    ]);
+    let data = serde_json::to_vec(&json)?;
+    let list = Mirror::list_from_reader(data.as_slice())?;
+    assert_eq!(2, list.len());
+
+    let m = &list[0];
+    assert_eq!("https://master.pgxn.org/", m.uri());
+    assert_eq!("every minute", m.frequency());
+    assert_eq!("Portland, OR, USA", m.location());
+    assert_eq!("PGXN", m.organization());
+    assert_eq!("America/Los_Angeles", m.timezone());
+    assert_eq!("pgxn|pgexperts.com", m.email());
+    assert_eq!("rsync://master.pgxn.org/pgxn/", m.src());
+    assert_eq!(Some("1Gbps"), m.bandwidth());
+    assert_eq!(Some("rsync://master.pgxn.org/pgxn/"), m.rsync());
+    assert_eq!(Some("This is the master mirror."), m.notes());
+
+    let m = &list[1];
+    assert_eq!("https://pgxn.example.org/", m.uri());
+    assert_eq!(None, m.bandwidth());
+    assert_eq!(None, m.rsync());
+    assert_eq!(None, m.notes());
+
+    // Should round-trip.
+    assert_eq!(json, serde_json::to_value(&list)?);
+
+    Ok(())
+}
+
+#[test]
+fn mirrors_err() {
+    for (name, json, err) in [
+        (
+            "not an array",
+            "{}",
+            "cannot parse mirror list: invalid type: map, expected a sequence at line 1 column 1",
+        ),
+        (
+            "missing uri",
+            r#"[{"frequency": "hourly"}]"#,
+            "cannot parse mirror list: missing field `uri` at line 1 column 24",
+        ),
+    ] {
+        match Mirror::list_from_reader(json.as_bytes()) {
+            Ok(_) => panic!("{name} unexpectedly succeeded"),
+            Err(e) => assert_eq!(err, e.to_string(), "{name}"),
+        }
+    }
+}
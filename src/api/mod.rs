@@ -4,20 +4,30 @@ Interface to local and remote PGXN mirrors and the PGXN API.
 
 */
 mod dist;
-pub use dist::{Dist, Release, Releases};
+mod mirror;
+mod stats;
+pub use dist::{Dist, Maturity, Release, Releases};
+pub use mirror::Mirror;
+pub use stats::Summary;
 
-use crate::error::BuildError;
+use crate::error::{BuildError, TooLarge};
 use iri_string::spec;
 use iri_string::template::{simple_context::SimpleContext, UriTemplateStr, UriTemplateString};
 use log::{debug, info, trace};
+use pgxn_meta::dist::Distribution;
 use semver::Version;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
 use std::{
     collections::HashMap,
-    fs::File,
-    io,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read},
     path::{Path, PathBuf},
-    time::Duration,
+    sync::OnceLock,
+    time::{Duration, Instant},
 };
 use url::Url;
 
@@ -44,11 +54,104 @@ macro_rules! type_of {
     }};
 }
 
+/// Stands in for the distribution name when expanding the `dist` template
+/// to find where a `file` mirror stores distribution data.
+const DIST_PLACEHOLDER: &str = "__pgxn_dist__";
+
+/// Default maximum size of metadata responses, such as `index.json` and
+/// `META.json`: 4 MiB.
+pub const MAX_METADATA_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Configuration for an [`Api`].
+#[derive(Clone, PartialEq)]
+pub struct Config {
+    /// Maximum time to wait to connect to the server.
+    pub connect_timeout: Duration,
+
+    /// Maximum time to wait for each read or write while fetching metadata,
+    /// such as `index.json` and `META.json`.
+    pub read_timeout: Duration,
+
+    /// Maximum time to wait to download a distribution archive, including
+    /// the response body.
+    pub download_timeout: Duration,
+
+    /// PEM-encoded PGXN public signing key to use instead of fetching it
+    /// from the mirror. Useful for offline use and private mirrors.
+    pub signing_key: Option<String>,
+
+    /// Directory in which to cache `index.json` from HTTP mirrors. When set,
+    /// [`Api::with_config`] sends a conditional request and uses the cached
+    /// copy if the server reports it unchanged. Caching is disabled when
+    /// [`None`], the default.
+    pub cache_dir: Option<PathBuf>,
+
+    /// Value for the `Authorization` header to send with HTTP requests to
+    /// the Api URL's scheme, host, and port, such as `Bearer <token>` or
+    /// `Basic <credentials>`, for mirrors that require authentication. Never
+    /// sent to other hosts or for `file:` URLs, and never logged.
+    pub authorization: Option<String>,
+
+    /// Validate downloads against every digest in the release metadata and
+    /// report all mismatches in a single [`BuildError::DigestMismatches`].
+    /// When `false`, the default, report only the first mismatch.
+    pub verify_all_digests: bool,
+
+    /// Maximum size in bytes of metadata responses, such as `index.json`
+    /// and `META.json`. Larger responses fail with
+    /// [`BuildError::TooLarge`]. Does not apply to archive downloads.
+    /// Defaults to [`MAX_METADATA_SIZE`].
+    pub max_metadata_size: u64,
+
+    /// Read the proxy from the standard `HTTPS_PROXY`, `HTTP_PROXY`, and
+    /// `ALL_PROXY` environment variables when no proxy is passed to the
+    /// constructor, unless the Api URL host appears in `NO_PROXY`. An
+    /// explicit proxy always wins. Defaults to `false`.
+    pub proxy_from_env: bool,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("connect_timeout", &self.connect_timeout)
+            .field("read_timeout", &self.read_timeout)
+            .field("download_timeout", &self.download_timeout)
+            .field("signing_key", &self.signing_key)
+            .field("cache_dir", &self.cache_dir)
+            .field(
+                "authorization",
+                &self.authorization.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("verify_all_digests", &self.verify_all_digests)
+            .field("max_metadata_size", &self.max_metadata_size)
+            .field("proxy_from_env", &self.proxy_from_env)
+            .finish()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            connect_timeout: Duration::from_secs(30),
+            read_timeout: Duration::from_secs(5),
+            download_timeout: Duration::from_secs(600),
+            signing_key: None,
+            cache_dir: None,
+            authorization: None,
+            verify_all_digests: false,
+            max_metadata_size: MAX_METADATA_SIZE,
+            proxy_from_env: false,
+        }
+    }
+}
+
 /// Interface to the PGXN API.
 pub struct Api {
     url: url::Url,
     agent: ureq::Agent,
     templates: HashMap<String, UriTemplateString>,
+    config: Config,
+    signing_key: OnceLock<String>,
 }
 
 impl Api {
@@ -57,40 +160,140 @@ impl Api {
     /// BuildError::Http if the Proxy URL is invalid. The `url` and `proxy`
     /// values are borrowed only for the duration of this function.
     pub fn new(url: &str, proxy: Option<&str>) -> Result<Api, BuildError> {
-        static APP_USER_AGENT: &str =
-            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
-
-        let mut builder = ureq::AgentBuilder::new()
-            .timeout_read(Duration::from_secs(5))
-            .timeout_write(Duration::from_secs(5))
-            .https_only(true)
-            .user_agent(APP_USER_AGENT);
-
-        if let Some(p) = proxy {
-            builder = builder.proxy(ureq::Proxy::new(p)?);
-        }
+        Api::with_config(url, proxy, Config::default())
+    }
 
+    /// Like [`Api::new`], but configures the Api with `config`.
+    pub fn with_config(url: &str, proxy: Option<&str>, config: Config) -> Result<Api, BuildError> {
         let url = parse_base_url(url)?;
-        let agent = builder.build();
+        let agent = new_agent(&url, proxy, &config)?;
         let idx = url.join("index.json")?;
-        let templates = fetch_templates(&agent, &idx)?;
+        let templates = match &config.cache_dir {
+            Some(dir) => fetch_templates_cached(&agent, &idx, dir, config.max_metadata_size)?,
+            None => fetch_templates(&agent, &idx, config.max_metadata_size)?,
+        };
 
         Ok(Api {
             url,
             agent,
             templates,
+            config,
+            signing_key: OnceLock::new(),
         })
     }
 
+    /// Like [`Api::with_config`], but uses `templates` instead of fetching
+    /// them from the `index.json` file at `url`. Makes no network requests,
+    /// allowing construction of an Api offline. Use [`Api::load_templates`]
+    /// to load templates from a previously downloaded `index.json` file.
+    pub fn with_templates(
+        url: &str,
+        proxy: Option<&str>,
+        config: Config,
+        templates: HashMap<String, UriTemplateString>,
+    ) -> Result<Api, BuildError> {
+        let url = parse_base_url(url)?;
+        Ok(Api {
+            agent: new_agent(&url, proxy, &config)?,
+            url,
+            templates,
+            config,
+            signing_key: OnceLock::new(),
+        })
+    }
+
+    /// Loads URI templates from the `index.json` file at `path`, for use
+    /// with [`Api::with_templates`].
+    pub fn load_templates<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<HashMap<String, UriTemplateString>, BuildError> {
+        let path = path.as_ref();
+        let url = Url::from_file_path(path).map_err(|_| {
+            BuildError::File(
+                "loading",
+                path.display().to_string(),
+                io::ErrorKind::InvalidInput,
+            )
+        })?;
+        fetch_templates(&ureq::agent(), &url, MAX_METADATA_SIZE)
+    }
+
+    /// Checks the health of the mirror at `url` by fetching and parsing its
+    /// `index.json` with the same agent as other requests, and returns the
+    /// time it took. Use to rank mirrors by responsiveness. Returns an error
+    /// if the mirror cannot be reached or returns an invalid index.
+    pub fn check_mirror(&self, url: &str) -> Result<Duration, BuildError> {
+        let idx = parse_base_url(url)?.join("index.json")?;
+        let start = Instant::now();
+        fetch_templates(&self.agent, &idx, self.config.max_metadata_size)?;
+        let latency = start.elapsed();
+        debug!(url:display = Redacted(&idx), latency:? ; "checked mirror");
+        Ok(latency)
+    }
+
     /// Fetch the distribution release data for distribution `name`.
     pub fn dist(&self, name: &str) -> Result<Dist, BuildError> {
         let mut ctx = SimpleContext::new();
         ctx.insert("dist", name);
         let url = self.url_for("dist", ctx)?;
-        let read = fetch_reader(&self.agent, &url)?;
+        let read = fetch_reader(&self.agent, &url, self.config.max_metadata_size)?;
         Dist::from_reader(read)
     }
 
+    /// List the names of all distributions on the mirror, sorted
+    /// alphabetically. The PGXN API provides no listing endpoint, so this
+    /// works only for a `file` mirror, where it lists the files in the
+    /// directory of the `dist` template, whose file name must contain the
+    /// distribution name, such as `{dist}.json`. Returns
+    /// [`BuildError::NoListing`] for HTTP and other mirrors, and for `file`
+    /// mirrors whose `dist` template has no such file name.
+    pub fn list_dists(&self) -> Result<Vec<String>, BuildError> {
+        let mut names = self.list_dist_files()?;
+        names.sort_unstable();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Lists the distribution names in the directory of the `dist`
+    /// template of a `file` mirror.
+    fn list_dist_files(&self) -> Result<Vec<String>, BuildError> {
+        let mut ctx = SimpleContext::new();
+        ctx.insert("dist", DIST_PLACEHOLDER);
+        let url = self.url_for("dist", ctx)?;
+        let no_list = || BuildError::NoListing(url.scheme().to_string());
+        if url.scheme() != "file" {
+            return Err(no_list());
+        }
+        let path = url
+            .to_file_path()
+            .map_err(|_| BuildError::NoUrlFile(url.clone()))?;
+        let (Some(dir), Some(file)) = (path.parent(), path.file_name().and_then(|f| f.to_str()))
+        else {
+            return Err(no_list());
+        };
+        let (prefix, suffix) = file.split_once(DIST_PLACEHOLDER).ok_or_else(no_list)?;
+
+        debug!(dir:display = dir.display(); "listing distributions");
+        let entries = fs::read_dir(dir)
+            .map_err(|e| BuildError::File("reading", dir.display().to_string(), e.kind()))?;
+        Ok(entries
+            .map_while(Result::ok)
+            .filter(|e| e.path().is_file())
+            .filter_map(|e| {
+                let name = e.file_name().into_string().ok()?;
+                let name = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+                (!name.is_empty()).then(|| name.to_string())
+            })
+            .collect())
+    }
+
+    /// Fetch the distribution release data for distribution `name` and
+    /// return all of its versions, sorted from newest to oldest.
+    pub fn versions(&self, name: &str) -> Result<Vec<Version>, BuildError> {
+        let dist = self.dist(name)?;
+        Ok(dist.versions().into_iter().cloned().collect())
+    }
+
     /// Fetch the distribution release metadata for distribution `name`
     /// version `version`.
     pub fn meta(
@@ -102,31 +305,221 @@ impl Api {
         ctx.insert("dist", name);
         ctx.insert("version", version.to_string());
         let url = self.url_for("meta", ctx)?;
-        let mut val = fetch_json(&self.agent, &url)?;
-        debug!(url:display; "parsing");
+        let mut val = fetch_json(&self.agent, &url, self.config.max_metadata_size)?;
+        debug!(url:display = Redacted(&url); "parsing");
         if val.get("meta-spec").is_none() {
             // PGXN v1 stripped meta-spec out of this API :-/.
             let val_type = type_of!(val);
             val.as_object_mut()
-                .ok_or_else(|| BuildError::Type(url.to_string(), "object", val_type))?
+                .ok_or_else(|| BuildError::Type(Redacted(&url).to_string(), "object", val_type))?
                 .insert("meta-spec".to_string(), json!({"version": "1.0.0"}));
         }
         let rel = pgxn_meta::release::Release::try_from(val)?;
         Ok(rel)
     }
 
+    /// Fetch the list of PGXN mirrors.
+    pub fn mirrors(&self) -> Result<Vec<Mirror>, BuildError> {
+        let url = self.url_for("mirrors", SimpleContext::new())?;
+        let read = fetch_reader(&self.agent, &url, self.config.max_metadata_size)?;
+        Mirror::list_from_reader(read)
+    }
+
+    /// Fetch the summary of the contents of the mirror from the `summary`
+    /// document of the `stats` template.
+    pub fn summary(&self) -> Result<Summary, BuildError> {
+        let mut ctx = SimpleContext::new();
+        ctx.insert("stats", "summary");
+        let url = self.url_for("stats", ctx)?;
+        let read = fetch_reader(&self.agent, &url, self.config.max_metadata_size)?;
+        Summary::from_reader(read)
+    }
+
+    /// Fetch the README for version `version` of distribution `name`.
+    pub fn readme(&self, name: &str, version: &Version) -> Result<String, BuildError> {
+        let mut ctx = SimpleContext::new();
+        ctx.insert("dist", name);
+        ctx.insert("version", version.to_string());
+        let url = self.url_for("readme", ctx)?;
+        fetch_text(&self.agent, &url, self.config.max_metadata_size)
+    }
+
+    /// Fetch the PGXN meta spec document in `format`, either `html` or
+    /// `txt`, from the URL for the `spec` template.
+    pub fn spec(&self, format: &str) -> Result<String, BuildError> {
+        if !matches!(format, "html" | "txt") {
+            return Err(BuildError::Invalid("spec format must be html or txt"));
+        }
+        let mut ctx = SimpleContext::new();
+        ctx.insert("format", format);
+        let url = self.url_for("spec", ctx)?;
+        fetch_text(&self.agent, &url, self.config.max_metadata_size)
+    }
+
+    /// Returns the PEM-encoded PGXN public key used to sign releases. Returns
+    /// [`Config::signing_key`] if set. Otherwise fetches the key from the URL
+    /// for the `signing_key` template and caches it for subsequent calls.
+    /// Returns [`BuildError::UnknownTemplate`] if the mirror's `index.json`
+    /// has no `signing_key` template.
+    pub fn signing_key(&self) -> Result<&str, BuildError> {
+        if let Some(key) = &self.config.signing_key {
+            return Ok(key);
+        }
+        if let Some(key) = self.signing_key.get() {
+            return Ok(key);
+        }
+
+        let url = self.url_for("signing_key", SimpleContext::new())?;
+        let read = fetch_reader(&self.agent, &url, self.config.max_metadata_size)?;
+        let key = io::read_to_string(read).map_err(BuildError::io)?;
+        if key.trim().is_empty() {
+            return Err(BuildError::Invalid("empty signing key"));
+        }
+        Ok(self.signing_key.get_or_init(|| key))
+    }
+
     /// Unpack download `file` in directory `into` and return the path to the
-    /// unpacked directory.
+    /// unpacked directory. Returns [`BuildError::UnsafePath`] without
+    /// unpacking anything if any entry in the archive would be written
+    /// outside `into`.
     pub fn unpack<P: AsRef<Path>>(&self, into: P, file: P) -> Result<PathBuf, BuildError> {
         info!(file:display = crate::filename(&file); "unpacking");
         let zip = File::open(file)?;
         let mut archive = zip::ZipArchive::new(zip)?;
-        archive.extract(&into)?;
-        let first = archive
-            .by_index(0)?
-            .enclosed_name()
-            .ok_or_else(|| zip::result::ZipError::FileNotFound)?;
-        Ok(into.as_ref().join(first))
+        extract(&mut archive, into.as_ref(), None)
+    }
+
+    /// Unpacks download `file` for release `meta` like [`Self::unpack`],
+    /// but first verifies that the archive's top-level directory is the
+    /// one returned by [`Self::expected_unpack_dir`], and returns
+    /// [`BuildError::ArchiveRootName`] without unpacking anything if not.
+    pub fn unpack_release<P: AsRef<Path>>(
+        &self,
+        into: P,
+        file: P,
+        meta: &pgxn_meta::release::Release,
+    ) -> Result<PathBuf, BuildError> {
+        info!(file:display = crate::filename(&file); "unpacking");
+        let zip = File::open(file)?;
+        let mut archive = zip::ZipArchive::new(zip)?;
+        let expected = self.expected_unpack_dir(into.as_ref(), meta);
+        extract(&mut archive, into.as_ref(), Some(&expected))
+    }
+
+    /// Returns the path to the directory in `into` that unpacking the
+    /// archive for release `meta` will create, following the PGXN
+    /// convention of naming the top-level directory `$name-$version`. Does
+    /// not download or examine the archive.
+    pub fn expected_unpack_dir<P: AsRef<Path>>(
+        &self,
+        into: P,
+        meta: &pgxn_meta::release::Release,
+    ) -> PathBuf {
+        into.as_ref()
+            .join(format!("{}-{}", meta.name(), meta.version()))
+    }
+
+    /// Download the archive for release `meta` into memory, validate it
+    /// against the digests in `meta`, and unpack it into directory `into`
+    /// without writing the archive to disk. Returns the path to the
+    /// unpacked directory, or [`BuildError::ArchiveRootName`] without
+    /// unpacking anything if the archive's top-level directory is not the
+    /// one returned by [`Self::expected_unpack_dir`].
+    pub fn download_and_unpack<P: AsRef<Path>>(
+        &self,
+        meta: &pgxn_meta::release::Release,
+        into: P,
+    ) -> Result<PathBuf, BuildError> {
+        let mut ctx = SimpleContext::new();
+        ctx.insert("dist", meta.name());
+        ctx.insert("version", meta.version().to_string());
+        let url = self.url_for("download", ctx)?;
+        info!(url:display = Redacted(&url); "downloading");
+        let mut input: Box<dyn io::Read> = match url.scheme() {
+            "file" => Box::new(get_file(&url)?),
+            _ => Box::new(
+                self.agent
+                    .request_url("GET", &url)
+                    .timeout(self.config.download_timeout)
+                    .call()?
+                    .into_reader(),
+            ),
+        };
+
+        // Buffer the archive, since reading a zip file requires seeking.
+        let mut buf = Digester::new(Vec::new(), Some(meta.release().digests()))
+            .verify_all(self.config.verify_all_digests);
+        if let Err(e) = io::copy(&mut input, &mut buf) {
+            return Err(BuildError::File(
+                "reading",
+                Redacted(&url).to_string(),
+                e.kind(),
+            ));
+        }
+        info!(url:display = Redacted(&url); "validating");
+        let data = buf.finish(Redacted(&url))?;
+
+        info!(into:display = into.as_ref().display(); "unpacking");
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(data))?;
+        let expected = self.expected_unpack_dir(into.as_ref(), meta);
+        extract(&mut archive, into.as_ref(), Some(&expected))
+    }
+
+    /// Reads the `META.json` file from the top-level directory of the
+    /// archive `file` without unpacking it.
+    pub fn archive_meta<P: AsRef<Path>>(&self, file: P) -> Result<Distribution, BuildError> {
+        let zip = File::open(file.as_ref())?;
+        let mut archive = zip::ZipArchive::new(zip)?;
+        let name = archive
+            .file_names()
+            .find(|n| {
+                let mut parts = n.split('/');
+                parts.next().is_some()
+                    && parts.next() == Some("META.json")
+                    && parts.next().is_none()
+            })
+            .ok_or(BuildError::MissingFile("META.json"))?
+            .to_string();
+        let entry = archive.by_name(&name)?;
+        let val: Value = serde_json::from_reader(entry)
+            .map_err(|e| BuildError::parse(format!("{name} in {}", file.as_ref().display()), e))?;
+        Ok(Distribution::try_from(val)?)
+    }
+
+    /// Compares the `META.json` file in the archive `file` to the release
+    /// metadata `meta`, as returned by [`Api::meta`], to detect archives
+    /// whose contents diverge from the published metadata. Returns
+    /// [`BuildError::MetaMismatch`] listing the properties that differ.
+    pub fn verify_archive_meta<P: AsRef<Path>>(
+        &self,
+        file: P,
+        meta: &pgxn_meta::release::Release,
+    ) -> Result<(), BuildError> {
+        let dist = self.archive_meta(file)?;
+        let mut diffs = Vec::new();
+        macro_rules! cmp {
+            ($($prop:ident => $name:literal),+) => {$(
+                if dist.$prop() != meta.$prop() {
+                    diffs.push($name.to_string());
+                }
+            )+};
+        }
+        cmp!(
+            name => "name",
+            version => "version",
+            abs_tract => "abstract",
+            description => "description",
+            license => "license",
+            maintainers => "maintainers",
+            classifications => "classifications",
+            contents => "contents",
+            dependencies => "dependencies",
+            resources => "resources"
+        );
+        if diffs.is_empty() {
+            return Ok(());
+        }
+        Err(BuildError::MetaMismatch(diffs))
     }
 
     /// url_for finds the `name` template, evaluates with `ctx`, and returns a
@@ -138,36 +531,95 @@ impl Api {
             .ok_or_else(|| BuildError::UnknownTemplate(name.to_string()))?;
         let path = template.expand::<spec::UriSpec, _>(&ctx)?;
         let url = self.url.join(&path.to_string())?;
-        trace!(url:display; "resolved URL");
+        trace!(url:display = Redacted(&url); "resolved URL");
         Ok(url)
     }
 
     /// Download the archive for release `meta` to `dir` and validate it
-    /// against the digests in `meta`. Returns the full path to the file.
+    /// against the digests in `meta` as it downloads. Returns the full path
+    /// to the file. Resumes a partial download left by a previous attempt,
+    /// but always validates the complete file, including the previously
+    /// downloaded bytes. Deletes the file on validation failure.
+    ///
+    /// When downloading over HTTP, records any `ETag` and `Last-Modified`
+    /// headers in a file next to the archive, named for it with
+    /// `.validators` appended. If the archive already exists, sends them in
+    /// a conditional request and, if the server reports the archive
+    /// unchanged, validates the existing file rather than downloading it
+    /// again.
     pub fn download_to<P: AsRef<Path>>(
         &self,
         dir: P,
         meta: &pgxn_meta::release::Release,
     ) -> Result<PathBuf, BuildError> {
+        self.download_to_with_progress(dir, meta, |_, _| {})
+    }
+
+    /// Like [`Api::download_to`], but calls `progress` as the download
+    /// proceeds with the number of bytes downloaded so far and the total
+    /// size of the file, if known.
+    pub fn download_to_with_progress<P, F>(
+        &self,
+        dir: P,
+        meta: &pgxn_meta::release::Release,
+        progress: F,
+    ) -> Result<PathBuf, BuildError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(u64, Option<u64>),
+    {
         let mut ctx = SimpleContext::new();
         ctx.insert("dist", meta.name());
         ctx.insert("version", meta.version().to_string());
         let url = self.url_for("download", ctx)?;
-        info!(url:display; "downloading");
-        let file = self.download_url_to(dir, url)?;
-        info!(file:display = file.display(); "validating");
-        meta.release().digests().validate(&file)?;
-        Ok(file)
+        info!(url:display = Redacted(&url); "downloading");
+        self.download_url_to(dir, url, Some(meta.release().digests()), progress)
     }
 
-    /// Download `url` to `dir`. The file name must be the last segment of the
-    /// URL. Returns the full path to the file.
-    fn download_url_to<P: AsRef<Path>>(
+    /// Like [`Api::download_to`], but calls `verify` with the path to the
+    /// downloaded and digest-validated archive and `meta` before accepting
+    /// it. An opt-in hook for high-assurance environments, for example to
+    /// check that the release signature is recorded in a Sigstore-style
+    /// transparency log. Deletes the archive and returns the error from
+    /// `verify` if it fails; verifiers should report rejections as
+    /// [`BuildError::Unverified`].
+    pub fn download_to_verified<P, V>(
+        &self,
+        dir: P,
+        meta: &pgxn_meta::release::Release,
+        verify: V,
+    ) -> Result<PathBuf, BuildError>
+    where
+        P: AsRef<Path>,
+        V: FnOnce(&Path, &pgxn_meta::release::Release) -> Result<(), BuildError>,
+    {
+        let path = self.download_to(dir, meta)?;
+        info!(file:display = crate::filename(&path); "verifying");
+        if let Err(e) = verify(&path, meta) {
+            let _ = fs::remove_file(&path);
+            let _ = fs::remove_file(validators_path(&path));
+            return Err(e);
+        }
+        Ok(path)
+    }
+
+    /// Download `url` to `dir`, calling `progress` with the number of bytes
+    /// downloaded so far and the total size of the file, if known. The file
+    /// name must be the last segment of the URL. If `digests` is not
+    /// [`None`], validates the file against them as it downloads and deletes
+    /// it on failure. Returns the full path to the file.
+    fn download_url_to<P, F>(
         &self,
         dir: P,
         url: url::Url,
-    ) -> Result<PathBuf, BuildError> {
-        trace!( url:display, dir:display = dir.as_ref().display(); "downloading");
+        digests: Option<&pgxn_meta::release::Digests>,
+        progress: F,
+    ) -> Result<PathBuf, BuildError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(u64, Option<u64>),
+    {
+        trace!(url:display = Redacted(&url), dir:display = dir.as_ref().display(); "downloading");
         // Extract the file name from the URL.
         match url.path_segments() {
             None => Err(BuildError::NoUrlFile(url))?,
@@ -178,43 +630,665 @@ impl Api {
                 if filename.is_empty() {
                     return Err(BuildError::NoUrlFile(url));
                 }
+                // Write to a temporary file in the same directory, and
+                // rename it into place only once complete and valid.
                 let dst = dir.as_ref().join(filename);
+                let part = part_path(&dst);
 
                 if url.scheme() == "file" {
                     // Copy the file. Eschew std::fs::copy for better
                     // error messages.
-                    let mut input = get_file(&url)?;
-                    return match File::create(&dst) {
+                    let input = get_file(&url)?;
+                    let total = input.metadata().ok().map(|m| m.len());
+                    let mut input = Progress::new(input, 0, total, progress);
+                    return match File::create(&part) {
                         Err(e) => Err(BuildError::File(
                             "creating",
-                            dst.display().to_string(),
+                            part.display().to_string(),
                             e.kind(),
                         )),
-                        Ok(mut out) => match io::copy(&mut input, &mut out) {
-                            Ok(_) => Ok(dst),
-                            Err(e) => copy_err!(url.to_file_path().unwrap().display(), dst, e),
-                        },
+                        Ok(out) => {
+                            let mut out = Digester::new(out, digests)
+                                .verify_all(self.config.verify_all_digests);
+                            match io::copy(&mut input, &mut out) {
+                                Ok(_) => finish(out, &part, dst),
+                                Err(e) => {
+                                    let _ = fs::remove_file(&part);
+                                    copy_err!(url.to_file_path().unwrap().display(), part, e)
+                                }
+                            }
+                        }
                     };
                 }
 
-                // Download the file over HTTP.
-                let res = self.agent.request_url("GET", &url).call()?;
-                match File::create(&dst) {
+                // Download the file over HTTP, allowing more time than the
+                // metadata requests. Resume a partial download if one
+                // exists.
+                let mut offset = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+                let get = || {
+                    self.agent
+                        .request_url("GET", &url)
+                        .timeout(self.config.download_timeout)
+                };
+                let cached = match offset {
+                    0 => Validators::load(&dst, &url),
+                    _ => None,
+                };
+                let res = if offset > 0 {
+                    debug!(file:display = part.display(), offset; "resuming download");
+                    match get().set("Range", &format!("bytes={offset}-")).call() {
+                        // The partial file is no good; start over.
+                        Err(ureq::Error::Status(416, _)) => get().call()?,
+                        res => res?,
+                    }
+                } else if let Some(v) = cached {
+                    let res = v.apply(get()).call()?;
+                    if res.status() != 304 {
+                        res
+                    } else {
+                        // Unchanged; make sure the cached file is intact.
+                        debug!(file:display = dst.display(); "validating cached download");
+                        match verify_file(&dst, digests, self.config.verify_all_digests) {
+                            Ok(_) => return Ok(dst),
+                            Err(e) => {
+                                info!(file:display = dst.display(), error:display = e; "invalid cached download");
+                                get().call()?
+                            }
+                        }
+                    }
+                } else {
+                    get().call()?
+                };
+
+                // Append only if the server returned the requested range.
+                if res.status() != 206 {
+                    offset = 0;
+                }
+                let validators = Validators::from_response(&url, &res);
+                let total = res
+                    .header("Content-Length")
+                    .and_then(|len| len.parse::<u64>().ok())
+                    .map(|len| len + offset);
+                let mut input = Progress::new(res.into_reader(), offset, total, progress);
+                let out = if offset > 0 {
+                    OpenOptions::new().append(true).open(&part)
+                } else {
+                    File::create(&part)
+                };
+                match out {
                     Err(e) => Err(BuildError::File(
                         "creating",
-                        dst.display().to_string(),
+                        part.display().to_string(),
                         e.kind(),
                     )),
-                    Ok(mut out) => match io::copy(&mut res.into_reader(), &mut out) {
-                        Ok(_) => Ok(dst),
-                        Err(e) => copy_err!(url, dst, e),
-                    },
+                    Ok(out) => {
+                        let mut out =
+                            Digester::new(out, digests).verify_all(self.config.verify_all_digests);
+                        if offset > 0 {
+                            // Hash the previously downloaded bytes, too.
+                            if let Err(e) = File::open(&part).and_then(|f| out.prefix(f)) {
+                                return Err(BuildError::File(
+                                    "reading",
+                                    part.display().to_string(),
+                                    e.kind(),
+                                ));
+                            }
+                        }
+                        match io::copy(&mut input, &mut out) {
+                            Ok(_) => finish(out, &part, dst).inspect(|dst| match validators {
+                                Some(v) => v.save(dst),
+                                None => {
+                                    let _ = fs::remove_file(validators_path(dst));
+                                }
+                            }),
+                            // Keep the partial file to resume next time.
+                            Err(e) => copy_err!(Redacted(&url), part, e),
+                        }
+                    }
                 }
             }
         }
     }
 }
 
+/// Validators records the HTTP cache validators for a downloaded file, so
+/// that a later download can send a conditional request.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Validators {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Returns the Validators in the `res` response for `url`, or [`None`]
+    /// if it has neither an `ETag` nor a `Last-Modified` header.
+    fn from_response(url: &url::Url, res: &ureq::Response) -> Option<Self> {
+        let etag = res.header("ETag").map(String::from);
+        let last_modified = res.header("Last-Modified").map(String::from);
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(Validators {
+            url: url.to_string(),
+            etag,
+            last_modified,
+        })
+    }
+
+    /// Loads the Validators recorded for `dst` when it was downloaded from
+    /// `url`. Returns [`None`] if `dst` does not exist or no validators for
+    /// `url` were recorded.
+    fn load(dst: &Path, url: &url::Url) -> Option<Self> {
+        if !dst.exists() {
+            return None;
+        }
+        let data = fs::read(validators_path(dst)).ok()?;
+        serde_json::from_slice::<Self>(&data)
+            .ok()
+            .filter(|v| v.url == url.as_str())
+    }
+
+    /// Records the Validators for `dst`. Logs but otherwise ignores
+    /// failure, since the validators are only an optimization.
+    fn save(&self, dst: &Path) {
+        let path = validators_path(dst);
+        let res = serde_json::to_vec(self)
+            .map_err(io::Error::from)
+            .and_then(|data| fs::write(&path, data));
+        if let Err(e) = res {
+            info!(file:display = path.display(), error:display = e; "cannot write validators");
+        }
+    }
+
+    /// Sets the conditional request headers for the Validators on `req`.
+    fn apply(&self, mut req: ureq::Request) -> ureq::Request {
+        if let Some(etag) = &self.etag {
+            req = req.set("If-None-Match", etag);
+        }
+        if let Some(modified) = &self.last_modified {
+            req = req.set("If-Modified-Since", modified);
+        }
+        req
+    }
+}
+
+/// Returns the path to the validators file for `dst`: the same path with
+/// `.validators` appended.
+fn validators_path(dst: &Path) -> PathBuf {
+    let mut path = dst.as_os_str().to_owned();
+    path.push(".validators");
+    PathBuf::from(path)
+}
+
+/// Validates the contents of the file at `path` against `digests`,
+/// reporting every mismatch if `all` is true.
+fn verify_file(
+    path: &Path,
+    digests: Option<&pgxn_meta::release::Digests>,
+    all: bool,
+) -> Result<(), BuildError> {
+    let mut out = Digester::new(io::sink(), digests).verify_all(all);
+    File::open(path)
+        .and_then(|mut f| io::copy(&mut f, &mut out))
+        .map_err(|e| BuildError::File("reading", path.display().to_string(), e.kind()))?;
+    out.finish(path.display()).map(|_| ())
+}
+
+/// Returns the path to use for a partial download of `dst`: the same path
+/// with `.part` appended.
+fn part_path(dst: &Path) -> PathBuf {
+    let mut part = dst.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Progress wraps a reader and reports the number of bytes read so far,
+/// along with the expected total, to a callback.
+struct Progress<R, F> {
+    reader: R,
+    read: u64,
+    total: Option<u64>,
+    callback: F,
+}
+
+impl<R: io::Read, F: FnMut(u64, Option<u64>)> Progress<R, F> {
+    /// Creates a new Progress that reads from `reader` and reports to
+    /// `callback`. Pass the number of bytes already read, if any, in `start`,
+    /// and the expected size in `total`, if known.
+    fn new(reader: R, start: u64, total: Option<u64>, callback: F) -> Self {
+        Progress {
+            reader,
+            read: start,
+            total,
+            callback,
+        }
+    }
+}
+
+impl<R: io::Read, F: FnMut(u64, Option<u64>)> io::Read for Progress<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        if n > 0 {
+            self.read += n as u64;
+            (self.callback)(self.read, self.total);
+        }
+        Ok(n)
+    }
+}
+
+/// Limited wraps a reader and fails with an [`io::Error`] wrapping
+/// [`TooLarge`] once more than `limit` bytes have been read.
+struct Limited<R> {
+    reader: R,
+    url: String,
+    limit: u64,
+    remaining: u64,
+}
+
+impl<R: io::Read> Limited<R> {
+    /// Creates a new Limited that reads no more than `limit` bytes of `url`
+    /// from `reader`.
+    fn new(reader: R, url: &url::Url, limit: u64) -> Self {
+        Limited {
+            reader,
+            url: Redacted(url).to_string(),
+            limit,
+            remaining: limit,
+        }
+    }
+}
+
+impl<R: io::Read> io::Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Read one extra byte to detect content beyond the limit.
+        let max = buf
+            .len()
+            .min(usize::try_from(self.remaining.saturating_add(1)).unwrap_or(usize::MAX));
+        let n = self.reader.read(&mut buf[..max])?;
+        if n as u64 > self.remaining {
+            return Err(io::Error::other(TooLarge(self.url.clone(), self.limit)));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Digester wraps a writer and computes the digests of the bytes written
+/// through it, for comparison to the digests in release metadata. It
+/// computes only the digests present in the metadata.
+struct Digester<'a, W> {
+    writer: W,
+    digests: Option<&'a pgxn_meta::release::Digests>,
+    all: bool,
+    sha512: Option<Sha512>,
+    sha256: Option<Sha256>,
+    sha1: Option<Sha1>,
+}
+
+impl<'a, W: io::Write> Digester<'a, W> {
+    /// Creates a new Digester that writes to `writer` and computes the
+    /// algorithms in `digests`. Computes nothing if `digests` is [`None`].
+    fn new(writer: W, digests: Option<&'a pgxn_meta::release::Digests>) -> Self {
+        Digester {
+            writer,
+            digests,
+            all: false,
+            sha512: digests.and_then(|d| d.sha512()).map(|_| Sha512::new()),
+            sha256: digests.and_then(|d| d.sha256()).map(|_| Sha256::new()),
+            sha1: digests.and_then(|d| d.sha1()).map(|_| Sha1::new()),
+        }
+    }
+
+    /// Reports every mismatch from [`Self::validate`] if `all` is true,
+    /// rather than only the first.
+    fn verify_all(mut self, all: bool) -> Self {
+        self.all = all;
+        self
+    }
+
+    /// Adds the contents of `reader` to the digests without writing them.
+    /// Use for data already written by a previous attempt.
+    fn prefix<R: io::Read>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut buf = [0; 8192];
+        loop {
+            match reader.read(&mut buf)? {
+                0 => return Ok(()),
+                n => self.update(&buf[..n]),
+            }
+        }
+    }
+
+    /// Adds `data` to each of the digests.
+    fn update(&mut self, data: &[u8]) {
+        if let Some(h) = self.sha512.as_mut() {
+            h.update(data);
+        }
+        if let Some(h) = self.sha256.as_mut() {
+            h.update(data);
+        }
+        if let Some(h) = self.sha1.as_mut() {
+            h.update(data);
+        }
+    }
+
+    /// Validates the digests of `file` and returns the wrapped writer.
+    fn finish<F: fmt::Display>(mut self, file: F) -> Result<W, BuildError> {
+        self.validate(file)?;
+        Ok(self.writer)
+    }
+
+    /// Compares the computed digests of `file` to those passed to
+    /// [`Self::new`]. Returns an error for the first mismatch, preferring
+    /// SHA-512, then SHA-256, then SHA-1, unless [`Self::verify_all`] is
+    /// set, in which case it returns [`BuildError::DigestMismatches`] for
+    /// two or more mismatches.
+    fn validate<F: fmt::Display>(&mut self, file: F) -> Result<(), BuildError> {
+        let Some(digests) = self.digests else {
+            return Ok(());
+        };
+        let results = [
+            (
+                "SHA-512",
+                self.sha512.take().map(|h| h.finalize().to_vec()),
+                digests.sha512().map(|d| &d[..]),
+            ),
+            (
+                "SHA-256",
+                self.sha256.take().map(|h| h.finalize().to_vec()),
+                digests.sha256().map(|d| &d[..]),
+            ),
+            (
+                "SHA-1",
+                self.sha1.take().map(|h| h.finalize().to_vec()),
+                digests.sha1().map(|d| &d[..]),
+            ),
+        ];
+        let mut mismatches = Vec::new();
+        for (alg, hash, exp) in results {
+            if let (Some(hash), Some(exp)) = (hash, exp) {
+                if hash != exp {
+                    mismatches.push((alg, hex::encode(exp), hex::encode(hash)));
+                    if !self.all {
+                        break;
+                    }
+                }
+            }
+        }
+        if mismatches.len() > 1 {
+            return Err(BuildError::DigestMismatches {
+                file: file.to_string(),
+                mismatches,
+            });
+        }
+        match mismatches.pop() {
+            None => Ok(()),
+            Some((algorithm, expected, actual)) => Err(BuildError::DigestMismatch {
+                algorithm,
+                expected,
+                actual,
+                file: file.to_string(),
+            }),
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for Digester<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.writer.write(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Validates the digests computed by `out` for the temporary file `part`
+/// and renames it to `dst`. Deletes `part` on failure.
+fn finish(mut out: Digester<File>, part: &Path, dst: PathBuf) -> Result<PathBuf, BuildError> {
+    if out.digests.is_some() {
+        info!(file:display = part.display(); "validating");
+    }
+    let res = out.validate(dst.display()).and_then(|_| {
+        fs::rename(part, &dst).map_err(|e| {
+            BuildError::File(
+                "renaming",
+                format!("{} to {}", part.display(), dst.display()),
+                e.kind(),
+            )
+        })
+    });
+    if res.is_err() {
+        let _ = fs::remove_file(part);
+    }
+    res.map(|_| dst)
+}
+
+/// Extracts all of the entries in `archive` into `into` and returns the
+/// path to the top-level directory that contains them. Unlike
+/// [`zip::ZipArchive::extract`], it validates every entry name before
+/// writing anything, refuses to write any file whose canonical parent
+/// directory falls outside `into`, and returns
+/// [`BuildError::ArchiveRoot`] unless every entry falls under a single
+/// top-level directory, and [`BuildError::ArchiveRootName`] if `expected`
+/// is set and that directory is not `expected`.
+fn extract<R: io::Read + io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    into: &Path,
+    expected: Option<&Path>,
+) -> Result<PathBuf, BuildError> {
+    // Make sure no entry tries to escape `into` or is a symlink that could
+    // redirect a later write.
+    let mut paths = Vec::with_capacity(archive.len());
+    let mut roots: Vec<String> = Vec::new();
+    let mut root_is_dir = false;
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        if entry.is_symlink() {
+            return Err(BuildError::Symlink(entry.name().to_string()));
+        }
+        let Some(name) = entry.enclosed_name() else {
+            return Err(BuildError::UnsafePath(entry.name().to_string()));
+        };
+        let mut parts = name.components();
+        if let Some(first) = parts.next() {
+            let first = first.as_os_str().to_string_lossy().to_string();
+            if !roots.contains(&first) {
+                roots.push(first);
+            }
+            root_is_dir |= entry.is_dir() || parts.next().is_some();
+        }
+        paths.push(into.join(name));
+    }
+
+    // Require a single top-level directory.
+    if roots.len() != 1 || !root_is_dir {
+        return Err(BuildError::ArchiveRoot(roots));
+    }
+    let top = into.join(&roots[0]);
+    if let Some(expected) = expected {
+        if top != expected {
+            return Err(BuildError::ArchiveRootName(
+                roots.swap_remove(0),
+                crate::filename(expected),
+            ));
+        }
+    }
+
+    let root = into
+        .canonicalize()
+        .map_err(|e| BuildError::File("opening", into.display().to_string(), e.kind()))?;
+    for (i, path) in paths.into_iter().enumerate() {
+        let mut entry = archive.by_index(i)?;
+        trace!(file:display = entry.name(); "extracting");
+        let dir = if entry.is_dir() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or(into)
+        };
+        if let Err(e) = fs::create_dir_all(dir) {
+            return Err(BuildError::File(
+                "creating",
+                dir.display().to_string(),
+                e.kind(),
+            ));
+        }
+
+        // Check the resolved directory, in case of symlinks.
+        match dir.canonicalize() {
+            Ok(d) if d.starts_with(&root) => {}
+            _ => return Err(BuildError::UnsafePath(entry.name().to_string())),
+        }
+        if entry.is_dir() {
+            continue;
+        }
+
+        match File::create(&path) {
+            Err(e) => {
+                return Err(BuildError::File(
+                    "creating",
+                    path.display().to_string(),
+                    e.kind(),
+                ))
+            }
+            Ok(mut out) => {
+                if let Err(e) = io::copy(&mut entry, &mut out) {
+                    return copy_err!(entry.name(), path, e);
+                }
+            }
+        }
+
+        // Keep only the permission bits: never setuid, setgid, or sticky.
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+        }
+    }
+
+    Ok(top)
+}
+
+/// Builds the agent for an Api at `url`, configured with the timeouts and
+/// authorization in `config` and to proxy requests through `proxy`, or
+/// through the proxy in the environment if `config.proxy_from_env` is true.
+/// Returns a BuildError::Http if the proxy URL is invalid.
+fn new_agent(
+    url: &url::Url,
+    proxy: Option<&str>,
+    config: &Config,
+) -> Result<ureq::Agent, BuildError> {
+    static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+    let mut builder = ureq::AgentBuilder::new()
+        .timeout_connect(config.connect_timeout)
+        .timeout_read(config.read_timeout)
+        .timeout_write(config.read_timeout)
+        .https_only(true)
+        .user_agent(APP_USER_AGENT);
+
+    let env = match proxy {
+        None if config.proxy_from_env => env_proxy(url),
+        _ => None,
+    };
+    if let Some(p) = proxy.or(env.as_deref()) {
+        builder = builder.proxy(ureq::Proxy::new(p)?);
+    }
+
+    Ok(authorize(builder, url, config).build())
+}
+
+/// Returns the proxy set in the environment for `url`, if any. Reads
+/// `HTTPS_PROXY` or `HTTP_PROXY`, depending on the URL scheme, falling back
+/// on `ALL_PROXY`, and checking the uppercase and then lowercase name of
+/// each. Returns [`None`] if the URL host matches an entry in `NO_PROXY`.
+fn env_proxy(url: &url::Url) -> Option<String> {
+    let var = |name: &str| {
+        [name.to_uppercase(), name.to_string()]
+            .into_iter()
+            .filter_map(|n| std::env::var(n).ok())
+            .find(|v| !v.trim().is_empty())
+    };
+
+    let host = url.host_str()?;
+    if let Some(no_proxy) = var("no_proxy") {
+        if no_proxy.split(',').any(|entry| no_proxy_match(entry, host)) {
+            debug!(url:display = Redacted(url); "NO_PROXY excludes url");
+            return None;
+        }
+    }
+
+    let name = match url.scheme() {
+        "https" => "https_proxy",
+        "http" => "http_proxy",
+        _ => return None,
+    };
+    var(name).or_else(|| var("all_proxy"))
+}
+
+/// Returns true if `host` matches the `NO_PROXY` `entry`. An entry matches
+/// the host itself and any of its subdomains; `*` matches all hosts.
+fn no_proxy_match(entry: &str, host: &str) -> bool {
+    let entry = entry.trim();
+    // Ignore any port.
+    let entry = match entry.rsplit_once(':') {
+        Some((h, p)) if !h.contains(':') && p.chars().all(|c| c.is_ascii_digit()) => h,
+        _ => entry,
+    };
+    let entry = entry.trim_start_matches('.');
+    if entry.is_empty() {
+        return false;
+    }
+    entry == "*"
+        || host.eq_ignore_ascii_case(entry)
+        || host.len() > entry.len()
+            && host.as_bytes()[host.len() - entry.len() - 1] == b'.'
+            && host[host.len() - entry.len()..].eq_ignore_ascii_case(entry)
+}
+
+/// Adds middleware to `builder` that sets the `Authorization` header to the
+/// value in `config`, if any, on requests to the scheme, host, and port of
+/// `url`. Requests to other origins, such as mirrors passed to
+/// [`Api::check_mirror`], never carry the header.
+// ureq::Middleware dictates the closure's large ureq::Error.
+#[allow(clippy::result_large_err)]
+fn authorize(builder: ureq::AgentBuilder, url: &url::Url, config: &Config) -> ureq::AgentBuilder {
+    match &config.authorization {
+        None => builder,
+        Some(auth) => {
+            let auth = auth.clone();
+            let origin = url.origin();
+            builder.middleware(move |req: ureq::Request, next: ureq::MiddlewareNext| {
+                match url::Url::parse(req.url()) {
+                    Ok(u) if u.origin() == origin => next.handle(req.set("Authorization", &auth)),
+                    _ => next.handle(req),
+                }
+            })
+        }
+    }
+}
+
+/// Redacted displays a URL with any username and password replaced with
+/// `***`, for logging URLs that may contain credentials.
+pub(crate) struct Redacted<'a>(pub(crate) &'a url::Url);
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.username().is_empty() && self.0.password().is_none() {
+            return self.0.fmt(f);
+        }
+        let mut url = self.0.clone();
+        // These only fail for URLs that cannot have credentials.
+        let _ = url.set_username("***");
+        if url.password().is_some() {
+            let _ = url.set_password(Some("***"));
+        }
+        url.fmt(f)
+    }
+}
+
 /// parse_base_url parses `url` into a [`url::Url`], ensuring that it always
 /// ends in a slash, so that it can be properly used as a base URL.
 fn parse_base_url(url: &str) -> Result<url::Url, url::ParseError> {
@@ -226,31 +1300,47 @@ fn parse_base_url(url: &str) -> Result<url::Url, url::ParseError> {
     }
 }
 
-/// Fetches the JSON at URL and converts it to a serde_json::Value.
-fn fetch_json(agent: &ureq::Agent, url: &url::Url) -> Result<Value, BuildError> {
-    debug!(url:display; "fetching");
-    match url.scheme() {
-        "file" => Ok(serde_json::from_reader(get_file(url)?)?),
-        // Avoid .into_json(); it returns IO errors.
-        "http" | "https" => Ok(serde_json::from_reader(
-            agent.request_url("GET", url).call()?.into_reader(),
-        )?),
-        s => Err(BuildError::Scheme(s.to_string())),
+/// Fetches the JSON at URL and converts it to a serde_json::Value. Returns
+/// [`BuildError::TooLarge`] if the JSON exceeds `limit` bytes.
+fn fetch_json(agent: &ureq::Agent, url: &url::Url, limit: u64) -> Result<Value, BuildError> {
+    let read = fetch_reader(agent, url, limit)?;
+    serde_json::from_reader(read).map_err(|e| BuildError::parse(Redacted(url), e))
+}
+
+/// Fetches the UTF-8 text content at `url`. Returns an error if it exceeds
+/// `limit` bytes or is not valid UTF-8.
+fn fetch_text(agent: &ureq::Agent, url: &url::Url, limit: u64) -> Result<String, BuildError> {
+    let mut text = String::new();
+    let mut read = fetch_reader(agent, url, limit)?;
+    if let Err(e) = read.read_to_string(&mut text) {
+        return match BuildError::io(e) {
+            BuildError::Io(e) => Err(BuildError::File(
+                "reading",
+                Redacted(url).to_string(),
+                e.kind(),
+            )),
+            e => Err(e),
+        };
     }
+    Ok(text)
 }
 
-/// Fetches the JSON at URL and converts it to a serde_json::Value.
+/// Fetches the content at URL and returns a reader. The reader returns an
+/// [`io::Error`] wrapping [`TooLarge`] if the content exceeds
+/// `limit` bytes.
 fn fetch_reader(
     agent: &ureq::Agent,
     url: &url::Url,
+    limit: u64,
 ) -> Result<Box<dyn io::Read + Send + Sync + 'static>, BuildError> {
-    debug!(url:display; "fetching");
-    match url.scheme() {
-        "file" => Ok(Box::new(get_file(url)?)),
+    debug!(url:display = Redacted(url); "fetching");
+    let read: Box<dyn io::Read + Send + Sync + 'static> = match url.scheme() {
+        "file" => Box::new(get_file(url)?),
         // Avoid .into_json(); it returns IO errors.
-        "http" | "https" => Ok(agent.request_url("GET", url).call()?.into_reader()),
-        s => Err(BuildError::Scheme(s.to_string())),
-    }
+        "http" | "https" => agent.request_url("GET", url).call()?.into_reader(),
+        s => return Err(BuildError::Scheme(s.to_string())),
+    };
+    Ok(Box::new(Limited::new(read, url, limit)))
 }
 
 /// Opens a the file on disk that `url` points to. The scheme in `url` must be
@@ -282,17 +1372,102 @@ fn get_file(url: &url::Url) -> Result<File, BuildError> {
 fn fetch_templates(
     agent: &ureq::Agent,
     url: &url::Url,
+    limit: u64,
+) -> Result<HashMap<String, UriTemplateString>, BuildError> {
+    let val = fetch_json(agent, url, limit)?;
+    parse_templates(url, &val)
+}
+
+/// Like [`fetch_templates`], but caches the `index.json` file from an HTTP
+/// `url` in `dir` along with its `ETag` and `Last-Modified` headers, and
+/// uses the cached copy when the server responds to a conditional request
+/// with `304 Not Modified`. Ignores a missing or corrupt cache file and logs
+/// failures to write the cache.
+fn fetch_templates_cached(
+    agent: &ureq::Agent,
+    url: &url::Url,
+    dir: &Path,
+    limit: u64,
+) -> Result<HashMap<String, UriTemplateString>, BuildError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return fetch_templates(agent, url, limit);
+    }
+
+    // Load the cache file.
+    let path = cache_path(dir, url);
+    let cached = fs::read(&path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<Value>(&data).ok())
+        .filter(|c| c["url"] == url.as_str() && c["index"].is_object());
+    if cached.is_none() && path.exists() {
+        debug!(file:display = path.display(); "ignoring corrupt cache");
+    }
+
+    // Send a conditional request if the cache has validators.
+    debug!(url:display = Redacted(url); "fetching");
+    let mut req = agent.request_url("GET", url);
+    if let Some(c) = &cached {
+        if let Some(etag) = c["etag"].as_str() {
+            req = req.set("If-None-Match", etag);
+        }
+        if let Some(modified) = c["last_modified"].as_str() {
+            req = req.set("If-Modified-Since", modified);
+        }
+    }
+    let res = req.call()?;
+    if res.status() == 304 {
+        if let Some(c) = cached {
+            debug!(file:display = path.display(); "using cached index");
+            return parse_templates(url, &c["index"]);
+        }
+    }
+
+    // Parse the new index and cache it.
+    let etag = res.header("ETag").map(String::from);
+    let modified = res.header("Last-Modified").map(String::from);
+    let read = Limited::new(res.into_reader(), url, limit);
+    let val: Value =
+        serde_json::from_reader(read).map_err(|e| BuildError::parse(Redacted(url), e))?;
+    let templates = parse_templates(url, &val)?;
+    if etag.is_some() || modified.is_some() {
+        let cache = json!({
+            "url": url.as_str(),
+            "etag": etag,
+            "last_modified": modified,
+            "index": val,
+        });
+        if let Err(e) = fs::create_dir_all(dir).and_then(|_| fs::write(&path, cache.to_string())) {
+            info!(file:display = path.display(), error:display = e; "cannot write cache");
+        }
+    }
+
+    Ok(templates)
+}
+
+/// Returns the path to the cache file in `dir` for `url`, named for the
+/// SHA-256 hash of the URL.
+fn cache_path(dir: &Path, url: &url::Url) -> PathBuf {
+    dir.join(format!(
+        "{}.json",
+        hex::encode(Sha256::digest(url.as_str()))
+    ))
+}
+
+/// Converts the `index.json` data in `val` fetched from `url` into a
+/// HashMap with template names pointing to UriTemplateString values.
+fn parse_templates(
+    url: &url::Url,
+    val: &Value,
 ) -> Result<HashMap<String, UriTemplateString>, BuildError> {
-    let val = fetch_json(agent, url)?;
     let obj = val
         .as_object()
-        .ok_or_else(|| BuildError::Type(url.to_string(), "object", type_of!(val)))?;
+        .ok_or_else(|| BuildError::Type(Redacted(url).to_string(), "object", type_of!(val)))?;
 
     let mut map: HashMap<String, UriTemplateString> = HashMap::with_capacity(obj.len());
     for (k, v) in obj.into_iter() {
         let str = v.as_str().ok_or_else(|| {
             BuildError::Type(
-                format!("template {} in {}", json!(k), url),
+                format!("template {} in {}", json!(k), Redacted(url)),
                 "string",
                 type_of!(val),
             )
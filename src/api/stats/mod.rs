@@ -0,0 +1,62 @@
+//! PGXN [Stats API].
+//!
+//! [Stats API]: https://github.com/pgxn/pgxn-api/wiki/stats-api
+
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::error::BuildError;
+
+/// Summarizes the contents of a PGXN mirror. Loaded from the PGXN
+/// [Stats API] `summary` document.
+///
+///  [Stats API]: https://github.com/pgxn/pgxn-api/wiki/stats-api
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub struct Summary {
+    dists: u64,
+    releases: u64,
+    extensions: u64,
+    users: u64,
+    tags: u64,
+    mirrors: u64,
+}
+
+impl Summary {
+    /// Loads a [`Summary`] from an [`std::io::Read`].
+    pub fn from_reader<R: io::Read>(rdr: R) -> Result<Summary, BuildError> {
+        serde_json::from_reader(rdr).map_err(|e| BuildError::parse("stats summary", e))
+    }
+
+    /// Returns the number of distributions.
+    pub fn dists(&self) -> u64 {
+        self.dists
+    }
+
+    /// Returns the number of releases of all distributions.
+    pub fn releases(&self) -> u64 {
+        self.releases
+    }
+
+    /// Returns the number of extensions.
+    pub fn extensions(&self) -> u64 {
+        self.extensions
+    }
+
+    /// Returns the number of users.
+    pub fn users(&self) -> u64 {
+        self.users
+    }
+
+    /// Returns the number of tags.
+    pub fn tags(&self) -> u64 {
+        self.tags
+    }
+
+    /// Returns the number of mirrors.
+    pub fn mirrors(&self) -> u64 {
+        self.mirrors
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,53 @@
+use super::*;
+use serde_json::json;
+
+#[test]
+fn summary() -> Result<(), BuildError> {
+    let json = json!({
+        "dists": 82,
+        "releases": 175,
+        "extensions": 121,
+        "users": 71,
+        "tags": 219,
+        "mirrors": 8,
+    });
+    let data = serde_json::to_vec(&json)?;
+    let sum = Summary::from_reader(data.as_slice())?;
+    assert_eq!(82, sum.dists());
+    assert_eq!(175, sum.releases());
+    assert_eq!(121, sum.extensions());
+    assert_eq!(71, sum.users());
+    assert_eq!(219, sum.tags());
+    assert_eq!(8, sum.mirrors());
+
+    // Should round-trip.
+    assert_eq!(json, serde_json::to_value(sum)?);
+
+    Ok(())
+}
+
+#[test]
+fn summary_err() {
+    for (name, json, err) in [
+        (
+            "string",
+            r#""hi""#,
+            "cannot parse stats summary: invalid type: string \"hi\", expected struct Summary at line 1 column 4",
+        ),
+        (
+            "missing",
+            r#"{"dists": 1}"#,
+            "cannot parse stats summary: missing field `releases` at line 1 column 12",
+        ),
+        (
+            "negative",
+            r#"{"dists": -1}"#,
+            "cannot parse stats summary: invalid value: integer `-1`, expected u64 at line 1 column 13",
+        ),
+    ] {
+        match Summary::from_reader(json.as_bytes()) {
+            Ok(_) => panic!("{name} unexpectedly succeeded"),
+            Err(e) => assert_eq!(err, e.to_string(), "{name}"),
+        }
+    }
+}
@@ -30,21 +30,40 @@ pub enum BuildError {
     Url(#[from] url::ParseError),
 
     /// URL lacks a file name segment.
-    #[error("missing file name segment from {0}")]
+    #[error("missing file name segment from {}", crate::api::Redacted(.0))]
     NoUrlFile(url::Url),
 
     /// URL scheme Error.
     #[error("unsupported URL scheme: {0}")]
     Scheme(String),
 
-    /// HTTP error.
+    /// HTTP error not covered by [`BuildError::HttpStatus`],
+    /// [`BuildError::Connect`], or [`BuildError::Transport`], such as an
+    /// invalid URL or too many redirects.
     #[error(transparent)]
     Http(#[from] Box<ureq::Error>),
 
+    /// HTTP response with an error status: the URL and the status code.
+    #[error("{0}: status code {1}")]
+    HttpStatus(String, u16),
+
+    /// Failure to resolve or connect to a server or proxy.
+    #[error("{}", redact(.0))]
+    Connect(Box<ureq::Error>),
+
+    /// I/O failure talking to a server after connecting, such as a timeout
+    /// or reset connection while reading the response.
+    #[error("{}", redact(.0))]
+    Transport(Box<ureq::Error>),
+
     /// Serde JSON error.
     #[error("invalid JSON: {0}")]
     Serde(#[from] serde_json::Error),
 
+    /// Error parsing a document, such as `index.json` or a dist listing.
+    #[error("cannot parse {0}: {1}")]
+    Parse(String, String),
+
     /// Invalid type.
     #[error("invalid type: {0} expected to be {1} but got {2}")]
     Type(String, &'static str, &'static str),
@@ -57,13 +76,17 @@ pub enum BuildError {
     #[error("unknown URI template: {0}")]
     UnknownTemplate(String),
 
+    /// Mirror cannot list its distributions.
+    #[error("listing distributions is not supported for {0} mirrors")]
+    NoListing(String),
+
     /// Unexpected data error.
     #[error("{0}")]
     Invalid(&'static str),
 
     /// Unexpected pgxn_meta error.
     #[error(transparent)]
-    InvalidMeta(#[from] pgxn_meta::error::Error),
+    InvalidMeta(#[from] Box<pgxn_meta::error::Error>),
 
     /// Zip archive error.
     #[error("{0}")]
@@ -73,13 +96,249 @@ pub enum BuildError {
     #[error("missing {0}")]
     MissingFile(&'static str),
 
-    /// Command execution failure.
+    /// Pipeline does not support the platform.
+    #[error("{0} pipeline does not support {1}")]
+    Unsupported(&'static str, &'static str),
+
+    /// Build inputs differ from the recorded manifest.
+    #[error("build inputs differ from manifest {0}: {diffs}", diffs = .1.join(", "))]
+    Frozen(String, Vec<String>),
+
+    /// Downloaded file with more than one digest that differs from the
+    /// release metadata.
+    #[error(
+        "digests of {file} do not match: {}",
+        mismatches
+            .iter()
+            .map(|(alg, exp, act)| format!("{alg} {act} != {exp}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )]
+    DigestMismatches {
+        /// The file or URL that failed validation.
+        file: String,
+        /// The algorithm, expected digest, and actual digest of each
+        /// mismatch.
+        mismatches: Vec<(&'static str, String, String)>,
+    },
+
+    /// Response body exceeds the maximum size.
+    #[error("{0} exceeds maximum size of {1} bytes")]
+    TooLarge(String, u64),
+
+    /// Archive metadata differs from the published metadata.
+    #[error("archive META.json differs from release metadata: {}", .0.join(", "))]
+    MetaMismatch(Vec<String>),
+
+    /// Downloaded file whose digest differs from the release metadata.
+    #[error("{algorithm} digest {actual} of {file} does not match {expected}")]
+    DigestMismatch {
+        /// The digest algorithm, such as `SHA-256`.
+        algorithm: &'static str,
+        /// The hex-encoded digest from the release metadata.
+        expected: String,
+        /// The hex-encoded digest of the downloaded file.
+        actual: String,
+        /// The file or URL that failed validation.
+        file: String,
+    },
+
+    /// Archive rejected by a download verifier.
+    #[error("cannot verify {0}: {1}")]
+    Unverified(String, String),
+
+    /// Archive entry that would be written outside the destination.
+    #[error("unsafe path in archive: {0}")]
+    UnsafePath(String),
+
+    /// Archive without a single top-level directory containing every entry.
+    #[error("archive must contain a single top-level directory; found {}", .0.join(", "))]
+    ArchiveRoot(Vec<String>),
+
+    /// Archive top-level directory not named for the release.
+    #[error("archive top-level directory {0} should be {1}")]
+    ArchiveRootName(String, String),
+
+    /// Archive entry that is a symbolic link.
+    #[error("symbolic link in archive: {0}")]
+    Symlink(String),
+
+    /// Executable not found in any of the searched directories.
+    #[error("cannot find {0} in PATH; searched {dirs}", dirs = .1.join(", "))]
+    NotFound(&'static str, Vec<String>),
+
+    /// Command execution failure: the command, its error output or the
+    /// reason it could not be run, and its exit status, if it ran.
     #[error("executing `{0}`: {1}")]
-    Command(String, String),
+    Command(String, String, Option<std::process::ExitStatus>),
+
+    /// No release of a distribution is at least as mature as required.
+    #[error("no release of {0} is at least {1}")]
+    Immature(String, crate::api::Maturity),
+
+    /// Command killed for exceeding its timeout.
+    #[error("executing `{0}`: timed out after {1:?}")]
+    Timeout(String, std::time::Duration),
+
+    /// Missing dependency in a build plan.
+    #[error("{0} requires {1}, which is not in the build plan")]
+    MissingDependency(String, String),
+
+    /// Dependency cycle in a build plan.
+    #[error("dependency cycle among {}", .0.join(", "))]
+    DependencyCycle(Vec<String>),
+
+    /// cargo-pgrx is not installed or cannot be executed.
+    #[error("cannot run cargo pgrx; install it with `cargo install --locked cargo-pgrx`: {0}")]
+    NoCargoPgrx(String),
+
+    /// Multiple packages in a Cargo workspace depend on pgrx.
+    #[error("multiple pgrx packages in workspace; select one of {}", .0.join(", "))]
+    SelectPackage(Vec<String>),
+}
+
+impl BuildError {
+    /// Returns the exit code of the command that failed for a
+    /// [`BuildError::Command`], or [`None`] for other errors, commands that
+    /// could not be run, or commands killed by a signal.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Self::Command(_, _, Some(status)) => status.code(),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of the signal that killed the command that failed
+    /// for a [`BuildError::Command`], or [`None`] for other errors or
+    /// commands that exited normally.
+    #[cfg(unix)]
+    pub fn signal(&self) -> Option<i32> {
+        use std::os::unix::process::ExitStatusExt;
+        match self {
+            Self::Command(_, _, Some(status)) => status.signal(),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the error may be transient, so that retrying the
+    /// operation may succeed: HTTP 408, 429, and 5xx responses, DNS,
+    /// connection, proxy connection, and I/O failures talking to a server,
+    /// and I/O and file errors, such as a failed read while streaming a
+    /// download, of kind [`io::ErrorKind::Interrupted`],
+    /// [`io::ErrorKind::WouldBlock`], [`io::ErrorKind::TimedOut`], and
+    /// connection resets. Returns false for all other errors, such as
+    /// missing files, unknown pipelines, invalid metadata, and failed
+    /// commands.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::HttpStatus(_, code) => matches!(code, 408 | 429 | 500..=599),
+            Self::Connect(_) | Self::Transport(_) => true,
+            Self::Io(err) => is_transient(err.kind()),
+            Self::File(_, _, kind) => is_transient(*kind),
+            _ => false,
+        }
+    }
+
+    /// Converts `err` from parsing `what` into a [`BuildError::Parse`], or
+    /// into a [`BuildError::Io`] if the error is an I/O error.
+    pub(crate) fn parse<W: std::fmt::Display>(what: W, err: serde_json::Error) -> Self {
+        if err.is_io() {
+            return Self::io(err.into());
+        }
+        Self::Parse(what.to_string(), err.to_string())
+    }
+
+    /// Converts `err` into a [`BuildError::Io`], unless it wraps a
+    /// [`TooLarge`] error, in which case it returns a
+    /// [`BuildError::TooLarge`].
+    pub(crate) fn io(err: io::Error) -> Self {
+        match err.get_ref().and_then(|e| e.downcast_ref::<TooLarge>()) {
+            Some(e) => Self::TooLarge(e.0.clone(), e.1),
+            None => Self::Io(err),
+        }
+    }
+}
+
+/// Reported by readers that limit the size of their content, wrapped in an
+/// [`io::Error`], to report [`BuildError::TooLarge`] through the
+/// [`io::Read`] interface.
+#[derive(Error, Debug)]
+#[error("{0} exceeds maximum size of {1} bytes")]
+pub(crate) struct TooLarge(pub(crate) String, pub(crate) u64);
+
+impl From<pgxn_meta::error::Error> for BuildError {
+    /// Boxes `value` in a [`BuildError::InvalidMeta`], to keep
+    /// [`BuildError`] small.
+    fn from(value: pgxn_meta::error::Error) -> Self {
+        Self::InvalidMeta(Box::new(value))
+    }
 }
 
 impl From<ureq::Error> for BuildError {
+    /// Converts `value` into [`BuildError::HttpStatus`] for error responses
+    /// (with URL credentials redacted), [`BuildError::Connect`] for DNS and
+    /// connection failures, [`BuildError::Transport`] for I/O failures, and
+    /// [`BuildError::Http`] for anything else.
     fn from(value: ureq::Error) -> Self {
-        Self::Http(Box::new(value))
+        match &value {
+            ureq::Error::Status(code, res) => Self::HttpStatus(redact_url(res.get_url()), *code),
+            ureq::Error::Transport(t) => match t.kind() {
+                ureq::ErrorKind::Dns
+                | ureq::ErrorKind::ConnectionFailed
+                | ureq::ErrorKind::ProxyConnect => Self::Connect(Box::new(value)),
+                ureq::ErrorKind::Io => Self::Transport(Box::new(value)),
+                _ => Self::Http(Box::new(value)),
+            },
+        }
+    }
+}
+
+/// Formats `err` like its [`Display`](std::fmt::Display) implementation,
+/// but with any credentials in its URL redacted.
+fn redact(err: &ureq::Error) -> String {
+    match err {
+        ureq::Error::Status(code, res) => {
+            format!("{}: status code {code}", redact_url(res.get_url()))
+        }
+        ureq::Error::Transport(t) => {
+            let mut msg = match t.url() {
+                Some(url) => format!("{}: {}", crate::api::Redacted(url), t.kind()),
+                None => t.kind().to_string(),
+            };
+            if let Some(m) = t.message() {
+                msg.push_str(": ");
+                msg.push_str(m);
+            }
+            if let Some(src) = std::error::Error::source(err) {
+                msg.push_str(&format!(": {src}"));
+            }
+            msg
+        }
     }
 }
+
+/// Returns `url` with any credentials redacted, or as-is if it cannot be
+/// parsed.
+fn redact_url(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(u) => crate::api::Redacted(&u).to_string(),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Returns true if I/O errors of `kind` may be transient.
+fn is_transient(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,177 @@
+//! Support for `.pgxnignore` files.
+//!
+//! A `.pgxnignore` file in the root of a distribution lists files to exclude
+//! from local builds using the [gitignore] pattern format, so that stray
+//! files, such as editor backups or a leftover `Cargo.toml`, do not confuse
+//! pipeline detection.
+//!
+//! [gitignore]: https://git-scm.com/docs/gitignore#_pattern_format
+
+use log::warn;
+use regex::Regex;
+use std::{fs, io, path::Path};
+
+/// The name of the ignore file.
+pub(crate) const IGNORE_FILE: &str = ".pgxnignore";
+
+/// A single pattern from an ignore file.
+#[derive(Debug)]
+struct Rule {
+    rx: Regex,
+    negate: bool,
+    dir_only: bool,
+}
+
+/// Ignore matches paths against the patterns in a `.pgxnignore` file.
+#[derive(Debug, Default)]
+pub(crate) struct Ignore {
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    /// Loads the `.pgxnignore` file in `dir`. Returns an Ignore that
+    /// ignores nothing if the file does not exist or cannot be read.
+    pub(crate) fn load(dir: &Path) -> Self {
+        let path = dir.join(IGNORE_FILE);
+        match fs::read_to_string(&path) {
+            Ok(src) => Self::parse(&src),
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    warn!(file:display = path.display(), error:display = e; "cannot read");
+                }
+                Self::default()
+            }
+        }
+    }
+
+    /// Parses the gitignore-style patterns in `src`. Skips blank lines,
+    /// comments, and invalid patterns.
+    pub(crate) fn parse(src: &str) -> Self {
+        let mut rules = Vec::new();
+        for line in src.lines() {
+            match Rule::parse(line) {
+                None => {}
+                Some(Ok(rule)) => rules.push(rule),
+                Some(Err(e)) => {
+                    warn!(pattern = line, error:display = e; "invalid {IGNORE_FILE} pattern")
+                }
+            }
+        }
+        Ignore { rules }
+    }
+
+    /// Returns true if `path`, relative to the root of the distribution,
+    /// should be ignored. Pass `is_dir` true if `path` is a directory. As
+    /// with gitignore, a path is ignored if any of its parent directories
+    /// is ignored.
+    pub(crate) fn is_ignored<P: AsRef<Path>>(&self, path: P, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let parts: Vec<_> = path
+            .as_ref()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        for i in 1..=parts.len() {
+            let last = i == parts.len();
+            if self.matches(&parts[..i].join("/"), !last || is_dir) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns true if the last rule to match `path` ignores it.
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| (is_dir || !r.dir_only) && r.rx.is_match(path))
+            .is_some_and(|r| !r.negate)
+    }
+}
+
+impl Rule {
+    /// Parses a single line from an ignore file. Returns [`None`] for blank
+    /// lines and comments.
+    fn parse(line: &str) -> Option<Result<Self, regex::Error>> {
+        let mut pat = line.trim_end();
+        if pat.is_empty() || pat.starts_with('#') {
+            return None;
+        }
+
+        // Strip a leading "!" for negation or "\" escaping "#" or "!".
+        let negate = pat.starts_with('!');
+        if negate || pat.starts_with("\\#") || pat.starts_with("\\!") {
+            pat = &pat[1..];
+        }
+
+        let dir_only = pat.ends_with('/');
+        let pat = pat.trim_end_matches('/');
+        if pat.is_empty() {
+            return None;
+        }
+
+        // Patterns with a slash anywhere but the end match relative to the
+        // root; others match a name at any depth.
+        let anchored = pat.contains('/');
+        let pat = pat.trim_start_matches('/');
+        let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+        let rx = Regex::new(&format!("{prefix}{}$", glob_to_regex(pat)));
+        Some(rx.map(|rx| Rule {
+            rx,
+            negate,
+            dir_only,
+        }))
+    }
+}
+
+/// Converts the gitignore glob `pat` to a regular expression.
+fn glob_to_regex(pat: &str) -> String {
+    let mut rx = String::new();
+    let mut chars = pat.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    // "**/" matches zero or more directories.
+                    chars.next();
+                    rx.push_str("(?:.*/)?");
+                } else {
+                    rx.push_str(".*");
+                }
+            }
+            '*' => rx.push_str("[^/]*"),
+            '?' => rx.push_str("[^/]"),
+            '[' => {
+                rx.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    rx.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        rx.push('\\');
+                    }
+                    rx.push(c);
+                }
+                rx.push(']');
+            }
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    rx.push_str(&regex::escape(&c.to_string()));
+                }
+            }
+            c => rx.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    rx
+}
+
+#[cfg(test)]
+mod tests;
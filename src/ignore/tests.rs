@@ -0,0 +1,94 @@
+use super::*;
+use tempfile::tempdir;
+
+#[test]
+fn is_ignored() {
+    let ignore = Ignore::parse(
+        "# Editor files
+*~
+*.sw?
+\\#*#
+
+# Build artifacts
+/results/
+*.o
+!keep.o
+build/**/*.so
+
+# Stray files
+/Cargo.toml
+docs/*.tmp
+[Mm]akefile.old
+",
+    );
+
+    for (path, is_dir, exp) in [
+        ("README.md", false, false),
+        ("pair.c~", false, true),
+        ("src/pair.c~", false, true),
+        ("src/.pair.c.swp", false, true),
+        ("#pair.c#", false, true),
+        ("pair.o", false, true),
+        ("src/pair.o", false, true),
+        ("keep.o", false, false),
+        ("src/keep.o", false, false),
+        ("results", true, true),
+        ("results", false, false),
+        ("results/pair.out", false, true),
+        ("test/results", true, false),
+        ("build/pair.so", false, true),
+        ("build/x/y/pair.so", false, true),
+        ("other/build/pair.so", false, false),
+        ("Cargo.toml", false, true),
+        ("sub/Cargo.toml", false, false),
+        ("docs/pair.tmp", false, true),
+        ("docs/x/pair.tmp", false, false),
+        ("Makefile.old", false, true),
+        ("makefile.old", false, true),
+        ("Makefile", false, false),
+    ] {
+        assert_eq!(exp, ignore.is_ignored(path, is_dir), "{path}");
+    }
+}
+
+#[test]
+fn parse() {
+    // Blank lines, comments, and bare slashes add no rules.
+    let ignore = Ignore::parse("\n# comment\n   \n/\n");
+    assert!(ignore.rules.is_empty());
+    assert!(!ignore.is_ignored("anything", false));
+
+    // Invalid patterns are skipped.
+    let ignore = Ignore::parse("[]\n*.o\n");
+    assert_eq!(1, ignore.rules.len());
+    assert!(ignore.is_ignored("pair.o", false));
+
+    // Trailing whitespace is ignored, escapes are literal.
+    let ignore = Ignore::parse("*.o   \n\\!important\n\\*\n");
+    assert!(ignore.is_ignored("pair.o", false));
+    assert!(ignore.is_ignored("!important", false));
+    assert!(ignore.is_ignored("*", false));
+    assert!(!ignore.is_ignored("star", false));
+
+    // Negated classes.
+    let ignore = Ignore::parse("pair.[!c]\n");
+    assert!(ignore.is_ignored("pair.h", false));
+    assert!(!ignore.is_ignored("pair.c", false));
+}
+
+#[test]
+fn load() -> Result<(), io::Error> {
+    let tmp = tempdir()?;
+
+    // No file.
+    let ignore = Ignore::load(tmp.path());
+    assert!(ignore.rules.is_empty());
+
+    // Load the file.
+    fs::write(tmp.path().join(IGNORE_FILE), "Cargo.toml\n")?;
+    let ignore = Ignore::load(tmp.path());
+    assert!(ignore.is_ignored("Cargo.toml", false));
+    assert!(!ignore.is_ignored("Makefile", false));
+
+    Ok(())
+}
@@ -9,15 +9,34 @@ versions.
 */
 pub mod api;
 pub mod error;
+mod ignore;
+mod manifest;
+pub mod matrix;
 mod pg_config;
 mod pgrx;
 mod pgxs;
 mod pipeline;
+pub mod plan;
+mod receipt;
+pub mod summary;
 
-use crate::{error::BuildError, pgrx::Pgrx, pgxs::Pgxs, pipeline::Pipeline};
-use pg_config::PgConfig;
+pub use pg_config::PgConfig;
+pub use pipeline::Captured;
+
+use crate::{
+    error::BuildError,
+    pgrx::Pgrx,
+    pgxs::Pgxs,
+    pipeline::{Pipeline, Probe},
+};
+use log::info;
 use pgxn_meta::{dist, release::Release};
-use std::path::Path;
+use sha2::Digest;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use summary::{ArtifactSummary, BuildSummary};
 
 /// Defines the types of builders.
 #[derive(Debug, PartialEq)]
@@ -43,37 +62,188 @@ impl<P: AsRef<Path>> Build<P> {
     /// Attempts to detect and return the appropriate build pipeline to build
     /// the contents of `dir`. Returns an error if no pipeline can do so.
     fn detect(dir: P, cfg: PgConfig) -> Result<Build<P>, BuildError> {
-        // Start with PGXS.
-        let mut score = Pgxs::confidence(&dir);
-        let mut pipe = dist::Pipeline::Pgxs;
+        // Find the highest score, preferring earlier pipelines on a tie.
+        let mut winner: Option<(dist::Pipeline, u8)> = None;
+        for (pipe, score) in Self::scores(&dir) {
+            if score > winner.as_ref().map_or(0, |w| w.1) {
+                winner = Some((pipe, score));
+            }
+        }
 
-        // Does pgrx have a higher score?
-        let c = Pgrx::confidence(&dir);
-        if c > score {
-            score = c;
-            pipe = dist::Pipeline::Pgrx;
+        // Return an error if no confidence; otherwise construct the winner.
+        match winner {
+            None => Err(BuildError::NoPipeline()),
+            Some((pipe, _)) => Self::new(&pipe, dir, cfg),
         }
+    }
+
+    /// Returns the confidence score of each supported pipeline that it can
+    /// build the contents of `dir`, starting with PGXS.
+    fn scores(dir: &P) -> Vec<(dist::Pipeline, u8)> {
+        vec![
+            (dist::Pipeline::Pgxs, Pgxs::confidence(dir)),
+            (dist::Pipeline::Pgrx, Pgrx::confidence(dir)),
+        ]
+    }
 
-        // Try each of the others as they're added.
-        // Return an error if no confidence.
-        if score == 0 {
-            return Err(BuildError::NoPipeline());
+    /// Returns the name of the pipeline, `pgxs` or `pgrx`.
+    fn name(&self) -> &'static str {
+        match self {
+            Build::Pgxs(_) => "pgxs",
+            Build::Pgrx(_) => "pgrx",
         }
+    }
 
-        // Construct the winner.
-        match pipe {
-            dist::Pipeline::Pgrx => Ok(Build::Pgrx(Pgrx::new(dir, cfg))),
-            dist::Pipeline::Pgxs => Ok(Build::Pgxs(Pgxs::new(dir, cfg))),
-            _ => unreachable!("unknown pipelines {pipe}"),
+    /// Returns the [`dist::Pipeline`] identifying the pipeline.
+    fn kind(&self) -> dist::Pipeline {
+        match self {
+            Build::Pgxs(_) => dist::Pipeline::Pgxs,
+            Build::Pgrx(_) => dist::Pipeline::Pgrx,
         }
     }
 }
 
+/// Describes an extension provided by a distribution, as declared in the
+/// `contents.extensions` object of its release metadata.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExtensionInfo {
+    name: String,
+    sql: String,
+    control: String,
+}
+
+impl ExtensionInfo {
+    /// Borrows the extension name.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Borrows the path to the extension's SQL file, relative to the root
+    /// of the distribution.
+    pub fn sql(&self) -> &str {
+        self.sql.as_str()
+    }
+
+    /// Borrows the path to the extension's control file, relative to the
+    /// root of the distribution.
+    pub fn control(&self) -> &str {
+        self.control.as_str()
+    }
+}
+
+/// Selects the tests that [`Builder::test`] runs for PGXS distributions.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum TestMode {
+    /// Runs only the `pg_regress` tests listed in the Makefile's `REGRESS`
+    /// variable.
+    Regress,
+
+    /// Runs only the TAP tests, the `.pl` files in the `t` directory, with
+    /// `prove`.
+    Tap,
+
+    /// Runs the `pg_regress` tests and, if the distribution has `.pl` files
+    /// in its `t` directory, the TAP tests.
+    #[default]
+    Both,
+}
+
+/// Describes the directories into which [`Builder::install`] installed a
+/// distribution, as reported by `pg_config`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InstallDirs {
+    pkglibdir: Option<PathBuf>,
+    sharedir: Option<PathBuf>,
+    bindir: Option<PathBuf>,
+    docdir: Option<PathBuf>,
+    includedir_server: Option<PathBuf>,
+    destdir: Option<PathBuf>,
+}
+
+impl InstallDirs {
+    /// Creates an InstallDirs from the directories reported by `cfg`.
+    fn new(cfg: &PgConfig) -> Self {
+        InstallDirs {
+            pkglibdir: cfg.pkglibdir().map(Path::to_path_buf),
+            sharedir: cfg.sharedir().map(Path::to_path_buf),
+            bindir: cfg.bindir().map(Path::to_path_buf),
+            docdir: cfg.docdir().map(Path::to_path_buf),
+            includedir_server: cfg.includedir_server().map(Path::to_path_buf),
+            destdir: None,
+        }
+    }
+
+    /// Creates an InstallDirs for a staged install into `destdir`, with the
+    /// directories reported by `cfg` prefixed by `destdir` the way `make`
+    /// prefixes them with `DESTDIR`.
+    fn staged(cfg: &PgConfig, destdir: &Path) -> Self {
+        let stage = |dir: &Path| {
+            let mut path = destdir.as_os_str().to_owned();
+            path.push(dir.as_os_str());
+            PathBuf::from(path)
+        };
+        InstallDirs {
+            pkglibdir: cfg.pkglibdir().map(stage),
+            sharedir: cfg.sharedir().map(stage),
+            bindir: cfg.bindir().map(stage),
+            docdir: cfg.docdir().map(stage),
+            includedir_server: cfg.includedir_server().map(stage),
+            destdir: Some(destdir.to_path_buf()),
+        }
+    }
+
+    /// Borrows the staging directory set by [`Builder::destdir`], or
+    /// [`None`] if the distribution was installed directly.
+    pub fn destdir(&self) -> Option<&Path> {
+        self.destdir.as_deref()
+    }
+
+    /// Borrows the directory for shared libraries, or [`None`] if
+    /// `pg_config` does not report `pkglibdir`.
+    pub fn pkglibdir(&self) -> Option<&Path> {
+        self.pkglibdir.as_deref()
+    }
+
+    /// Borrows the directory for architecture-independent support files,
+    /// or [`None`] if `pg_config` does not report `sharedir`.
+    pub fn sharedir(&self) -> Option<&Path> {
+        self.sharedir.as_deref()
+    }
+
+    /// Returns the directory for extension control and SQL files, the
+    /// `extension` subdirectory of [`sharedir`](Self::sharedir).
+    pub fn extension_dir(&self) -> Option<PathBuf> {
+        self.sharedir.as_ref().map(|d| d.join("extension"))
+    }
+
+    /// Borrows the directory for executables, such as PGXS `PROGRAM` and
+    /// `SCRIPTS`, or [`None`] if `pg_config` does not report `bindir`.
+    pub fn bindir(&self) -> Option<&Path> {
+        self.bindir.as_deref()
+    }
+
+    /// Borrows the directory for documentation, such as PGXS `DOCS`, or
+    /// [`None`] if `pg_config` does not report `docdir`.
+    pub fn docdir(&self) -> Option<&Path> {
+        self.docdir.as_deref()
+    }
+
+    /// Borrows the directory for server C header files, such as PGXS
+    /// `HEADERS`, or [`None`] if `pg_config` does not report
+    /// `includedir-server`.
+    pub fn includedir_server(&self) -> Option<&Path> {
+        self.includedir_server.as_deref()
+    }
+}
+
 /// Builder builds PGXN releases.
 #[derive(Debug, PartialEq)]
 pub struct Builder<P: AsRef<Path>> {
     pipeline: Build<P>,
     meta: Release,
+    frozen: Option<PathBuf>,
+    receipt: Option<PathBuf>,
+    patches: Vec<manifest::Patch>,
 }
 
 impl<P: AsRef<Path>> Builder<P> {
@@ -89,18 +259,491 @@ impl<P: AsRef<Path>> Builder<P> {
             Build::detect(dir, cfg)?
         };
 
-        Ok(Builder { pipeline, meta })
+        Ok(Self::from_build(pipeline, meta))
+    }
+
+    /// Creates and returns a new builder for the distribution in `dir`,
+    /// reading its release metadata from `META.json` in `dir` rather than
+    /// from [`Api::meta`](api::Api::meta), so that it requires no network
+    /// access. `META.json` must contain valid release metadata, including
+    /// `certs`. Uses the pipeline specified by the metadata or detected from
+    /// the contents of `dir`, as [`Self::new`] does.
+    pub fn from_dir(dir: P, cfg: PgConfig) -> Result<Self, BuildError> {
+        let path = dir.as_ref().join("META.json");
+        let file = std::fs::File::open(&path)
+            .map_err(|e| BuildError::File("reading", path.display().to_string(), e.kind()))?;
+        let val: serde_json::Value =
+            serde_json::from_reader(file).map_err(|e| BuildError::parse(path.display(), e))?;
+        Self::new(dir, Release::try_from(val)?, cfg)
+    }
+
+    /// Creates and returns a new builder using `pipeline`, ignoring the
+    /// pipeline specified by the release metadata and skipping detection.
+    /// Useful for distributions that contain files for multiple pipelines,
+    /// such as both a `Makefile` and a `Cargo.toml`. Returns
+    /// [`BuildError::UnknownPipeline`] if `pipeline` is not supported.
+    pub fn with_pipeline(
+        dir: P,
+        meta: Release,
+        cfg: PgConfig,
+        pipeline: dist::Pipeline,
+    ) -> Result<Self, BuildError> {
+        let pipeline = Build::new(&pipeline, dir, cfg)?;
+        Ok(Self::from_build(pipeline, meta))
+    }
+
+    /// Returns the confidence score, from 0 to 255, of each supported
+    /// pipeline that it can build the contents of `dir`. [`Self::new`]
+    /// selects the pipeline with the highest score when the release metadata
+    /// specifies none, preferring the first on a tie, and fails if all
+    /// scores are 0. Useful for explaining why it selected a pipeline.
+    pub fn detect_scores(dir: P) -> Vec<(dist::Pipeline, u8)> {
+        Build::scores(&dir)
+    }
+
+    /// Creates and returns a new builder for `meta` using `pipeline`.
+    fn from_build(pipeline: Build<P>, meta: Release) -> Self {
+        Builder {
+            pipeline,
+            meta,
+            frozen: None,
+            receipt: None,
+            patches: Vec::new(),
+        }
+    }
+
+    /// Returns the name of the pipeline that builds the distribution, `pgxs`
+    /// or `pgrx`, whether specified by the release metadata or detected
+    /// from the contents of the distribution.
+    pub fn pipeline_name(&self) -> &'static str {
+        self.pipeline.name()
+    }
+
+    /// Returns the [`dist::Pipeline`] that builds the distribution. See
+    /// [`Self::pipeline_name`].
+    pub fn pipeline_kind(&self) -> dist::Pipeline {
+        self.pipeline.kind()
+    }
+
+    /// Enables frozen mode, in which [`Self::configure`] records the build
+    /// inputs in the manifest file at `manifest` the first time it runs.
+    /// Subsequent runs verify that the inputs match the recorded manifest,
+    /// and fail if they do not. The inputs include the distribution name,
+    /// version, and digests, the build pipeline, the `pg_config` values, and
+    /// the operating system and architecture, and the names and digests of
+    /// patches applied by [`Self::apply_patch`]. Also sets
+    /// `CARGO_NET_OFFLINE=true` for the pgrx pipeline, so that the build
+    /// fails rather than fetch crates missing from the Cargo cache.
+    pub fn frozen<M: Into<PathBuf>>(mut self, manifest: M) -> Self {
+        self.frozen = Some(manifest.into());
+        if let Build::Pgrx(pgrx) = &mut self.pipeline {
+            pgrx.set_env("CARGO_NET_OFFLINE".to_string(), Some("true".to_string()));
+        }
+        self
+    }
+
+    /// Enables install receipts, in which [`Self::install`] writes a JSON
+    /// receipt to `path` recording the distribution name and version, the
+    /// Postgres version, and the paths to the files it installed. It
+    /// identifies the files by comparing snapshots taken before and after
+    /// installing, so it includes files replaced by a reinstall. For a
+    /// [staged install](Self::destdir) the snapshots cover the whole
+    /// `DESTDIR`; otherwise they cover only the files in the `pkglibdir`,
+    /// `sharedir`, `bindir`, `docdir`, and `includedir-server` directories
+    /// named for the distribution, its extensions, and the modules and
+    /// directories named in their control files.
+    pub fn receipt<R: Into<PathBuf>>(mut self, path: R) -> Self {
+        self.receipt = Some(path.into());
+        self
+    }
+
+    /// Returns the extensions the distribution declares in its metadata,
+    /// sorted by name.
+    pub fn extensions(&self) -> Vec<ExtensionInfo> {
+        let mut list: Vec<ExtensionInfo> = match self.meta.contents().extensions() {
+            None => Vec::new(),
+            Some(exts) => exts
+                .iter()
+                .map(|(name, ext)| ExtensionInfo {
+                    name: name.to_string(),
+                    sql: ext.sql().to_string(),
+                    control: ext.control().to_string(),
+                })
+                .collect(),
+        };
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+
+    /// Sets whether to treat compiler warnings as errors when compiling the
+    /// distribution. Currently applies only to the PGXS pipeline, where it
+    /// passes `COPT=-Werror` to `make`.
+    pub fn warnings_as_errors(mut self, yes: bool) -> Self {
+        if let Build::Pgxs(pgxs) = &mut self.pipeline {
+            pgxs.warnings_as_errors(yes);
+        }
+        self
+    }
+
+    /// Sets the shell with which to run a `configure` script, such as `sh`
+    /// or `/bin/bash`, instead of running it directly. Useful where the
+    /// script's shebang points to an unavailable interpreter or where
+    /// policy requires scripts to run through a vetted shell. Currently
+    /// applies only to the PGXS pipeline.
+    pub fn configure_shell<S: Into<String>>(mut self, shell: S) -> Self {
+        if let Build::Pgxs(pgxs) = &mut self.pipeline {
+            pgxs.set_shell(Some(shell.into()));
+        }
+        self
+    }
+
+    /// Sets whether to skip running `cargo pgrx init` to register the
+    /// Postgres installation with pgrx when configuring and testing, for
+    /// environments that manage pgrx initialization externally. Applies only
+    /// to the pgrx pipeline.
+    pub fn skip_pgrx_init(mut self, yes: bool) -> Self {
+        if let Build::Pgrx(pgrx) = &mut self.pipeline {
+            pgrx.skip_init(yes);
+        }
+        self
+    }
+
+    /// Sets the package to build in a Cargo workspace with multiple members
+    /// that depend on pgrx. Without it, the pgrx pipeline builds the only
+    /// member that depends on pgrx, and returns
+    /// [`BuildError::SelectPackage`] listing the candidates if there is more
+    /// than one. Applies only to the pgrx pipeline.
+    pub fn pgrx_package<S: Into<String>>(mut self, package: S) -> Self {
+        if let Build::Pgrx(pgrx) = &mut self.pipeline {
+            pgrx.set_package(Some(package.into()));
+        }
+        self
+    }
+
+    /// Sets the Cargo features to enable when building, testing, and
+    /// installing, for example to select a Postgres version feature.
+    /// Applies only to the pgrx pipeline.
+    pub fn pgrx_features<I, S>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if let Build::Pgrx(pgrx) = &mut self.pipeline {
+            pgrx.set_features(features.into_iter().map(Into::into).collect());
+        }
+        self
+    }
+
+    /// Sets the Cargo profile with which to build, test, and install, such
+    /// as `dev`. Defaults to the release profile. Applies only to the pgrx
+    /// pipeline.
+    pub fn pgrx_profile<S: Into<String>>(mut self, profile: S) -> Self {
+        if let Build::Pgrx(pgrx) = &mut self.pipeline {
+            pgrx.set_profile(Some(profile.into()));
+        }
+        self
+    }
+
+    /// Sets a wrapper command with which to run every build command, for
+    /// example to limit resources or sandbox the build of untrusted code
+    /// with a wrapper such as `prlimit --as=2000000000` or
+    /// `systemd-run --scope -p MemoryMax=2G`. The first item is the
+    /// program, and the rest are its arguments; the build command and its
+    /// arguments follow them. Wraps `sudo`, too, when the install requires
+    /// it.
+    ///
+    /// Replaces `{dir}` in each item with the build directory, so that a
+    /// wrapper can run commands in a container or chroot with the build
+    /// directory mapped into it, e.g.,
+    /// `docker run --rm -v {dir}:{dir} -w {dir} pgxn/pgxn-tools`.
+    pub fn command_wrapper<I, S>(mut self, wrapper: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let wrapper: Vec<String> = wrapper.into_iter().map(Into::into).collect();
+        match &mut self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.set_wrapper(wrapper),
+            Build::Pgrx(pgrx) => pgrx.set_wrapper(wrapper),
+        }
+        self
+    }
+
+    /// Sets whether to run build commands in the C locale by setting
+    /// `LC_ALL` and `LANG` to `C`, so that compiler and `make` output is in
+    /// a predictable language for parsing and consistent logs. Defaults to
+    /// true; pass false to use the caller's locale.
+    pub fn c_locale(mut self, yes: bool) -> Self {
+        match &mut self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.set_c_locale(yes),
+            Build::Pgrx(pgrx) => pgrx.set_c_locale(yes),
+        }
+        self
+    }
+
+    /// Sets the program with which to run install and uninstall commands
+    /// with elevated privileges when the current user cannot write to the
+    /// `pkglibdir` directory, such as `doas`. Defaults to `sudo`.
+    pub fn sudo_program<S: Into<String>>(mut self, program: S) -> Self {
+        let program = program.into();
+        match &mut self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.set_sudo_program(program),
+            Build::Pgrx(pgrx) => pgrx.set_sudo_program(program),
+        }
+        self
+    }
+
+    /// Sets whether to pass `-n` to the [`sudo_program`](Self::sudo_program)
+    /// so that it fails with an error rather than prompts for a password
+    /// when it has no cached credentials, as in CI, where a prompt would
+    /// block the build. Defaults to false.
+    pub fn sudo_non_interactive(mut self, yes: bool) -> Self {
+        match &mut self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.set_sudo_non_interactive(yes),
+            Build::Pgrx(pgrx) => pgrx.set_sudo_non_interactive(yes),
+        }
+        self
+    }
+
+    /// Sets the maximum time each build command may run, so that a hung
+    /// `make` or `cargo` fails the build rather than blocking it forever.
+    /// When a command exceeds the timeout, kills it and, on Unix, the
+    /// processes it started, and returns [`BuildError::Timeout`]. Commands
+    /// run with `sudo` may survive the kill. Defaults to no timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        match &mut self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.set_timeout(Some(timeout)),
+            Build::Pgrx(pgrx) => pgrx.set_timeout(Some(timeout)),
+        }
+        self
+    }
+
+    /// Sets the maximum number of bytes of stdout and stderr to keep from
+    /// each build command, so that a noisy build cannot exhaust memory.
+    /// Keeps the last `cap` bytes of each stream, which also limits the
+    /// output embedded in [`BuildError::Command`]. Defaults to 1 MiB.
+    pub fn output_cap(mut self, cap: usize) -> Self {
+        match &mut self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.set_output_cap(cap),
+            Build::Pgrx(pgrx) => pgrx.set_output_cap(cap),
+        }
+        self
+    }
+
+    /// Returns the output of the most recent build command that ran to
+    /// completion, whether or not it succeeded, such as the `make` run by
+    /// [`compile`](Self::compile), so that callers can inspect it, for
+    /// example to parse compiler warnings. Returns [`None`] if no command
+    /// has completed. See [`output_cap`](Self::output_cap).
+    pub fn last_output(&self) -> Option<Captured> {
+        match &self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.last_output(),
+            Build::Pgrx(pgrx) => pgrx.last_output(),
+        }
+        .and_then(|cell| cell.borrow().clone())
+    }
+
+    /// Sets the environment variable `key` to `val` for every build
+    /// command, such as `CARGO_TARGET_DIR` or a `PATH` with additional
+    /// directories, overriding the inherited value and the C locale
+    /// variables set by [`c_locale`](Self::c_locale). Commands run with
+    /// `sudo` may not see the variable, depending on the `sudo`
+    /// configuration.
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, val: V) -> Self {
+        self.set_env(key.into(), Some(val.into()));
+        self
+    }
+
+    /// Sets each of the environment variables in `vars` for every build
+    /// command, as described for [`env`](Self::env).
+    pub fn envs<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (key, val) in vars {
+            self.set_env(key.into(), Some(val.into()));
+        }
+        self
+    }
+
+    /// Removes the environment variable `key` from the environment of
+    /// every build command, so that commands do not inherit it.
+    pub fn env_remove<K: Into<String>>(mut self, key: K) -> Self {
+        self.set_env(key.into(), None);
+        self
+    }
+
+    /// Sets the environment variable `key` to `val` only for the command
+    /// that runs the tests, `make installcheck`, overriding variables set by
+    /// [`env`](Self::env). Set libpq variables such as `PGHOST`, `PGPORT`,
+    /// and `PGUSER` to run the tests against a cluster other than the
+    /// default. Applies only to the PGXS pipeline.
+    pub fn pgxs_test_env<K: Into<String>, V: Into<String>>(mut self, key: K, val: V) -> Self {
+        if let Build::Pgxs(pgxs) = &mut self.pipeline {
+            pgxs.set_test_env(key.into(), Some(val.into()));
+        }
+        self
+    }
+
+    /// Sets or, when `val` is [`None`], removes the environment variable
+    /// `key` for every build command.
+    fn set_env(&mut self, key: String, val: Option<String>) {
+        match &mut self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.set_env(key, val),
+            Build::Pgrx(pgrx) => pgrx.set_env(key, val),
+        }
+    }
+
+    /// Stages the install in `dir` rather than installing into the
+    /// directories reported by `pg_config`, so that packagers can collect
+    /// the files without touching the live Postgres. For PGXS,
+    /// [`install`](Self::install) runs `make install DESTDIR=<dir>` without
+    /// `sudo` and returns the staged directories. The pgrx pipeline does not
+    /// support staged installs, so `install` returns
+    /// [`BuildError::Unsupported`]; use [`package`](Self::package) instead,
+    /// which stages its output in `dir`.
+    pub fn destdir<D: Into<PathBuf>>(mut self, dir: D) -> Self {
+        let dir = Some(dir.into());
+        match &mut self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.set_destdir(dir),
+            Build::Pgrx(pgrx) => pgrx.set_destdir(dir),
+        }
+        self
+    }
+
+    /// Sets the `make` program for the PGXS pipeline to run, such as
+    /// `/usr/local/bin/gmake`. Otherwise it runs the first of `gmake` or
+    /// `make` in the `PATH` that is GNU make, as PGXS requires, and fails
+    /// with [`BuildError::NotFound`] if neither is. Applies only to the
+    /// PGXS pipeline.
+    pub fn pgxs_make<S: Into<String>>(mut self, make: S) -> Self {
+        if let Build::Pgxs(pgxs) = &mut self.pipeline {
+            pgxs.set_make(Some(make.into()));
+        }
+        self
+    }
+
+    /// Selects the tests to run for the PGXS pipeline: `pg_regress` tests,
+    /// TAP tests in `t/*.pl`, or [both](TestMode::Both), the
+    /// default. Runs them via `make installcheck`, setting `TAP_TESTS=1` to
+    /// run the TAP tests and `REGRESS=` to skip the `pg_regress` tests. Pass
+    /// options to `prove` with [`make_args`](Self::make_args), e.g.,
+    /// `PROVE_FLAGS=--verbose`. Applies only to the PGXS pipeline.
+    pub fn pgxs_test_mode(mut self, mode: TestMode) -> Self {
+        if let Build::Pgxs(pgxs) = &mut self.pipeline {
+            pgxs.set_test_mode(mode);
+        }
+        self
+    }
+
+    /// Sets extra arguments to pass to `make` after the target when
+    /// compiling, testing, and installing, such as variable overrides like
+    /// `PG_CPPFLAGS=-DFOO -DBAR` or additional targets. Each item is passed
+    /// as a single argument, without shell interpretation, even if it
+    /// contains spaces. Applies only to the PGXS pipeline.
+    pub fn make_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if let Build::Pgxs(pgxs) = &mut self.pipeline {
+            pgxs.set_make_args(args.into_iter().map(Into::into).collect());
+        }
+        self
+    }
+
+    /// Sets the name of the temporary file used to test whether the
+    /// Postgres `pkglibdir` is writeable, and therefore whether to install
+    /// with `sudo`. The file name starts with `prefix`, ends with `suffix`,
+    /// and defaults to `pgxn-*.test`. On systems where security policies
+    /// vary by file name, such as SELinux, pass a suffix matching the files
+    /// the install writes, e.g., `.so`, for a more accurate result.
+    pub fn write_probe<S: Into<String>>(mut self, prefix: S, suffix: S) -> Self {
+        let probe = Probe {
+            prefix: prefix.into(),
+            suffix: suffix.into(),
+        };
+        match &mut self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.set_probe(probe),
+            Build::Pgrx(pgrx) => pgrx.set_probe(probe),
+        }
+        self
+    }
+
+    /// Applies the patch file `patch` to the distribution source by running
+    /// `patch -p1 --forward` in the build directory, so that packagers can
+    /// apply downstream fixes before building. Call it before
+    /// [`configure`](Self::configure), which records the names and SHA-256
+    /// digests of the applied patches in the build manifest in
+    /// [frozen mode](Self::frozen).
+    pub fn apply_patch<F: AsRef<Path>>(&mut self, patch: F) -> Result<(), BuildError> {
+        let patch = patch.as_ref();
+        let file_err =
+            |e: std::io::Error| BuildError::File("reading", patch.display().to_string(), e.kind());
+        // Use an absolute path, since the command runs in the build directory.
+        let path = std::fs::canonicalize(patch).map_err(file_err)?;
+        let data = std::fs::read(&path).map_err(file_err)?;
+
+        info!(patch:display = filename(&path); "applying patch");
+        let args = [
+            "-p1".into(),
+            "--forward".into(),
+            "-i".into(),
+            path.as_os_str().to_owned(),
+        ];
+        match &self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.run("patch", &args, false)?,
+            Build::Pgrx(pgrx) => pgrx.run("patch", &args, false)?,
+        }
+        self.patches.push(manifest::Patch {
+            file: filename(&path),
+            sha256: hex::encode(sha2::Sha256::digest(&data)),
+        });
+        Ok(())
     }
 
     /// Configures a distribution to build on a particular platform and
-    /// Postgres version.
+    /// Postgres version. In [frozen mode](Self::frozen), first records or
+    /// verifies the build manifest.
     pub fn configure(&self) -> Result<(), BuildError> {
+        if let Some(path) = &self.frozen {
+            let (pipe, cfg) = self.pipeline_config();
+            let manifest = manifest::manifest(&self.meta, pipe, cfg, &self.patches);
+            manifest::verify(path, &manifest)?;
+        }
         match &self.pipeline {
             Build::Pgxs(pgxs) => pgxs.configure(),
             Build::Pgrx(pgrx) => pgrx.configure(),
         }
     }
 
+    /// Returns true if the distribution needs to be compiled, because it
+    /// has not yet been compiled or a source file has been modified since.
+    /// Compares source file modification times to those of the shared
+    /// libraries built by PGXS or in the Cargo `target` directory for pgrx.
+    /// Tooling may skip [`compile`](Self::compile) when it returns false.
+    pub fn needs_rebuild(&self) -> bool {
+        match &self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.needs_rebuild(),
+            Build::Pgrx(pgrx) => pgrx.needs_rebuild(),
+        }
+    }
+
+    /// Returns the files produced by [`compile`](Self::compile) that
+    /// [`install`](Self::install) would install: for PGXS, the shared
+    /// libraries, LLVM bitcode, and the files listed in the Makefile's
+    /// `DATA_built` variable; for pgrx, the shared libraries in the Cargo
+    /// `target` directory. Useful for inspecting or signing the artifacts
+    /// before installing them. Returns an empty list before compiling.
+    pub fn artifacts(&self) -> Result<Vec<PathBuf>, BuildError> {
+        match &self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.artifacts(),
+            Build::Pgrx(pgrx) => pgrx.artifacts(),
+        }
+    }
+
     /// Compiles a distribution on a particular platform and Postgres version.
     pub fn compile(&self) -> Result<(), BuildError> {
         match &self.pipeline {
@@ -110,6 +753,9 @@ impl<P: AsRef<Path>> Builder<P> {
     }
 
     /// Tests a distribution a particular platform and Postgres version.
+    /// Succeeds without running anything if the distribution defines no
+    /// tests. For PGXS, runs `make installcheck`, which tests the installed
+    /// extension, so run it after [`install`](Self::install).
     pub fn test(&self) -> Result<(), BuildError> {
         match &self.pipeline {
             Build::Pgxs(pgxs) => pgxs.test(),
@@ -117,13 +763,200 @@ impl<P: AsRef<Path>> Builder<P> {
         }
     }
 
+    /// Removes the files installed by [`install`](Self::install). For PGXS,
+    /// runs `make uninstall`, with `sudo` if the `pkglibdir` reported by
+    /// `pg_config` isn't writeable by the current user. This depends on
+    /// the Makefile supporting the `uninstall` target, as Makefiles that
+    /// include PGXS do; returns [`BuildError::Unsupported`] if it doesn't.
+    /// The pgrx pipeline does not support uninstall and always returns
+    /// [`BuildError::Unsupported`].
+    pub fn uninstall(&self) -> Result<(), BuildError> {
+        match &self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.uninstall(),
+            Build::Pgrx(pgrx) => pgrx.uninstall(),
+        }
+    }
+
+    /// Removes the files produced by [`compile`](Self::compile), by running
+    /// `make clean` for PGXS or `cargo clean` for pgrx, so that the next
+    /// compile starts fresh, e.g., when building for another Postgres
+    /// version. Succeeds without running anything if there is nothing to
+    /// clean.
+    pub fn clean(&self) -> Result<(), BuildError> {
+        match &self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.clean(),
+            Build::Pgrx(pgrx) => pgrx.clean(),
+        }
+    }
+
+    /// Packages a pgrx distribution by running `cargo pgrx package`, and
+    /// returns the path to the staged output: the directory set by
+    /// [`destdir`](Self::destdir), if any, or `target/<profile>/package` in
+    /// the distribution directory. The PGXS pipeline returns
+    /// [`BuildError::Unsupported`]; stage a PGXS install with
+    /// [`destdir`](Self::destdir) and [`install`](Self::install) instead.
+    pub fn package(&self) -> Result<PathBuf, BuildError> {
+        match &self.pipeline {
+            Build::Pgxs(_) => Err(BuildError::Unsupported(
+                "pgxs",
+                "package; stage the install with destdir instead",
+            )),
+            Build::Pgrx(pgrx) => pgrx.stage(),
+        }
+    }
+
     /// Installs a distribution on a particular platform and Postgres version.
-    pub fn install(&self) -> Result<(), BuildError> {
+    /// Returns the directories from `pg_config` into which it installed,
+    /// whether or not it ran the install with `sudo`, prefixed with the
+    /// staging directory set by [`destdir`](Self::destdir), if any.
+    pub fn install(&self) -> Result<InstallDirs, BuildError> {
+        self.install_files(false).map(|(dirs, _)| dirs)
+    }
+
+    /// Installs the distribution like [`Self::install`] and returns the
+    /// directories into which it installed along with the files it created
+    /// or replaced, identified as for [receipts](Self::receipt). Skips
+    /// identifying the files and returns an empty list unless `record` is
+    /// true or receipts are enabled.
+    fn install_files(&self, record: bool) -> Result<(InstallDirs, Vec<PathBuf>), BuildError> {
+        let (_, cfg) = self.pipeline_config();
+        let dirs = match &self.pipeline {
+            Build::Pgxs(pgxs) => match pgxs.destdir() {
+                Some(dir) => InstallDirs::staged(cfg, &std::path::absolute(dir)?),
+                None => InstallDirs::new(cfg),
+            },
+            Build::Pgrx(_) => InstallDirs::new(cfg),
+        };
+        let (names, before) = if record || self.receipt.is_some() {
+            let names = receipt::names(self.dir().as_ref(), self.meta.name(), &self.extensions());
+            let before = receipt::snapshot(&dirs, &names)?;
+            (names, Some(before))
+        } else {
+            (Vec::new(), None)
+        };
+
         match &self.pipeline {
-            Build::Pgxs(pgxs) => pgxs.install(),
-            Build::Pgrx(pgrx) => pgrx.install(),
+            Build::Pgxs(pgxs) => pgxs.install()?,
+            Build::Pgrx(pgrx) => pgrx.install()?,
+        }
+
+        let files = match before {
+            Some(before) => receipt::installed(&before, &receipt::snapshot(&dirs, &names)?),
+            None => Vec::new(),
+        };
+        if let Some(path) = &self.receipt {
+            receipt::write(path, &self.meta, cfg, &files)?;
         }
+
+        info!(
+            pkglibdir:? = dirs.pkglibdir(),
+            sharedir:? = dirs.sharedir(),
+            destdir:? = dirs.destdir();
+            "installed",
+        );
+        Ok((dirs, files))
+    }
+
+    /// Configures, compiles, installs, and tests the distribution, and
+    /// returns a [`BuildSummary`] recording the platform, the duration and
+    /// outcome of each step, the SHA-256 digests of the
+    /// [artifacts](Self::artifacts) collected before installing, and the
+    /// directories into which it installed and the files it installed
+    /// there. Installs before testing, because the PGXS tests run by
+    /// `make installcheck` require the extension to be installed. Stops at
+    /// the first step to fail and records its error in the summary rather
+    /// than returning it; use [`BuildSummary::succeeded`] to check for
+    /// success.
+    pub fn build_all(&self) -> BuildSummary {
+        let (pipeline, cfg) = self.pipeline_config();
+        let mut summary = BuildSummary {
+            dist: self.meta.name().to_string(),
+            version: self.meta.version().to_string(),
+            pipeline,
+            pg_version: cfg.get("version").map(str::to_string),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            steps: Vec::new(),
+            artifacts: Vec::new(),
+            installed: None,
+            files: Vec::new(),
+        };
+
+        // Each step runs only if the previous step succeeded.
+        let _ = summary.step("configure", |_| self.configure())
+            && summary.step("compile", |_| self.compile())
+            && summary.step("artifacts", |s| {
+                for path in self.artifacts()? {
+                    let data = std::fs::read(&path).map_err(|e| {
+                        BuildError::File("reading", path.display().to_string(), e.kind())
+                    })?;
+                    s.artifacts.push(ArtifactSummary {
+                        path,
+                        sha256: hex::encode(sha2::Sha256::digest(&data)),
+                    });
+                }
+                Ok(())
+            })
+            && summary.step("install", |s| {
+                let (dirs, files) = self.install_files(true)?;
+                s.installed = Some(dirs);
+                s.files = files;
+                Ok(())
+            })
+            && summary.step("test", |_| self.test());
+
+        info!(
+            dist = summary.dist(),
+            version = summary.version(),
+            success = summary.succeeded();
+            "build complete",
+        );
+        summary
+    }
+
+    /// Borrows the directory containing the distribution.
+    fn dir(&self) -> &P {
+        match &self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.dir(),
+            Build::Pgrx(pgrx) => pgrx.dir(),
+        }
+    }
+
+    /// Returns the name of the pipeline and its `pg_config`.
+    fn pipeline_config(&self) -> (&'static str, &PgConfig) {
+        let cfg = match &self.pipeline {
+            Build::Pgxs(pgxs) => pgxs.pg_config(),
+            Build::Pgrx(pgrx) => pgrx.pg_config(),
+        };
+        (self.pipeline.name(), cfg)
+    }
+}
+
+/// Downloads release `version` of distribution `name` from `api` into
+/// `workdir`, validates it against the digests in its release metadata,
+/// unpacks it with [`Api::unpack_release`](api::Api::unpack_release), and
+/// builds it with [`Builder::build_all`], detecting the
+/// pipeline if the metadata specifies none. Deletes the downloaded archive
+/// if the build succeeds, and leaves it and the unpacked directory in
+/// `workdir` for inspection if it fails. Returns an error if downloading or
+/// unpacking fails or no pipeline can build the distribution; otherwise
+/// returns the [`BuildSummary`], which records any build failure.
+pub fn build_release<P: AsRef<Path>>(
+    api: &api::Api,
+    name: &str,
+    version: &semver::Version,
+    cfg: PgConfig,
+    workdir: P,
+) -> Result<BuildSummary, BuildError> {
+    let meta = api.meta(name, version)?;
+    let file = api.download_to(workdir.as_ref(), &meta)?;
+    let dir = api.unpack_release(workdir.as_ref(), &file, &meta)?;
+    let summary = Builder::new(dir, meta, cfg)?.build_all();
+    if summary.succeeded() {
+        std::fs::remove_file(&file)
+            .map_err(|e| BuildError::File("removing", file.display().to_string(), e.kind()))?;
     }
+    Ok(summary)
 }
 
 /// Returns a string representation of `path`.
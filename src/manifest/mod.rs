@@ -0,0 +1,127 @@
+//! Build manifests for frozen builds.
+//!
+//! A build manifest records the inputs to a build: the distribution name,
+//! version, and digests, the build pipeline, the `pg_config` values, the
+//! platform, and any patches applied to the source. Frozen builds record
+//! the manifest on the first run and verify that none of the inputs have
+//! changed on subsequent runs. They also forbid network access by Cargo, so
+//! that pgrx builds fail rather than fetch crates missing from its cache.
+
+use crate::{error::BuildError, pg_config::PgConfig};
+use log::info;
+use pgxn_meta::release::Release;
+use serde_json::{json, Map, Value};
+use std::{fs, io, path::Path};
+
+/// A patch applied to the distribution source before building.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Patch {
+    /// The file name of the patch.
+    pub(crate) file: String,
+    /// The hex-encoded SHA-256 digest of the patch.
+    pub(crate) sha256: String,
+}
+
+/// Returns the build manifest for building `meta` with the `pipeline` and
+/// `cfg` on the current platform. Includes `patches` only if there are any,
+/// so that manifests for unpatched builds omit them.
+pub(crate) fn manifest(meta: &Release, pipeline: &str, cfg: &PgConfig, patches: &[Patch]) -> Value {
+    let digests = meta.release().digests();
+    let mut dig = Map::new();
+    if let Some(d) = digests.sha512() {
+        dig.insert("sha512".to_string(), json!(hex::encode(d)));
+    }
+    if let Some(d) = digests.sha256() {
+        dig.insert("sha256".to_string(), json!(hex::encode(d)));
+    }
+    if let Some(d) = digests.sha1() {
+        dig.insert("sha1".to_string(), json!(hex::encode(d)));
+    }
+
+    let pg_config: Map<String, Value> = cfg.iter().map(|(k, v)| (k.clone(), json!(v))).collect();
+
+    let mut manifest = json!({
+        "dist": meta.name(),
+        "version": meta.version().to_string(),
+        "digests": dig,
+        "pipeline": pipeline,
+        "pg_config": pg_config,
+        "platform": {
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+        },
+    });
+    if !patches.is_empty() {
+        manifest["patches"] = patches
+            .iter()
+            .map(|p| json!({"file": p.file, "sha256": p.sha256}))
+            .collect();
+    }
+    manifest
+}
+
+/// Verifies `manifest` against the manifest recorded in the file at `path`.
+/// Records `manifest` in `path` if the file does not exist. Returns
+/// [`BuildError::Frozen`] listing the inputs that differ from the recorded
+/// manifest.
+pub(crate) fn verify(path: &Path, manifest: &Value) -> Result<(), BuildError> {
+    let recorded = match fs::read(path) {
+        Ok(data) => serde_json::from_slice::<Value>(&data)
+            .map_err(|e| BuildError::parse(path.display(), e))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            info!(file:display = path.display(); "recording build manifest");
+            let data = serde_json::to_vec_pretty(manifest)?;
+            return fs::write(path, data)
+                .map_err(|e| BuildError::File("writing", path.display().to_string(), e.kind()));
+        }
+        Err(e) => {
+            return Err(BuildError::File(
+                "reading",
+                path.display().to_string(),
+                e.kind(),
+            ))
+        }
+    };
+
+    info!(file:display = path.display(); "verifying build manifest");
+    let diffs = diff(&recorded, manifest);
+    if diffs.is_empty() {
+        return Ok(());
+    }
+    Err(BuildError::Frozen(path.display().to_string(), diffs))
+}
+
+/// Returns the dotted paths to the properties that differ between `left`
+/// and `right`, in sorted order. Descends into objects present on both
+/// sides.
+fn diff(left: &Value, right: &Value) -> Vec<String> {
+    let mut diffs = Vec::new();
+    diff_into(&mut diffs, "", left, right);
+    diffs.sort();
+    diffs
+}
+
+/// Appends to `diffs` the paths under `prefix` that differ between `left`
+/// and `right`.
+fn diff_into(diffs: &mut Vec<String>, prefix: &str, left: &Value, right: &Value) {
+    match (left, right) {
+        (Value::Object(l), Value::Object(r)) => {
+            for key in l.keys().chain(r.keys().filter(|k| !l.contains_key(*k))) {
+                let path = if prefix.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                match (l.get(key), r.get(key)) {
+                    (Some(lv), Some(rv)) => diff_into(diffs, &path, lv, rv),
+                    _ => diffs.push(path),
+                }
+            }
+        }
+        (l, r) if l != r => diffs.push(prefix.to_string()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,143 @@
+use super::*;
+use crate::tests::release_meta;
+use std::collections::HashMap;
+use tempfile::tempdir;
+
+#[test]
+fn manifest_fn() {
+    let meta = Release::try_from(release_meta("pgxs")).unwrap();
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "version".to_string(),
+        "PostgreSQL 17.2".to_string(),
+    )]));
+    assert_eq!(
+        json!({
+            "dist": "pair",
+            "version": "0.1.8",
+            "digests": {
+                "sha512": "b353b5a82b3b54e95f4a2859e7a2bd0648abcb35a7c3612b126c2c75438fc2f8e8ee1f19e61f30fa54d7bb64bcf217ed1264722b497bcb613f82d78751515b67",
+            },
+            "pipeline": "pgxs",
+            "pg_config": {"version": "PostgreSQL 17.2"},
+            "platform": {
+                "os": std::env::consts::OS,
+                "arch": std::env::consts::ARCH,
+            },
+        }),
+        manifest(&meta, "pgxs", &cfg, &[]),
+    );
+
+    // Record patches.
+    let patches = [
+        Patch {
+            file: "fix.patch".to_string(),
+            sha256: "abc".to_string(),
+        },
+        Patch {
+            file: "port.patch".to_string(),
+            sha256: "def".to_string(),
+        },
+    ];
+    let val = manifest(&meta, "pgxs", &cfg, &patches);
+    assert_eq!(
+        json!([
+            {"file": "fix.patch", "sha256": "abc"},
+            {"file": "port.patch", "sha256": "def"},
+        ]),
+        val["patches"]
+    );
+}
+
+#[test]
+fn diff_fn() {
+    for (name, left, right, exp) in [
+        ("empty", json!({}), json!({}), vec![]),
+        (
+            "same",
+            json!({"a": 1, "b": {"c": 2}}),
+            json!({"a": 1, "b": {"c": 2}}),
+            vec![],
+        ),
+        (
+            "changed",
+            json!({"a": 1, "b": 2}),
+            json!({"a": 1, "b": 3}),
+            vec!["b"],
+        ),
+        (
+            "nested",
+            json!({"b": {"c": 2, "d": 1}}),
+            json!({"b": {"c": 3, "d": 1}}),
+            vec!["b.c"],
+        ),
+        ("added", json!({"a": 1}), json!({"a": 1, "z": 2}), vec!["z"]),
+        (
+            "removed",
+            json!({"a": 1, "b": {"c": 2}}),
+            json!({"a": 1, "b": {}}),
+            vec!["b.c"],
+        ),
+        ("type", json!({"a": {"b": 1}}), json!({"a": 1}), vec!["a"]),
+        (
+            "sorted",
+            json!({"z": 1, "a": 1}),
+            json!({"z": 2, "a": 2}),
+            vec!["a", "z"],
+        ),
+    ] {
+        assert_eq!(exp, diff(&left, &right), "{name}");
+    }
+}
+
+#[test]
+fn verify_fn() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let path = tmp.as_ref().join("manifest.json");
+    let man = json!({"dist": "pair", "pg_config": {"bindir": "/usr/bin"}});
+
+    // Record the manifest.
+    verify(&path, &man)?;
+    let recorded: Value = serde_json::from_slice(&fs::read(&path)?)?;
+    assert_eq!(man, recorded);
+
+    // Verify it.
+    verify(&path, &man)?;
+
+    // Change it.
+    let changed = json!({"dist": "pair", "pg_config": {"bindir": "/opt/bin"}});
+    match verify(&path, &changed) {
+        Ok(_) => panic!("changed manifest unexpectedly verified"),
+        Err(e) => assert_eq!(
+            format!(
+                "build inputs differ from manifest {}: pg_config.bindir",
+                path.display()
+            ),
+            e.to_string()
+        ),
+    }
+
+    // Corrupt it.
+    fs::write(&path, "{")?;
+    match verify(&path, &man) {
+        Ok(_) => panic!("corrupt manifest unexpectedly verified"),
+        Err(e) => assert_eq!(
+            format!(
+                "cannot parse {}: EOF while parsing an object at line 1 column 1",
+                path.display()
+            ),
+            e.to_string()
+        ),
+    }
+
+    // Cannot write it.
+    let path = tmp.as_ref().join("nonesuch").join("manifest.json");
+    match verify(&path, &man) {
+        Ok(_) => panic!("unwritable manifest unexpectedly recorded"),
+        Err(e) => assert_eq!(
+            format!("writing {}: entity not found", path.display()),
+            e.to_string()
+        ),
+    }
+
+    Ok(())
+}
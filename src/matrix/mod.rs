@@ -0,0 +1,101 @@
+//! Build matrices for multiple Postgres installations.
+//!
+//! A [`BuildMatrix`] builds a single distribution for several Postgres
+//! installations, each described by its own `PgConfig`, in one call.
+
+use crate::{error::BuildError, pg_config::PgConfig, Builder, InstallDirs};
+use log::info;
+use pgxn_meta::release::Release;
+use std::path::Path;
+
+/// BuildMatrix builds and installs a PGXN release for multiple Postgres
+/// installations.
+#[derive(Debug, PartialEq)]
+pub struct BuildMatrix<P: AsRef<Path>> {
+    targets: Vec<(String, Builder<P>)>,
+}
+
+impl<P: AsRef<Path>> BuildMatrix<P> {
+    /// Creates a new BuildMatrix to build the release described by `meta`
+    /// for each of `targets`, pairs of a directory containing the unpacked
+    /// distribution and the `PgConfig` for a Postgres installation.
+    /// Pipelines build in their directory, so each target requires its own
+    /// copy of the distribution.
+    pub fn new<I>(meta: Release, targets: I) -> Result<Self, BuildError>
+    where
+        I: IntoIterator<Item = (P, PgConfig)>,
+    {
+        // Release doesn't implement Clone, so copy it via its JSON.
+        let json = serde_json::to_value(&meta)?;
+        let mut list = Vec::new();
+        for (dir, cfg) in targets {
+            let label = label(&cfg);
+            let meta = serde_json::from_value(json.clone())?;
+            list.push((label, Builder::new(dir, meta, cfg)?));
+        }
+        Ok(BuildMatrix { targets: list })
+    }
+
+    /// Returns an iterator over the Postgres version of each target, in
+    /// build order, such as `PostgreSQL 17.2`, and its Builder.
+    pub fn builders(&self) -> impl Iterator<Item = (&str, &Builder<P>)> {
+        self.targets.iter().map(|(v, b)| (v.as_str(), b))
+    }
+
+    /// Configures the distribution for each Postgres installation.
+    pub fn configure(&self) -> Result<(), BuildError> {
+        self.each("configuring", Builder::configure)
+    }
+
+    /// Compiles the distribution for each Postgres installation.
+    pub fn compile(&self) -> Result<(), BuildError> {
+        self.each("compiling", Builder::compile)
+    }
+
+    /// Tests the distribution for each Postgres installation.
+    pub fn test(&self) -> Result<(), BuildError> {
+        self.each("testing", Builder::test)
+    }
+
+    /// Cleans the build for each Postgres installation.
+    pub fn clean(&self) -> Result<(), BuildError> {
+        self.each("cleaning", Builder::clean)
+    }
+
+    /// Installs the distribution into each Postgres installation. Returns
+    /// the directories into which it installed for each, in build order.
+    pub fn install(&self) -> Result<Vec<InstallDirs>, BuildError> {
+        let mut dirs = Vec::with_capacity(self.targets.len());
+        self.each("installing", |b| {
+            dirs.push(b.install()?);
+            Ok(())
+        })?;
+        Ok(dirs)
+    }
+
+    /// Calls `step` for each target's Builder, logging `action` and the
+    /// target Postgres version first. Stops and returns the error from the
+    /// first call to fail.
+    fn each<F>(&self, action: &str, mut step: F) -> Result<(), BuildError>
+    where
+        F: FnMut(&Builder<P>) -> Result<(), BuildError>,
+    {
+        for (version, builder) in &self.targets {
+            info!(pg = version.as_str(); "{action}");
+            step(builder)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns a label identifying the Postgres installation described by
+/// `cfg`: its `version`, if known, and otherwise its `bindir`.
+fn label(cfg: &PgConfig) -> String {
+    cfg.get("version")
+        .map(String::from)
+        .or_else(|| cfg.bindir().map(|d| d.display().to_string()))
+        .unwrap_or_else(|| "unknown Postgres".to_string())
+}
+
+#[cfg(test)]
+mod tests;
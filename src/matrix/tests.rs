@@ -0,0 +1,91 @@
+use super::*;
+use crate::tests::{release_meta, with_mock_cargo};
+use std::collections::HashMap;
+use tempfile::tempdir;
+
+fn cfg(version: &str) -> PgConfig {
+    PgConfig::from_map(HashMap::from([(
+        "version".to_string(),
+        version.to_string(),
+    )]))
+}
+
+#[test]
+fn matrix() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let dirs: Vec<_> = ["16", "17"].iter().map(|d| tmp.path().join(d)).collect();
+    for dir in &dirs {
+        std::fs::create_dir(dir)?;
+    }
+    let meta = Release::try_from(release_meta("pgrx")).unwrap();
+    let matrix = BuildMatrix::new(
+        meta,
+        [
+            (dirs[0].as_path(), cfg("PostgreSQL 16.6")),
+            (dirs[1].as_path(), cfg("PostgreSQL 17.2")),
+        ],
+    )?;
+
+    // Each target has a builder with its own directory and config.
+    let targets: Vec<_> = matrix.builders().collect();
+    assert_eq!(2, targets.len());
+    for (i, (exp, (version, builder))) in ["PostgreSQL 16.6", "PostgreSQL 17.2"]
+        .into_iter()
+        .zip(targets)
+        .enumerate()
+    {
+        assert_eq!(exp, version);
+        assert_eq!("pair", builder.meta.name());
+        let exp = Builder::new(
+            dirs[i].as_path(),
+            Release::try_from(release_meta("pgrx")).unwrap(),
+            cfg(exp),
+        )?;
+        assert_eq!(&exp, builder);
+    }
+
+    // Every step succeeds with a mock cargo.
+    with_mock_cargo("echo", || -> Result<(), BuildError> {
+        matrix.configure()?;
+        matrix.compile()?;
+        matrix.test()?;
+        assert_eq!(2, matrix.install()?.len());
+        matrix.clean()?;
+        Ok(())
+    })?;
+
+    // An empty matrix does nothing.
+    let meta = Release::try_from(release_meta("pgrx")).unwrap();
+    let matrix = BuildMatrix::<&Path>::new(meta, [])?;
+    assert_eq!(0, matrix.builders().count());
+    matrix.compile()?;
+    assert!(matrix.install()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn matrix_err() -> Result<(), BuildError> {
+    // PGXS compile fails without a Makefile.
+    let tmp = tempdir()?;
+    let meta = Release::try_from(release_meta("pgxs")).unwrap();
+    let matrix = BuildMatrix::new(meta, [(tmp.as_ref(), cfg("PostgreSQL 17.2"))])?;
+    assert!(matrix.compile().is_err());
+    Ok(())
+}
+
+#[test]
+fn labels() {
+    assert_eq!("PostgreSQL 17.2", label(&cfg("PostgreSQL 17.2")));
+    assert_eq!(
+        "/pg/bin",
+        label(&PgConfig::from_map(HashMap::from([(
+            "bindir".to_string(),
+            "/pg/bin".to_string()
+        )])))
+    );
+    assert_eq!(
+        "unknown Postgres",
+        label(&PgConfig::from_map(HashMap::new()))
+    );
+}
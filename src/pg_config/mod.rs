@@ -1,12 +1,15 @@
 use std::{
     collections::{self, HashMap},
+    env,
     io::{BufRead, BufReader},
     path::Path,
     process::Command,
 };
 
-use crate::error::BuildError;
+use crate::{error::BuildError, pipeline};
 
+/// PgConfig holds the configuration of a Postgres installation, as
+/// reported by `pg_config`.
 #[derive(Debug, PartialEq, Clone)]
 pub struct PgConfig(HashMap<String, String>);
 
@@ -18,11 +21,12 @@ impl PgConfig {
         let mut cmd = Command::new(pg_config.as_ref().as_os_str());
         let out = cmd
             .output()
-            .map_err(|e| BuildError::Command(format!("{:?}", cmd), e.kind().to_string()))?;
+            .map_err(|e| BuildError::Command(format!("{:?}", cmd), e.kind().to_string(), None))?;
         if !out.status.success() {
             return Err(BuildError::Command(
                 format!("{:?}", cmd),
                 String::from_utf8_lossy(&out.stderr).to_string(),
+                Some(out.status),
             ));
         }
 
@@ -41,6 +45,19 @@ impl PgConfig {
         Ok(PgConfig(cfg))
     }
 
+    /// Locates `pg_config` and returns a `PgConfig` containing its
+    /// key/value pairs. Uses the path in the `PG_CONFIG` environment
+    /// variable, if set. Otherwise searches the directories in `PATH` for an
+    /// executable named `pg_config`, returning [`BuildError::NotFound`] with
+    /// the list of searched directories if none is found.
+    pub fn find_in_path() -> Result<Self, BuildError> {
+        if let Some(path) = env::var_os("PG_CONFIG").filter(|p| !p.is_empty()) {
+            return Self::new(path);
+        }
+
+        Self::new(pipeline::find_in_path("pg_config")?)
+    }
+
     #[cfg(test)]
     pub(crate) fn from_map(map: HashMap<String, String>) -> Self {
         Self(map)
@@ -55,6 +72,96 @@ impl PgConfig {
         }
     }
 
+    /// Returns the directory for user executables, from `bindir`.
+    pub fn bindir(&self) -> Option<&Path> {
+        self.get("bindir").map(Path::new)
+    }
+
+    /// Returns the directory for loadable modules, from `pkglibdir`.
+    pub fn pkglibdir(&self) -> Option<&Path> {
+        self.get("pkglibdir").map(Path::new)
+    }
+
+    /// Returns the directory for architecture-independent support files,
+    /// from `sharedir`.
+    pub fn sharedir(&self) -> Option<&Path> {
+        self.get("sharedir").map(Path::new)
+    }
+
+    /// Returns the directory for documentation files, from `docdir`.
+    pub fn docdir(&self) -> Option<&Path> {
+        self.get("docdir").map(Path::new)
+    }
+
+    /// Returns the directory for C header files for server programming,
+    /// from `includedir-server`.
+    pub fn includedir_server(&self) -> Option<&Path> {
+        self.get("includedir-server").map(Path::new)
+    }
+
+    /// Returns the major version of Postgres parsed from the `version`
+    /// value, such as `17` for `PostgreSQL 17.2` or `18` for
+    /// `PostgreSQL 18devel`. For releases prior to 10, where the major
+    /// version has two parts, returns the first part times 100 plus the
+    /// second, as in `server_version_num` divided by 100, such as `906` for
+    /// `PostgreSQL 9.6.24` and `905` for `PostgreSQL 9.5.25`. Returns
+    /// [`None`] if `version` is missing or cannot be parsed.
+    pub fn major_version(&self) -> Option<u32> {
+        let v = self.server_version()?;
+        let major = u32::try_from(v.major).ok()?;
+        if major < 10 {
+            return major
+                .checked_mul(100)?
+                .checked_add(u32::try_from(v.minor).ok()?);
+        }
+        Some(major)
+    }
+
+    /// Returns the version of Postgres parsed from the `version` value.
+    /// Missing minor and patch parts default to zero, and any pre-release
+    /// suffix becomes the pre-release, so that `PostgreSQL 17.2` returns
+    /// `17.2.0`, `PostgreSQL 9.6.24` returns `9.6.24`, and
+    /// `PostgreSQL 18beta1` returns `18.0.0-beta1`. Returns [`None`] if
+    /// `version` is missing or cannot be parsed by
+    /// [`parse_version`](Self::parse_version).
+    pub fn server_version(&self) -> Option<semver::Version> {
+        Self::parse_version(self.get("version")?)
+    }
+
+    /// Parses a `pg_config --version` string, such as `PostgreSQL 17.2`,
+    /// `PostgreSQL 18beta1`, or `PostgreSQL 16.3 (Debian 16.3-1.pgdg120+1)`,
+    /// into a version as described for
+    /// [`server_version`](Self::server_version), ignoring any vendor
+    /// suffix. Returns [`None`] if `version` does not start with
+    /// `PostgreSQL` followed by a version number.
+    pub fn parse_version(version: &str) -> Option<semver::Version> {
+        let version = version
+            .trim()
+            .strip_prefix("PostgreSQL")?
+            .split_whitespace()
+            .next()?;
+
+        // Split the numeric parts from any pre-release suffix, ignoring
+        // trailing punctuation.
+        let idx = version
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(version.len());
+        let (nums, rest) = version.split_at(idx);
+        let pre = &rest[..rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len())];
+        let mut parts = nums.split('.').map(|p| p.parse::<u64>().ok());
+        let mut v = semver::Version::new(
+            parts.next()??,
+            parts.next().flatten().unwrap_or(0),
+            parts.next().flatten().unwrap_or(0),
+        );
+        if !pre.is_empty() {
+            v.pre = semver::Prerelease::new(pre).ok()?;
+        }
+        Some(v)
+    }
+
     /// An iterator visiting all `pg_config` key-value pairs in arbitrary
     /// order. Keys are lowercase. The iterator element type is
     /// `(&'a str, &'a str)`.
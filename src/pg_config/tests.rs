@@ -1,6 +1,7 @@
 use super::*;
 use crate::tests::compile_mock;
 use assertables::*;
+use std::{env, fs};
 use tempfile::tempdir;
 
 #[test]
@@ -112,3 +113,168 @@ fn pg_config_err() {
         }
     }
 }
+
+#[test]
+fn find_in_path() -> Result<(), BuildError> {
+    // Build a mock pg_config in a bin directory.
+    let tmp = tempdir()?;
+    let bin = tmp.path().join("bin");
+    let empty = tmp.path().join("empty");
+    fs::create_dir(&bin)?;
+    fs::create_dir(&empty)?;
+    let exe = bin.join(format!("pg_config{}", env::consts::EXE_SUFFIX));
+    compile_mock("pg_config", &exe.display().to_string());
+    let path = env::join_paths([&empty, &bin]).unwrap();
+
+    // Find it in the path.
+    temp_env::with_vars(
+        [("PATH", Some(path.as_os_str())), ("PG_CONFIG", None)],
+        || {
+            let cfg = PgConfig::find_in_path().unwrap();
+            assert_eq!(Some("PostgreSQL 17.2"), cfg.get("version"));
+        },
+    );
+
+    // PG_CONFIG overrides PATH.
+    temp_env::with_vars(
+        [
+            ("PATH", Some(empty.as_os_str())),
+            ("PG_CONFIG", Some(exe.as_os_str())),
+        ],
+        || {
+            let cfg = PgConfig::find_in_path().unwrap();
+            assert_eq!(Some("PostgreSQL 17.2"), cfg.get("version"));
+        },
+    );
+
+    // Report the directories searched when not found.
+    let path = env::join_paths([&empty, &tmp.path().join("nonesuch")]).unwrap();
+    temp_env::with_vars(
+        [("PATH", Some(path.as_os_str())), ("PG_CONFIG", None)],
+        || match PgConfig::find_in_path() {
+            Ok(_) => panic!("find_in_path unexpectedly succeeded"),
+            Err(e) => assert_eq!(
+                format!(
+                    "cannot find pg_config in PATH; searched {}, {}",
+                    empty.display(),
+                    tmp.path().join("nonesuch").display(),
+                ),
+                e.to_string(),
+            ),
+        },
+    );
+
+    Ok(())
+}
+
+#[test]
+fn server_version() {
+    for (name, version, exp) in [
+        ("17.2", "PostgreSQL 17.2", Some("17.2.0")),
+        ("10.0", "PostgreSQL 10.0", Some("10.0.0")),
+        ("9.6", "PostgreSQL 9.6.24", Some("9.6.24")),
+        ("9.4", "PostgreSQL 9.4.0", Some("9.4.0")),
+        ("devel", "PostgreSQL 18devel", Some("18.0.0-devel")),
+        ("beta", "PostgreSQL 18beta1", Some("18.0.0-beta1")),
+        ("rc", "PostgreSQL 17rc1", Some("17.0.0-rc1")),
+        ("old beta", "PostgreSQL 9.6beta2", Some("9.6.0-beta2")),
+        (
+            "vendor",
+            "PostgreSQL 16.4 (Debian 16.4-1.pgdg120+1)",
+            Some("16.4.0"),
+        ),
+        ("whitespace", "  PostgreSQL 15.1\n", Some("15.1.0")),
+        ("not postgres", "MySQL 8.0", None),
+        ("no number", "PostgreSQL devel", None),
+        ("empty", "", None),
+    ] {
+        let cfg = PgConfig::from_map(HashMap::from([(
+            "version".to_string(),
+            version.to_string(),
+        )]));
+        let exp = exp.map(|v| semver::Version::parse(v).unwrap());
+        assert_eq!(exp, cfg.server_version(), "{name}");
+        assert_eq!(
+            exp.map(|v| match v.major {
+                m if m < 10 => (m * 100 + v.minor) as u32,
+                m => m as u32,
+            }),
+            cfg.major_version(),
+            "{name} major"
+        );
+    }
+
+    // Two-part majors before 10, single-number majors from 10.
+    let major = |v: &str| {
+        PgConfig::from_map(HashMap::from([("version".to_string(), v.to_string())])).major_version()
+    };
+    assert_eq!(Some(906), major("PostgreSQL 9.6.24"));
+    assert_eq!(Some(905), major("PostgreSQL 9.5.25"));
+    assert_ne!(major("PostgreSQL 9.5.25"), major("PostgreSQL 9.6.24"));
+    assert_eq!(Some(904), major("PostgreSQL 9.4beta1"));
+    assert_eq!(Some(10), major("PostgreSQL 10.23"));
+    assert_eq!(Some(17), major("PostgreSQL 17.2"));
+
+    // Missing version.
+    let cfg = PgConfig::from_map(HashMap::new());
+    assert_eq!(None, cfg.server_version());
+    assert_eq!(None, cfg.major_version());
+}
+
+#[test]
+fn parse_version() {
+    for (name, version, exp) in [
+        ("release", "PostgreSQL 17.2", Some("17.2.0")),
+        ("beta", "PostgreSQL 18beta1", Some("18.0.0-beta1")),
+        (
+            "debian",
+            "PostgreSQL 16.3 (Debian 16.3-1.pgdg120+1)",
+            Some("16.3.0"),
+        ),
+        (
+            "ubuntu",
+            "PostgreSQL 14.12 (Ubuntu 14.12-0ubuntu0.22.04.1)",
+            Some("14.12.0"),
+        ),
+        ("homebrew", "PostgreSQL 17.2 (Homebrew)", Some("17.2.0")),
+        ("comma", "PostgreSQL 15.4, compiled by gcc", Some("15.4.0")),
+        ("three part", "PostgreSQL 9.6.24", Some("9.6.24")),
+        ("no space", "PostgreSQL17.2", Some("17.2.0")),
+        ("lowercase", "postgresql 17.2", None),
+        ("vendor only", "PostgreSQL (Debian)", None),
+        ("prefix only", "PostgreSQL", None),
+    ] {
+        let exp = exp.map(|v| semver::Version::parse(v).unwrap());
+        assert_eq!(exp, PgConfig::parse_version(version), "{name}");
+    }
+}
+
+#[test]
+fn dirs() {
+    let cfg = PgConfig::from_map(HashMap::from([
+        ("bindir".to_string(), "/pg/bin".to_string()),
+        ("pkglibdir".to_string(), "/pg/lib".to_string()),
+        ("sharedir".to_string(), "/pg/share".to_string()),
+        ("docdir".to_string(), "/pg/doc".to_string()),
+        (
+            "includedir-server".to_string(),
+            "/pg/include/server".to_string(),
+        ),
+    ]));
+    assert_eq!(Some(Path::new("/pg/bin")), cfg.bindir());
+    assert_eq!(Some(Path::new("/pg/lib")), cfg.pkglibdir());
+    assert_eq!(Some(Path::new("/pg/share")), cfg.sharedir());
+    assert_eq!(Some(Path::new("/pg/doc")), cfg.docdir());
+    assert_eq!(
+        Some(Path::new("/pg/include/server")),
+        cfg.includedir_server()
+    );
+
+    // Missing directories.
+    let cfg = PgConfig::from_map(HashMap::new());
+    assert_eq!(None, cfg.bindir());
+    assert_eq!(None, cfg.pkglibdir());
+    assert_eq!(None, cfg.sharedir());
+    assert_eq!(None, cfg.docdir());
+    assert_eq!(None, cfg.includedir_server());
+}
@@ -3,9 +3,18 @@
 //! [pgrx]: https://github.com/pgcentralfoundation/pgrx
 
 use crate::error::BuildError;
+use crate::ignore::Ignore;
 use crate::pg_config::PgConfig;
-use crate::pipeline::Pipeline;
-use std::path::Path;
+use crate::pipeline::{self, Captured, Pipeline, Probe};
+use log::{debug, info, warn};
+use std::{
+    cell::RefCell,
+    env::{self, consts::EXE_SUFFIX},
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 /// Builder implementation for [pgrx] Pipelines.
 ///
@@ -14,11 +23,315 @@ use std::path::Path;
 pub(crate) struct Pgrx<P: AsRef<Path>> {
     cfg: PgConfig,
     dir: P,
+    probe: Probe,
+    wrapper: Vec<String>,
+    c_locale: bool,
+    sudo_program: String,
+    sudo_non_interactive: bool,
+    skip_init: bool,
+    package: Option<String>,
+    features: Vec<String>,
+    profile: Option<String>,
+    destdir: Option<PathBuf>,
+    env: Vec<(String, Option<String>)>,
+    timeout: Option<Duration>,
+    output_cap: usize,
+    last_output: RefCell<Option<Captured>>,
+}
+
+impl<P: AsRef<Path>> Pgrx<P> {
+    /// Sets the Probe used to test write access to the install directory.
+    pub(crate) fn set_probe(&mut self, probe: Probe) {
+        self.probe = probe;
+    }
+
+    /// Sets the wrapper command and arguments with which to run every
+    /// command.
+    pub(crate) fn set_wrapper(&mut self, wrapper: Vec<String>) {
+        self.wrapper = wrapper;
+    }
+
+    /// Sets a staging directory for [`Pipeline::install`], which cargo-pgrx
+    /// does not support, and the output directory for [`Self::stage`].
+    pub(crate) fn set_destdir(&mut self, dir: Option<PathBuf>) {
+        self.destdir = dir;
+    }
+
+    /// Sets whether to run commands in the C locale.
+    pub(crate) fn set_c_locale(&mut self, yes: bool) {
+        self.c_locale = yes;
+    }
+
+    /// Sets the program with which to run commands with elevated
+    /// privileges.
+    pub(crate) fn set_sudo_program(&mut self, program: String) {
+        self.sudo_program = program;
+    }
+
+    /// Sets whether to pass `-n` to the sudo program.
+    pub(crate) fn set_sudo_non_interactive(&mut self, yes: bool) {
+        self.sudo_non_interactive = yes;
+    }
+
+    /// Sets the maximum time a command may run before it is killed.
+    pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Sets the maximum number of bytes of each output stream to keep.
+    pub(crate) fn set_output_cap(&mut self, cap: usize) {
+        self.output_cap = cap;
+    }
+
+    /// Sets the environment variable `key` to `val` for every command, or
+    /// removes it if `val` is [`None`].
+    pub(crate) fn set_env(&mut self, key: String, val: Option<String>) {
+        pipeline::set_env(&mut self.env, key, val);
+    }
+
+    /// Sets whether [`Pipeline::configure`] should skip running
+    /// `cargo pgrx init`, for environments that manage it externally.
+    pub(crate) fn skip_init(&mut self, yes: bool) {
+        self.skip_init = yes;
+    }
+
+    /// Sets the package to build in a Cargo workspace, overriding
+    /// detection of the workspace member that depends on pgrx.
+    pub(crate) fn set_package(&mut self, package: Option<String>) {
+        self.package = package;
+    }
+
+    /// Sets the Cargo features to enable when building, testing, and
+    /// installing.
+    pub(crate) fn set_features(&mut self, features: Vec<String>) {
+        self.features = features;
+    }
+
+    /// Sets the Cargo profile with which to build, test, and install.
+    /// Defaults to the release profile when `None`.
+    pub(crate) fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
+    /// Returns the package to build: the package set by
+    /// [`Self::set_package`], if any, and otherwise the only member of a
+    /// Cargo workspace that depends on pgrx. Returns `None` if `Cargo.toml`
+    /// is not a workspace manifest, if the workspace root package itself
+    /// depends on pgrx, or if no member does. Returns
+    /// [`BuildError::SelectPackage`] if multiple members depend on pgrx.
+    fn package(&self) -> Result<Option<String>, BuildError> {
+        if self.package.is_some() {
+            return Ok(self.package.clone());
+        }
+        let dir = self.dir.as_ref();
+        let Ok(root) = cargo_toml::Manifest::from_path(dir.join("Cargo.toml")) else {
+            return Ok(None);
+        };
+        if root.workspace.is_none() || root.dependencies.contains_key("pgrx") {
+            return Ok(None);
+        }
+
+        let mut names = pgrx_members(dir, &root);
+        match names.len() {
+            0 => Ok(None),
+            1 => {
+                let name = names.pop();
+                debug!(package = name.as_deref().unwrap_or_default(); "selected pgrx package");
+                Ok(name)
+            }
+            _ => Err(BuildError::SelectPackage(names)),
+        }
+    }
+
+    /// Runs `cargo pgrx --version` to check that cargo-pgrx is installed
+    /// and returns the version it reports, or [`None`] if the version
+    /// cannot be parsed. Warns if the version does not satisfy the pgrx
+    /// requirement in `Cargo.toml`. Returns [`BuildError::NoCargoPgrx`] if
+    /// the command fails.
+    pub(crate) fn available(&self) -> Result<Option<semver::Version>, BuildError> {
+        let out = match self.capture("cargo", ["pgrx", "--version"], false) {
+            Ok(out) => out,
+            Err(BuildError::Command(_, msg, _)) => {
+                return Err(BuildError::NoCargoPgrx(msg.trim().to_string()))
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Output looks like `cargo-pgrx 0.12.6`.
+        let stdout = out.stdout;
+        let Some(version) = stdout
+            .split_whitespace()
+            .last()
+            .and_then(|v| semver::Version::parse(v).ok())
+        else {
+            warn!(output = stdout.trim(); "cannot parse cargo pgrx version");
+            return Ok(None);
+        };
+        debug!(version:display = version; "found cargo-pgrx");
+
+        if let Some(req) = self.pgrx_requirement() {
+            if !req.matches(&version) {
+                warn!(
+                    version:display = version, requires:display = req;
+                    "cargo-pgrx version does not match the pgrx dependency"
+                );
+            }
+        }
+        Ok(Some(version))
+    }
+
+    /// Returns the version requirement for the pgrx dependency in
+    /// `Cargo.toml` or its workspace dependencies, if any.
+    fn pgrx_requirement(&self) -> Option<semver::VersionReq> {
+        let cargo = cargo_toml::Manifest::from_path(self.dir.as_ref().join("Cargo.toml")).ok()?;
+        let dep = cargo.dependencies.get("pgrx").or_else(|| {
+            cargo
+                .workspace
+                .as_ref()
+                .and_then(|ws| ws.dependencies.get("pgrx"))
+        })?;
+        semver::VersionReq::parse(dep.req()).ok()
+    }
+
+    /// Returns the name of the Cargo `target` subdirectory for the
+    /// configured profile: `release` by default and `debug` for the `dev`
+    /// profile.
+    fn target_dir(&self) -> &str {
+        match self.profile.as_deref() {
+            None => "release",
+            Some("dev") => "debug",
+            Some(profile) => profile,
+        }
+    }
+
+    /// Returns the shared libraries in the Cargo `target` subdirectory
+    /// `profile`.
+    fn libs(&self, profile: &str) -> Vec<PathBuf> {
+        let dir = self.dir.as_ref().join("target").join(profile);
+        match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .map_while(Result::ok)
+                .map(|e| e.path())
+                .filter(|p| p.is_file() && pipeline::has_extension(p, &pipeline::SHLIB_EXTENSIONS))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Returns the arguments for `cargo pgrx <cmd>`: `--profile` with the
+    /// profile set by [`Self::set_profile`] or else `--release`, followed
+    /// by `--package` with the package returned by [`Self::package`] and
+    /// `--features` with the features set by [`Self::set_features`], if
+    /// any.
+    fn cargo_args(&self, cmd: &str) -> Result<Vec<OsString>, BuildError> {
+        let mut args: Vec<OsString> = vec!["pgrx".into(), cmd.into()];
+        match &self.profile {
+            Some(profile) => {
+                args.push("--profile".into());
+                args.push(profile.into());
+            }
+            None => args.push("--release".into()),
+        }
+        if let Some(pkg) = self.package()? {
+            args.push("--package".into());
+            args.push(pkg.into());
+        }
+        if !self.features.is_empty() {
+            args.push("--features".into());
+            args.push(self.features.join(",").into());
+        }
+        Ok(args)
+    }
+
+    /// Runs `cargo pgrx init --pg<major> <pg_config>` unless the pgrx home
+    /// directory already registers the `pg_config` in the `bindir` reported
+    /// by the PgConfig for its Postgres major version, or init is disabled
+    /// by [`Self::skip_init`]. Re-runs init if pgrx registers a different
+    /// `pg_config` for the version. Skips init if the PgConfig does not
+    /// report the major version and `bindir`.
+    fn init(&self) -> Result<(), BuildError> {
+        if self.skip_init {
+            return Ok(());
+        }
+        let (Some(major), Some(bin)) = (self.cfg.major_version(), self.cfg.bindir()) else {
+            warn!("cannot determine Postgres version; skipping cargo pgrx init");
+            return Ok(());
+        };
+        let name = format!("pg{major}");
+        let pg_config = bin.join(format!("pg_config{EXE_SUFFIX}"));
+        if is_registered(&name, &pg_config) {
+            debug!(version = name.as_str(); "pgrx already initialized");
+            return Ok(());
+        }
+
+        info!(version = name.as_str(); "initializing pgrx");
+        let args: [OsString; 4] = [
+            "pgrx".into(),
+            "init".into(),
+            format!("--{name}").into(),
+            pg_config.into(),
+        ];
+        self.run("cargo", args, false)
+    }
+
+    /// Runs `cargo pgrx <cmd>` with the arguments returned by
+    /// [`Self::cargo_args`], passing `--pg-config` with the path to
+    /// `pg_config` in the `bindir` reported by the PgConfig, if any,
+    /// followed by `extra`.
+    fn cargo_pgrx(&self, cmd: &str, extra: Vec<OsString>, sudo: bool) -> Result<(), BuildError> {
+        let mut args = self.cargo_args(cmd)?;
+        if let Some(bin) = self.cfg.bindir() {
+            args.push("--pg-config".into());
+            args.push(bin.join(format!("pg_config{EXE_SUFFIX}")).into());
+        }
+        args.extend(extra);
+        self.run("cargo", args, sudo)
+    }
+
+    /// Runs `cargo pgrx package` with the arguments described by
+    /// [`Self::cargo_pgrx`] to stage the extension's install files, the way
+    /// a PGXS install with `DESTDIR` does. Passes `--out-dir` with the
+    /// absolute path to the directory set by [`Self::set_destdir`], or to
+    /// `package` in the Cargo `target` subdirectory for the configured
+    /// profile, and returns that path.
+    pub(crate) fn stage(&self) -> Result<PathBuf, BuildError> {
+        let out = match &self.destdir {
+            Some(dir) => std::path::absolute(dir)?,
+            None => std::path::absolute(self.dir.as_ref())?
+                .join("target")
+                .join(self.target_dir())
+                .join("package"),
+        };
+        info!(dir:display = out.display(); "packaging extension");
+        self.cargo_pgrx(
+            "package",
+            vec!["--out-dir".into(), out.clone().into()],
+            false,
+        )?;
+        Ok(out)
+    }
 }
 
 impl<P: AsRef<Path>> Pipeline<P> for Pgrx<P> {
     fn new(dir: P, cfg: PgConfig) -> Self {
-        Pgrx { cfg, dir }
+        Pgrx {
+            cfg,
+            dir,
+            probe: Probe::default(),
+            wrapper: Vec::new(),
+            c_locale: true,
+            sudo_program: "sudo".to_string(),
+            sudo_non_interactive: false,
+            skip_init: false,
+            destdir: None,
+            env: Vec::new(),
+            timeout: None,
+            output_cap: pipeline::MAX_OUTPUT,
+            last_output: RefCell::new(None),
+            package: None,
+            features: Vec::new(),
+            profile: None,
+        }
     }
 
     /// Returns the directory passed to [`Self::new`].
@@ -31,13 +344,62 @@ impl<P: AsRef<Path>> Pipeline<P> for Pgrx<P> {
         &self.cfg
     }
 
+    /// Returns the Probe set by [`Self::set_probe`].
+    fn probe(&self) -> Probe {
+        self.probe.clone()
+    }
+
+    /// Returns the wrapper set by [`Self::set_wrapper`].
+    fn wrapper(&self) -> &[String] {
+        &self.wrapper
+    }
+
+    /// Returns the value set by [`Self::set_c_locale`], true by default.
+    fn c_locale(&self) -> bool {
+        self.c_locale
+    }
+
+    /// Returns the program set by [`Self::set_sudo_program`], `sudo` by
+    /// default.
+    fn sudo_program(&self) -> &str {
+        &self.sudo_program
+    }
+
+    /// Returns the value set by [`Self::set_sudo_non_interactive`], false
+    /// by default.
+    fn sudo_non_interactive(&self) -> bool {
+        self.sudo_non_interactive
+    }
+
+    /// Returns the timeout set by [`Self::set_timeout`].
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Returns the cap set by [`Self::set_output_cap`],
+    /// [`pipeline::MAX_OUTPUT`] by default.
+    fn output_cap(&self) -> usize {
+        self.output_cap
+    }
+
+    /// Returns the output of the most recent command.
+    fn last_output(&self) -> Option<&RefCell<Option<Captured>>> {
+        Some(&self.last_output)
+    }
+
+    /// Returns the variables set by [`Self::set_env`].
+    fn env(&self) -> &[(String, Option<String>)] {
+        &self.env
+    }
+
     /// Determines the confidence that the Pgrx pipeline can build the
     /// contents of `dir`. Returns 255 if it contains a file named
-    /// `Cargo.toml` and lists pgrx as a dependency. Otherwise returns 1 if
-    /// `Cargo.toml` exists and 0 if it does not.
+    /// `Cargo.toml` that lists pgrx as a dependency or defines a workspace
+    /// with a member that does. Otherwise returns 1 if `Cargo.toml` exists
+    /// and 0 if it does not or is listed in `.pgxnignore`.
     fn confidence(dir: P) -> u8 {
         let file = dir.as_ref().join("Cargo.toml");
-        if !file.exists() {
+        if !file.exists() || Ignore::load(dir.as_ref()).is_ignored("Cargo.toml", false) {
             return 0;
         }
 
@@ -47,31 +409,186 @@ impl<P: AsRef<Path>> Pipeline<P> for Pgrx<P> {
                 // Full confidence
                 return 255;
             }
+            if !pgrx_members(dir.as_ref(), &cargo).is_empty() {
+                // A workspace member depends on pgrx.
+                return 255;
+            }
         }
 
         // Have Cargo.toml but no dependence on pgrx. Weak confidence.
         1
     }
 
-    /// Runs `cargo init`.
+    /// Compares the modification times of `Cargo.toml`, `Cargo.lock`, and
+    /// the files in `src` to those of the shared libraries in the Cargo
+    /// `target` directories for the debug, release, and configured
+    /// profiles. Returns true if no shared library exists or any
+    /// source file is newer.
+    fn needs_rebuild(&self) -> bool {
+        let dir = self.dir().as_ref();
+        let mut sources = pipeline::find_files(&dir.join("src"), &|_| true);
+        sources.extend(
+            ["Cargo.toml", "Cargo.lock"]
+                .into_iter()
+                .map(|f| dir.join(f))
+                .filter(|p| p.exists()),
+        );
+        let mut profiles = vec!["debug", "release"];
+        let profile = self.target_dir();
+        if !profiles.contains(&profile) {
+            profiles.push(profile);
+        }
+        let artifacts: Vec<PathBuf> = profiles.into_iter().flat_map(|p| self.libs(p)).collect();
+        pipeline::is_stale(&sources, &artifacts)
+    }
+
+    /// Returns the shared libraries in the Cargo `target` subdirectory for
+    /// the configured profile, sorted.
+    fn artifacts(&self) -> Result<Vec<PathBuf>, BuildError> {
+        let mut libs = self.libs(self.target_dir());
+        libs.sort();
+        Ok(libs)
+    }
+
+    /// Checks that cargo-pgrx is installed via [`Self::available`], then
+    /// runs `cargo pgrx init` as described for [`Self::init`].
     fn configure(&self) -> Result<(), BuildError> {
-        Ok(())
+        self.available()?;
+        self.init()
     }
 
-    /// Runs `cargo build`.
+    /// Runs `cargo pgrx build` with the arguments described by
+    /// [`Self::cargo_pgrx`].
     fn compile(&self) -> Result<(), BuildError> {
-        Ok(())
+        info!("building extension");
+        self.cargo_pgrx("build", Vec::new(), false)
     }
 
-    /// Runs `cargo test`.
+    /// Runs `cargo pgrx test` with the arguments returned by
+    /// [`Self::cargo_args`], selecting the Postgres major version reported
+    /// by the PgConfig, if known, e.g., `pg17`. `cargo pgrx test` has no
+    /// `--pg-config` option and tests against the `pg_config` pgrx
+    /// registers for the version, so first runs [`Self::init`] to make sure
+    /// it registers the PgConfig's `pg_config`.
     fn test(&self) -> Result<(), BuildError> {
-        Ok(())
+        self.init()?;
+        info!("testing extension");
+        let mut args = self.cargo_args("test")?;
+        if let Some(major) = self.cfg.major_version() {
+            args.push(format!("pg{major}").into());
+        }
+        self.run("cargo", args, false)
     }
 
-    /// Runs `cargo install`.
+    /// Runs `cargo pgrx install` with the arguments described by
+    /// [`Self::cargo_pgrx`]. Runs it with `sudo` if the `pkglibdir` reported
+    /// by the PgConfig isn't writeable by the current user. Returns
+    /// [`BuildError::Unsupported`] if [`Self::set_destdir`] set a staging
+    /// directory.
     fn install(&self) -> Result<(), BuildError> {
-        Ok(())
+        if self.destdir.is_some() {
+            return Err(BuildError::Unsupported(
+                "pgrx",
+                "staged installs; cargo-pgrx install has no DESTDIR option",
+            ));
+        }
+        info!("installing extension");
+        self.cargo_pgrx("install", Vec::new(), true)
     }
+
+    /// Returns [`BuildError::Unsupported`], because cargo-pgrx provides no
+    /// command to remove an installed extension.
+    fn uninstall(&self) -> Result<(), BuildError> {
+        Err(BuildError::Unsupported(
+            "pgrx",
+            "uninstall; cargo-pgrx has no uninstall command",
+        ))
+    }
+
+    /// Runs `cargo clean`, unless the Cargo `target` directory does not
+    /// exist, in which case there is nothing to clean.
+    fn clean(&self) -> Result<(), BuildError> {
+        if !self.dir().as_ref().join("target").exists() {
+            info!("no target directory; nothing to clean");
+            return Ok(());
+        }
+        info!("cleaning extension");
+        self.run("cargo", ["clean"], false)
+    }
+}
+
+/// Returns the pgrx home directory: `$PGRX_HOME` if set, and otherwise
+/// `.pgrx` in the user's home directory.
+fn pgrx_home() -> Option<PathBuf> {
+    if let Some(home) = env::var_os("PGRX_HOME").filter(|h| !h.is_empty()) {
+        return Some(PathBuf::from(home));
+    }
+    env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        .filter(|h| !h.is_empty())
+        .map(|h| PathBuf::from(h).join(".pgrx"))
+}
+
+/// Returns true if the pgrx `config.toml` registers `pg_config` for the
+/// Postgres version `name`, e.g., `pg17`. Returns false if it registers no
+/// `pg_config` or a different one for the version.
+fn is_registered(name: &str, pg_config: &Path) -> bool {
+    let Some(file) = pgrx_home().map(|h| h.join("config.toml")) else {
+        return false;
+    };
+    let Some(path) = fs::read_to_string(&file)
+        .ok()
+        .and_then(|src| src.parse::<toml::Table>().ok())
+        .and_then(|cfg| Some(PathBuf::from(cfg.get("configs")?.get(name)?.as_str()?)))
+    else {
+        return false;
+    };
+    // Compare canonical paths, too, in case either is a symlink.
+    path == pg_config
+        || matches!(
+            (path.canonicalize(), pg_config.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+/// Returns the sorted names of the packages in the workspace defined by
+/// the `Cargo.toml` manifest `root` in `dir` that depend on pgrx. Expands
+/// members ending in `/*` to the subdirectories containing a `Cargo.toml`
+/// and skips excluded members. Returns an empty list if `root` defines no
+/// workspace.
+fn pgrx_members(dir: &Path, root: &cargo_toml::Manifest) -> Vec<String> {
+    let Some(ws) = &root.workspace else {
+        return Vec::new();
+    };
+
+    let mut paths = Vec::new();
+    for member in &ws.members {
+        match member.strip_suffix("/*") {
+            Some(parent) => {
+                if let Ok(entries) = fs::read_dir(dir.join(parent)) {
+                    let mut subdirs: Vec<PathBuf> = entries
+                        .map_while(Result::ok)
+                        .map(|e| e.path())
+                        .filter(|p| p.join("Cargo.toml").is_file())
+                        .collect();
+                    subdirs.sort();
+                    paths.extend(subdirs);
+                }
+            }
+            None => paths.push(dir.join(member)),
+        }
+    }
+
+    let excluded: Vec<PathBuf> = ws.exclude.iter().map(|e| dir.join(e)).collect();
+    let mut names: Vec<String> = paths
+        .into_iter()
+        .filter(|p| !excluded.contains(p))
+        .filter_map(|p| cargo_toml::Manifest::from_path(p.join("Cargo.toml")).ok())
+        .filter(|m| m.dependencies.contains_key("pgrx"))
+        .filter_map(|m| m.package.map(|p| p.name))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
 }
 
 #[cfg(test)]
@@ -1,5 +1,11 @@
 use super::*;
-use std::{collections::HashMap, fs::File, io::Write};
+use crate::tests::{compile_mock, mock_cargo_path, with_mock_cargo};
+use assertables::*;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Write,
+};
 use tempfile::tempdir;
 
 #[test]
@@ -24,6 +30,144 @@ fn confidence() -> Result<(), BuildError> {
     Ok(())
 }
 
+#[test]
+fn confidence_ignored() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let mut file = File::create(tmp.as_ref().join("Cargo.toml"))?;
+    writeln!(&file, "[dependencies]\npgrx = \"0.12.6\"")?;
+    file.flush()?;
+    assert_eq!(255, Pgrx::confidence(tmp.as_ref()));
+
+    // Ignore Cargo.toml.
+    let mut file = File::create(tmp.as_ref().join(".pgxnignore"))?;
+    writeln!(&file, "# Leftover\nCargo.toml")?;
+    file.flush()?;
+    assert_eq!(0, Pgrx::confidence(tmp.as_ref()));
+
+    Ok(())
+}
+
+// Writes a Cargo.toml for package `name` to `dir`, depending on pgrx if
+// `pgrx` is true.
+fn write_member(dir: &Path, name: &str, pgrx: bool) -> Result<(), BuildError> {
+    fs::create_dir_all(dir)?;
+    let mut toml = format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n");
+    if pgrx {
+        toml.push_str("\n[dependencies]\npgrx = \"0.12.6\"\n");
+    }
+    fs::write(dir.join("Cargo.toml"), toml)?;
+    Ok(())
+}
+
+#[test]
+fn workspace() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    let cfg = PgConfig::from_map(HashMap::new());
+    let pipe = Pgrx::new(dir, cfg.clone());
+
+    // Not a workspace.
+    write_member(dir, "root", true)?;
+    assert_eq!(None, pipe.package()?);
+
+    // Workspace without pgrx members.
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"util\", \"crates/*\"]\nexclude = [\"crates/skip\"]\n",
+    )?;
+    write_member(&dir.join("util"), "util", false)?;
+    assert_eq!(1, Pgrx::confidence(dir));
+    assert_eq!(None, pipe.package()?);
+
+    // One pgrx member.
+    write_member(&dir.join("crates").join("alpha"), "alpha", true)?;
+    write_member(&dir.join("crates").join("skip"), "skip", true)?;
+    assert_eq!(255, Pgrx::confidence(dir));
+    assert_eq!(Some("alpha".to_string()), pipe.package()?);
+    with_mock_cargo("exit_err", || {
+        match pipe.compile() {
+            Ok(_) => panic!("compile unexpectedly succeeded"),
+            Err(e) => {
+                assert_ends_with!(e.to_string(), "DED: pgrx build --release --package alpha\n")
+            }
+        }
+        match pipe.test() {
+            Ok(_) => panic!("test unexpectedly succeeded"),
+            Err(e) => {
+                assert_ends_with!(e.to_string(), "DED: pgrx test --release --package alpha\n")
+            }
+        }
+    });
+
+    // Multiple pgrx members.
+    write_member(&dir.join("crates").join("beta"), "beta", true)?;
+    match pipe.package() {
+        Ok(_) => panic!("package unexpectedly succeeded"),
+        Err(e) => {
+            assert!(matches!(e, BuildError::SelectPackage(_)));
+            assert_eq!(
+                "multiple pgrx packages in workspace; select one of alpha, beta",
+                e.to_string()
+            );
+        }
+    }
+    match pipe.compile() {
+        Ok(_) => panic!("compile unexpectedly succeeded"),
+        Err(e) => assert!(matches!(e, BuildError::SelectPackage(_))),
+    }
+
+    // Select one.
+    let mut pipe = Pgrx::new(dir, cfg);
+    pipe.set_package(Some("beta".to_string()));
+    assert_eq!(Some("beta".to_string()), pipe.package()?);
+
+    Ok(())
+}
+
+#[test]
+fn features_profile() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "version".to_string(),
+        "PostgreSQL 17.2".to_string(),
+    )]));
+    let mut pipe = Pgrx::new(&tmp, cfg);
+
+    for (name, features, profile, args) in [
+        ("defaults", vec![], None, "--release"),
+        (
+            "features",
+            vec!["a".to_string(), "b".to_string()],
+            None,
+            "--release --features a,b",
+        ),
+        ("profile", vec![], Some("dev".to_string()), "--profile dev"),
+        (
+            "both",
+            vec!["pg17".to_string()],
+            Some("dev".to_string()),
+            "--profile dev --features pg17",
+        ),
+    ] {
+        pipe.set_features(features);
+        pipe.set_profile(profile);
+        with_mock_cargo("exit_err", || {
+            match pipe.compile() {
+                Ok(_) => panic!("{name} compile unexpectedly succeeded"),
+                Err(e) => assert_ends_with!(e.to_string(), format!("DED: pgrx build {args}\n")),
+            }
+            match pipe.test() {
+                Ok(_) => panic!("{name} test unexpectedly succeeded"),
+                Err(e) => {
+                    assert_ends_with!(e.to_string(), format!("DED: pgrx test {args} pg17\n"))
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
+
 #[test]
 fn new() {
     let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
@@ -45,8 +189,447 @@ fn new() {
 fn configure_et_al() {
     let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
     let pipe = Pgrx::new(dir, PgConfig::from_map(HashMap::new()));
-    assert!(pipe.configure().is_ok());
-    assert!(pipe.compile().is_ok());
-    assert!(pipe.test().is_ok());
-    assert!(pipe.install().is_ok());
+    with_mock_cargo("echo", || assert!(pipe.configure().is_ok()));
+}
+
+#[test]
+fn available() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let pipe = Pgrx::new(&tmp, PgConfig::from_map(HashMap::new()));
+
+    // Parse the version.
+    with_mock_cargo("cargo_pgrx", || -> Result<(), BuildError> {
+        assert_eq!(Some(semver::Version::new(0, 12, 6)), pipe.available()?);
+
+        // Still succeed when the version doesn't match the dependency.
+        fs::write(
+            tmp.path().join("Cargo.toml"),
+            "[package]\nname = \"x\"\nversion = \"0.1.0\"\n\n[dependencies]\npgrx = \"=0.11.4\"\n",
+        )?;
+        assert_eq!(
+            Some(semver::VersionReq::parse("=0.11.4").unwrap()),
+            pipe.pgrx_requirement()
+        );
+        assert_eq!(Some(semver::Version::new(0, 12, 6)), pipe.available()?);
+        Ok(())
+    })?;
+
+    // Unparseable version.
+    with_mock_cargo("echo", || -> Result<(), BuildError> {
+        assert_eq!(None, pipe.available()?);
+        Ok(())
+    })?;
+
+    // Command fails.
+    with_mock_cargo("exit_err", || {
+        for res in [pipe.available().map(|_| ()), pipe.configure()] {
+            match res {
+                Ok(_) => panic!("available unexpectedly succeeded"),
+                Err(e) => {
+                    assert!(matches!(e, BuildError::NoCargoPgrx(_)));
+                    assert_eq!(
+                        "cannot run cargo pgrx; install it with `cargo install --locked cargo-pgrx`: DED: pgrx --version",
+                        e.to_string()
+                    );
+                }
+            }
+        }
+    });
+
+    // Command not found.
+    let empty = tempdir()?;
+    temp_env::with_var("PATH", Some(empty.path()), || match pipe.available() {
+        Ok(_) => panic!("available unexpectedly succeeded"),
+        Err(e) => assert!(matches!(e, BuildError::NoCargoPgrx(_))),
+    });
+
+    Ok(())
+}
+
+#[test]
+fn test() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+
+    // Select the Postgres version.
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "version".to_string(),
+        "PostgreSQL 17.2".to_string(),
+    )]));
+    let pipe = Pgrx::new(&tmp, cfg);
+    with_mock_cargo("echo", || pipe.test())?;
+    with_mock_cargo("exit_err", || match pipe.test() {
+        Ok(_) => panic!("test unexpectedly succeeded"),
+        Err(e) => {
+            assert!(matches!(e, BuildError::Command(_, _, _)));
+            assert_contains!(e.to_string(), "\"cargo\" \"pgrx\" \"test\"");
+            assert_ends_with!(e.to_string(), "DED: pgrx test --release pg17\n");
+        }
+    });
+
+    // Omit it when unknown.
+    let pipe = Pgrx::new(&tmp, PgConfig::from_map(HashMap::new()));
+    with_mock_cargo("exit_err", || match pipe.test() {
+        Ok(_) => panic!("test unexpectedly succeeded"),
+        Err(e) => assert_ends_with!(e.to_string(), "DED: pgrx test --release\n"),
+    });
+
+    Ok(())
+}
+
+#[test]
+fn compile() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let pg_config = Path::new("/pg/bin").join(format!("pg_config{EXE_SUFFIX}"));
+
+    // Pass --pg-config when pg_config reports bindir.
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "bindir".to_string(),
+        "/pg/bin".to_string(),
+    )]));
+    let pipe = Pgrx::new(&tmp, cfg);
+    with_mock_cargo("exit_err", || match pipe.compile() {
+        Ok(_) => panic!("compile unexpectedly succeeded"),
+        Err(e) => {
+            assert_starts_with!(e.to_string(), "executing `");
+            assert_contains!(e.to_string(), "\"cargo\" \"pgrx\" \"build\" \"--release\"");
+            assert_ends_with!(
+                e.to_string(),
+                format!(
+                    "DED: pgrx build --release --pg-config {}\n",
+                    pg_config.display()
+                )
+            );
+        }
+    });
+    with_mock_cargo("echo", || pipe.compile())?;
+
+    // Omit it otherwise.
+    let pipe = Pgrx::new(&tmp, PgConfig::from_map(HashMap::new()));
+    with_mock_cargo("exit_err", || match pipe.compile() {
+        Ok(_) => panic!("compile unexpectedly succeeded"),
+        Err(e) => assert_ends_with!(e.to_string(), "DED: pgrx build --release\n"),
+    });
+
+    Ok(())
+}
+
+#[test]
+fn needs_rebuild() -> Result<(), BuildError> {
+    use std::time::{Duration, SystemTime};
+    let tmp = tempdir()?;
+    let touch = |name: &str, secs: u64| -> Result<(), BuildError> {
+        let file = File::create(tmp.path().join(name))?;
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))?;
+        Ok(())
+    };
+    let pipe = Pgrx::new(&tmp, PgConfig::from_map(HashMap::new()));
+
+    // Never built.
+    std::fs::create_dir_all(tmp.path().join("src"))?;
+    std::fs::create_dir_all(tmp.path().join("target").join("release"))?;
+    touch("Cargo.toml", 100)?;
+    touch("src/lib.rs", 100)?;
+    assert!(pipe.needs_rebuild());
+
+    // Built since.
+    touch("target/release/libpair.so", 200)?;
+    assert!(!pipe.needs_rebuild());
+
+    // Modified source file.
+    touch("src/lib.rs", 300)?;
+    assert!(pipe.needs_rebuild());
+    touch("src/lib.rs", 100)?;
+
+    // Modified lock file.
+    touch("Cargo.lock", 300)?;
+    assert!(pipe.needs_rebuild());
+
+    Ok(())
+}
+
+#[test]
+fn artifacts() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let target = tmp.path().join("target");
+    let mut pipe = Pgrx::new(tmp.path(), PgConfig::from_map(HashMap::new()));
+    assert!(pipe.artifacts()?.is_empty());
+
+    for dir in ["debug", "release", "profiling"] {
+        fs::create_dir_all(target.join(dir))?;
+        for file in ["libpair.so", "pair.d", "libpair.rlib"] {
+            fs::write(target.join(dir).join(file), "")?;
+        }
+    }
+
+    // Default to release.
+    assert_eq!(
+        vec![target.join("release").join("libpair.so")],
+        pipe.artifacts()?
+    );
+
+    // The dev profile builds into debug.
+    pipe.set_profile(Some("dev".to_string()));
+    assert_eq!(
+        vec![target.join("debug").join("libpair.so")],
+        pipe.artifacts()?
+    );
+
+    // Others build into a directory named for the profile.
+    pipe.set_profile(Some("profiling".to_string()));
+    assert_eq!(
+        vec![target.join("profiling").join("libpair.so")],
+        pipe.artifacts()?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn uninstall() {
+    let pipe = Pgrx::new(Path::new("."), PgConfig::from_map(HashMap::new()));
+    match pipe.uninstall() {
+        Ok(_) => panic!("uninstall unexpectedly succeeded"),
+        Err(e) => {
+            assert!(matches!(e, BuildError::Unsupported("pgrx", _)));
+            assert_eq!(
+                "pgrx pipeline does not support uninstall; cargo-pgrx has no uninstall command",
+                e.to_string()
+            );
+        }
+    }
+}
+
+#[test]
+fn clean() -> Result<(), BuildError> {
+    // Nothing to clean without a target directory.
+    let tmp = tempdir()?;
+    let pipe = Pgrx::new(&tmp, PgConfig::from_map(HashMap::new()));
+    with_mock_cargo("exit_err", || pipe.clean())?;
+
+    // Run cargo clean.
+    fs::create_dir(tmp.path().join("target"))?;
+    with_mock_cargo("echo", || pipe.clean())?;
+    with_mock_cargo("exit_err", || match pipe.clean() {
+        Ok(_) => panic!("clean unexpectedly succeeded"),
+        Err(e) => {
+            assert_contains!(e.to_string(), "\"cargo\" \"clean\"");
+            assert_ends_with!(e.to_string(), "DED: clean\n");
+        }
+    });
+
+    Ok(())
+}
+
+#[test]
+fn install() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let lib = tmp.path().join("lib");
+    std::fs::create_dir(&lib)?;
+    let cfg = |lib: &Path| {
+        PgConfig::from_map(HashMap::from([
+            ("bindir".to_string(), "/pg/bin".to_string()),
+            ("pkglibdir".to_string(), lib.display().to_string()),
+        ]))
+    };
+
+    // Install without sudo when pkglibdir is writeable.
+    let pipe = Pgrx::new(tmp.path(), cfg(&lib));
+    with_mock_cargo("exit_err", || match pipe.install() {
+        Ok(_) => panic!("install unexpectedly succeeded"),
+        Err(e) => {
+            assert_contains!(
+                e.to_string(),
+                "\"cargo\" \"pgrx\" \"install\" \"--release\""
+            );
+            assert_not_contains!(e.to_string(), "\"sudo\"");
+            assert_ends_with!(
+                e.to_string(),
+                format!(
+                    "DED: pgrx install --release --pg-config {}\n",
+                    Path::new("/pg/bin")
+                        .join(format!("pg_config{EXE_SUFFIX}"))
+                        .display()
+                )
+            );
+        }
+    });
+    with_mock_cargo("echo", || pipe.install())?;
+
+    // Install with sudo when pkglibdir is not writeable.
+    let sudo = tmp.path().join(format!("sudo{EXE_SUFFIX}"));
+    compile_mock("exit_err", &sudo.display().to_string());
+    let path = std::env::var("PATH").unwrap();
+    let path = [tmp.path().to_path_buf()]
+        .into_iter()
+        .chain(std::env::split_paths(&path));
+    let pipe = Pgrx::new(tmp.path(), cfg(&tmp.path().join("nonesuch")));
+    temp_env::with_var(
+        "PATH",
+        Some(std::env::join_paths(path).unwrap()),
+        || match pipe.install() {
+            Ok(_) => panic!("sudo install unexpectedly succeeded"),
+            Err(e) => {
+                assert_contains!(e.to_string(), "\"sudo\" \"cargo\" \"pgrx\" \"install\"");
+                assert_contains!(e.to_string(), "DED: cargo pgrx install --release");
+            }
+        },
+    );
+
+    Ok(())
+}
+
+#[test]
+fn stage() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "bindir".to_string(),
+        "/pg/bin".to_string(),
+    )]));
+    let pg_config = Path::new("/pg/bin").join(format!("pg_config{EXE_SUFFIX}"));
+
+    // Default to the package directory under the target directory.
+    let mut pipe = Pgrx::new(tmp.path(), cfg);
+    let out = std::path::absolute(tmp.path())?
+        .join("target")
+        .join("release")
+        .join("package");
+    with_mock_cargo("exit_err", || match pipe.stage() {
+        Ok(_) => panic!("stage unexpectedly succeeded"),
+        Err(e) => {
+            assert_contains!(e.to_string(), "\"cargo\" \"pgrx\" \"package\"");
+            assert_ends_with!(
+                e.to_string(),
+                format!(
+                    "DED: pgrx package --release --pg-config {} --out-dir {}\n",
+                    pg_config.display(),
+                    out.display(),
+                )
+            );
+        }
+    });
+    assert_eq!(out, with_mock_cargo("echo", || pipe.stage())?);
+
+    // Follow the profile.
+    pipe.set_profile(Some("dev".to_string()));
+    let out = std::path::absolute(tmp.path())?
+        .join("target")
+        .join("debug")
+        .join("package");
+    assert_eq!(out, with_mock_cargo("echo", || pipe.stage())?);
+
+    // Stage in the destdir.
+    let dest = tmp.path().join("stage");
+    pipe.set_destdir(Some(dest.clone()));
+    with_mock_cargo("exit_err", || match pipe.stage() {
+        Ok(_) => panic!("stage unexpectedly succeeded"),
+        Err(e) => assert_ends_with!(
+            e.to_string(),
+            format!(
+                "DED: pgrx package --profile dev --pg-config {} --out-dir {}\n",
+                pg_config.display(),
+                dest.display(),
+            )
+        ),
+    });
+    assert_eq!(dest, with_mock_cargo("echo", || pipe.stage())?);
+
+    Ok(())
+}
+
+#[test]
+fn configure() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let home = tmp.path().join("pgrx");
+    let bin = tmp.path().join("bin");
+    fs::create_dir_all(&home)?;
+    fs::create_dir_all(&bin)?;
+    let path = mock_cargo_path("cargo_pgrx", &bin);
+    let pg_config = Path::new("/pg/bin").join(format!("pg_config{EXE_SUFFIX}"));
+    let cfg = PgConfig::from_map(HashMap::from([
+        ("version".to_string(), "PostgreSQL 17.2".to_string()),
+        ("bindir".to_string(), "/pg/bin".to_string()),
+    ]));
+    let vars = [
+        ("PATH", Some(path.as_os_str())),
+        ("PGRX_HOME", Some(home.as_os_str())),
+    ];
+
+    temp_env::with_vars(vars, || {
+        // Run init when no config.toml.
+        let mut pipe = Pgrx::new(tmp.path(), cfg.clone());
+        match pipe.configure() {
+            Ok(_) => panic!("configure unexpectedly succeeded"),
+            Err(e) => {
+                assert_contains!(e.to_string(), "\"cargo\" \"pgrx\" \"init\"");
+                assert_ends_with!(
+                    e.to_string(),
+                    format!("DED: pgrx init --pg17 {}\n", pg_config.display())
+                );
+            }
+        }
+
+        // Run init when the version isn't registered.
+        let config = home.join("config.toml");
+        fs::write(&config, "[configs]\npg16 = \"/pg16/bin/pg_config\"\n").unwrap();
+        assert!(!is_registered("pg17", &pg_config));
+        assert!(pipe.configure().is_err());
+
+        // Run init when the version registers another pg_config.
+        fs::write(
+            &config,
+            "[configs]\npg16 = \"/pg16/bin/pg_config\"\npg17 = \"/other/bin/pg_config\"\n",
+        )
+        .unwrap();
+        assert!(!is_registered("pg17", &pg_config));
+        match pipe.configure() {
+            Ok(_) => panic!("configure unexpectedly succeeded"),
+            Err(e) => assert_ends_with!(
+                e.to_string(),
+                format!("DED: pgrx init --pg17 {}\n", pg_config.display())
+            ),
+        }
+
+        // Test re-runs init, too, since cargo pgrx test has no --pg-config.
+        match pipe.test() {
+            Ok(_) => panic!("test unexpectedly succeeded"),
+            Err(e) => assert_contains!(e.to_string(), "DED: pgrx init --pg17"),
+        }
+
+        // Skip init when registered.
+        fs::write(
+            &config,
+            format!(
+                "[configs]\npg16 = \"/pg16/bin/pg_config\"\npg17 = {:?}\n",
+                pg_config.display().to_string()
+            ),
+        )
+        .unwrap();
+        assert!(is_registered("pg17", &pg_config));
+        assert!(pipe.configure().is_ok());
+
+        // Skip init when disabled.
+        fs::remove_file(&config).unwrap();
+        assert!(pipe.configure().is_err());
+        pipe.skip_init(true);
+        assert!(pipe.configure().is_ok());
+
+        // Skip init when the version is unknown.
+        let pipe = Pgrx::new(tmp.path(), PgConfig::from_map(HashMap::new()));
+        assert!(pipe.configure().is_ok());
+    });
+
+    Ok(())
+}
+
+#[test]
+fn home() {
+    temp_env::with_vars(
+        [("PGRX_HOME", Some("/pgrx")), ("HOME", Some("/home/pgxn"))],
+        || assert_eq!(Some(PathBuf::from("/pgrx")), pgrx_home()),
+    );
+    if cfg!(not(windows)) {
+        temp_env::with_vars([("PGRX_HOME", None), ("HOME", Some("/home/pgxn"))], || {
+            assert_eq!(Some(Path::new("/home/pgxn").join(".pgrx")), pgrx_home())
+        });
+    }
 }
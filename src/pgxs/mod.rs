@@ -2,14 +2,18 @@
 //!
 //! [PGXS]: https://www.postgresql.org/docs/current/extend-pgxs.html
 
-use crate::pipeline::Pipeline;
-use crate::{error::BuildError, pg_config::PgConfig};
+use crate::ignore::Ignore;
+use crate::pipeline::{self, Captured, Pipeline, Probe};
+use crate::{error::BuildError, pg_config::PgConfig, TestMode};
 use log::info;
 use regex::Regex;
 use std::{
+    cell::{OnceCell, RefCell},
+    env::consts::EXE_SUFFIX,
     fs::{self, File},
     io::{BufRead, BufReader},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 /// Builder implementation for [PGXS] Pipelines.
@@ -19,16 +23,246 @@ use std::{
 pub(crate) struct Pgxs<P: AsRef<Path>> {
     cfg: PgConfig,
     dir: P,
+    werror: bool,
+    probe: Probe,
+    wrapper: Vec<String>,
+    c_locale: bool,
+    sudo_program: String,
+    sudo_non_interactive: bool,
+    shell: Option<String>,
+    make: Option<String>,
+    gnu_make: OnceCell<String>,
+    make_args: Vec<String>,
+    test_mode: TestMode,
+    destdir: Option<PathBuf>,
+    env: Vec<(String, Option<String>)>,
+    test_env: Vec<(String, Option<String>)>,
+    timeout: Option<Duration>,
+    output_cap: usize,
+    last_output: RefCell<Option<Captured>>,
+}
+
+impl<P: AsRef<Path>> Pgxs<P> {
+    /// Sets whether [`Pipeline::compile`] should treat compiler warnings as
+    /// errors, by passing `COPT=-Werror` to `make`.
+    pub(crate) fn warnings_as_errors(&mut self, yes: bool) {
+        self.werror = yes;
+    }
+
+    /// Sets the shell with which [`Pipeline::configure`] runs the
+    /// `configure` script. When [`None`], the default, runs the script
+    /// directly, relying on its shebang line.
+    pub(crate) fn set_shell(&mut self, shell: Option<String>) {
+        self.shell = shell;
+    }
+
+    /// Runs `make` with `args`, followed by `USE_PGXS=1` and
+    /// `PG_CONFIG` set to the path to `pg_config` in the `bindir` reported
+    /// by the PgConfig, if any, so that `make` builds against the same
+    /// Postgres rather than the first `pg_config` in the `PATH`. Omits
+    /// either variable if `args` already sets it. Returns
+    /// [`BuildError::Unsupported`] for Postgres built with MSVC, which does
+    /// not support PGXS.
+    fn make<I, S>(&self, args: I, sudo: bool) -> Result<(), BuildError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.make_env(args, sudo, &[])
+    }
+
+    /// Runs `make` like [`Self::make`], with the variables in `env` set in
+    /// its environment.
+    fn make_env<I, S>(
+        &self,
+        args: I,
+        sudo: bool,
+        env: &[(String, Option<String>)],
+    ) -> Result<(), BuildError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if is_msvc(self.pg_config()) {
+            return Err(BuildError::Unsupported(
+                "pgxs",
+                "Postgres built with MSVC; use a MinGW build of Postgres",
+            ));
+        }
+        let mut args: Vec<String> = args.into_iter().map(Into::into).collect();
+        let sets = |var: &str| args.iter().any(|a| a.starts_with(var));
+        let use_pgxs = !sets("USE_PGXS=");
+        let pg_config = self.cfg.bindir().filter(|_| !sets("PG_CONFIG="));
+        if use_pgxs {
+            args.push("USE_PGXS=1".to_string());
+        }
+        if let Some(bin) = pg_config {
+            let pg_config = bin.join(format!("pg_config{EXE_SUFFIX}"));
+            args.push(format!("PG_CONFIG={}", pg_config.display()));
+        }
+        let make = self.make_program()?;
+        self.capture_env(&make, args, sudo, env).map(|_| ())
+    }
+
+    /// Sets the `make` program to run. When [`None`], the default, finds
+    /// GNU make as described for [`Self::make_program`].
+    pub(crate) fn set_make(&mut self, make: Option<String>) {
+        self.make = make;
+    }
+
+    /// Returns the `make` program to run. PGXS requires GNU make, which is
+    /// named `gmake` on the BSDs, so unless [`Self::set_make`] set a
+    /// program, returns the first of `gmake` or `make` in the `PATH` whose
+    /// `--version` output reports GNU make, checking only the first time
+    /// it's called and without recording the check's output as the
+    /// [last output](Pipeline::last_output). Returns `make` if commands run
+    /// in a wrapper, which has its own `PATH`. Returns
+    /// [`BuildError::NotFound`] if neither is GNU make.
+    fn make_program(&self) -> Result<String, BuildError> {
+        if let Some(make) = &self.make {
+            return Ok(make.clone());
+        }
+        if !self.wrapper().is_empty() {
+            return Ok("make".to_string());
+        }
+        if let Some(make) = self.gnu_make.get() {
+            return Ok(make.clone());
+        }
+
+        // Preserve the output of the last build command.
+        let last = self.last_output.take();
+        let found = ["gmake", "make"].into_iter().find(|make| {
+            pipeline::find_in_path(make).is_ok()
+                && self
+                    .capture(make, ["--version"], false)
+                    .is_ok_and(|out| out.stdout.contains("GNU Make"))
+        });
+        self.last_output.replace(last);
+        if let Some(make) = found {
+            return Ok(self.gnu_make.get_or_init(|| make.to_string()).clone());
+        }
+        let searched = std::env::var_os("PATH")
+            .map(|path| {
+                std::env::split_paths(&path)
+                    .map(|dir| dir.display().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Err(BuildError::NotFound("GNU make", searched))
+    }
+
+    /// Sets extra arguments, such as variables like `PG_CPPFLAGS=-DFOO`, to
+    /// pass to `make` after the target in [`Pipeline::compile`],
+    /// [`Pipeline::test`], and [`Pipeline::install`]. Each item is a single
+    /// argument, even if it contains spaces.
+    pub(crate) fn set_make_args(&mut self, args: Vec<String>) {
+        self.make_args = args;
+    }
+
+    /// Returns `args` followed by the arguments set by
+    /// [`Self::set_make_args`].
+    fn with_make_args<const N: usize>(&self, args: [&str; N]) -> Vec<String> {
+        args.into_iter()
+            .map(String::from)
+            .chain(self.make_args.iter().cloned())
+            .collect()
+    }
+
+    /// Sets the tests run by [`Pipeline::test`].
+    pub(crate) fn set_test_mode(&mut self, mode: TestMode) {
+        self.test_mode = mode;
+    }
+
+    /// Sets the staging directory into which [`Pipeline::install`] installs
+    /// by passing `DESTDIR` to `make`. When [`None`], the default, installs
+    /// into the directories reported by the PgConfig.
+    pub(crate) fn set_destdir(&mut self, dir: Option<PathBuf>) {
+        self.destdir = dir;
+    }
+
+    /// Returns the staging directory set by [`Self::set_destdir`].
+    pub(crate) fn destdir(&self) -> Option<&Path> {
+        self.destdir.as_deref()
+    }
+
+    /// Sets the Probe used to test write access to the install directory.
+    pub(crate) fn set_probe(&mut self, probe: Probe) {
+        self.probe = probe;
+    }
+
+    /// Sets the wrapper command and arguments with which to run every
+    /// command.
+    pub(crate) fn set_wrapper(&mut self, wrapper: Vec<String>) {
+        self.wrapper = wrapper;
+    }
+
+    /// Sets whether to run commands in the C locale.
+    pub(crate) fn set_c_locale(&mut self, yes: bool) {
+        self.c_locale = yes;
+    }
+
+    /// Sets the program with which to run commands with elevated
+    /// privileges.
+    pub(crate) fn set_sudo_program(&mut self, program: String) {
+        self.sudo_program = program;
+    }
+
+    /// Sets whether to pass `-n` to the sudo program.
+    pub(crate) fn set_sudo_non_interactive(&mut self, yes: bool) {
+        self.sudo_non_interactive = yes;
+    }
+
+    /// Sets the maximum time a command may run before it is killed.
+    pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Sets the maximum number of bytes of each output stream to keep.
+    pub(crate) fn set_output_cap(&mut self, cap: usize) {
+        self.output_cap = cap;
+    }
+
+    /// Sets the environment variable `key` to `val` for every command, or
+    /// removes it if `val` is [`None`].
+    pub(crate) fn set_env(&mut self, key: String, val: Option<String>) {
+        pipeline::set_env(&mut self.env, key, val);
+    }
+
+    /// Sets the environment variable `key` to `val` for the command run by
+    /// [`Pipeline::test`], or removes it if `val` is [`None`].
+    pub(crate) fn set_test_env(&mut self, key: String, val: Option<String>) {
+        pipeline::set_env(&mut self.test_env, key, val);
+    }
 }
 
 impl<P: AsRef<Path>> Pipeline<P> for Pgxs<P> {
     fn new(dir: P, cfg: PgConfig) -> Self {
-        Pgxs { cfg, dir }
+        Pgxs {
+            cfg,
+            dir,
+            werror: false,
+            probe: Probe::default(),
+            wrapper: Vec::new(),
+            c_locale: true,
+            sudo_program: "sudo".to_string(),
+            sudo_non_interactive: false,
+            shell: None,
+            make: None,
+            gnu_make: OnceCell::new(),
+            make_args: Vec::new(),
+            test_mode: TestMode::default(),
+            destdir: None,
+            env: Vec::new(),
+            test_env: Vec::new(),
+            timeout: None,
+            output_cap: pipeline::MAX_OUTPUT,
+            last_output: RefCell::new(None),
+        }
     }
 
     /// Determines the confidence that the Pgxs pipeline can build the
-    /// contents of `dir`. Returns 0 unless the directory contains a Makefile.
-    /// Otherwise it returns a score as follows;
+    /// contents of `dir`. Returns 0 unless the directory contains a Makefile
+    /// not listed in `.pgxnignore`. Otherwise it returns a score as follows;
     ///
     /// *   Returns 255 if it declares a variable named `PG_CONFIG`.
     /// *   Returns 200 if it declares variables named `MODULES`,
@@ -39,25 +273,24 @@ impl<P: AsRef<Path>> Pipeline<P> for Pgxs<P> {
             Some(f) => f,
             None => return 0,
         };
+        if Ignore::load(dir.as_ref()).is_ignored(crate::filename(&file), false) {
+            return 0;
+        }
 
         // https://www.postgresql.org/docs/current/extend-pgxs.html
         // https://github.com/postgres/postgres/blob/master/src/makefiles/pgxs.mk
         let mut score: u8 = 127;
-        if let Ok(file) = File::open(file) {
-            let reader = BufReader::new(file);
-            let pgc_rx = Regex::new(r"^PG_CONFIG\s*[:?]?=\s*").unwrap();
-            let var_rx =
-                Regex::new(r"^(MODULE(?:S|_big)|PROGRAM|EXTENSION|DATA(?:_built)?)\s*[:?]?=")
-                    .unwrap();
-            for line in reader.lines().map_while(Result::ok) {
-                if pgc_rx.is_match(&line) {
-                    // Full confidence
-                    return 255;
-                }
-                if var_rx.is_match(&line) {
-                    // Probably
-                    score = 200;
-                }
+        let pgc_rx = Regex::new(r"^PG_CONFIG\s*[:?]?=\s*").unwrap();
+        let var_rx =
+            Regex::new(r"^(MODULE(?:S|_big)|PROGRAM|EXTENSION|DATA(?:_built)?)\s*[:?]?=").unwrap();
+        for line in makefile_lines(&file) {
+            if pgc_rx.is_match(&line) {
+                // Full confidence
+                return 255;
+            }
+            if var_rx.is_match(&line) {
+                // Probably
+                score = 200;
             }
         }
 
@@ -75,6 +308,56 @@ impl<P: AsRef<Path>> Pipeline<P> for Pgxs<P> {
         &self.cfg
     }
 
+    /// Returns the Probe set by [`Self::set_probe`].
+    fn probe(&self) -> Probe {
+        self.probe.clone()
+    }
+
+    /// Returns the wrapper set by [`Self::set_wrapper`].
+    fn wrapper(&self) -> &[String] {
+        &self.wrapper
+    }
+
+    /// Returns the value set by [`Self::set_c_locale`], true by default.
+    fn c_locale(&self) -> bool {
+        self.c_locale
+    }
+
+    /// Returns the program set by [`Self::set_sudo_program`], `sudo` by
+    /// default.
+    fn sudo_program(&self) -> &str {
+        &self.sudo_program
+    }
+
+    /// Returns the value set by [`Self::set_sudo_non_interactive`], false
+    /// by default.
+    fn sudo_non_interactive(&self) -> bool {
+        self.sudo_non_interactive
+    }
+
+    /// Returns the timeout set by [`Self::set_timeout`].
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Returns the cap set by [`Self::set_output_cap`],
+    /// [`pipeline::MAX_OUTPUT`] by default.
+    fn output_cap(&self) -> usize {
+        self.output_cap
+    }
+
+    /// Returns the output of the most recent command.
+    fn last_output(&self) -> Option<&RefCell<Option<Captured>>> {
+        Some(&self.last_output)
+    }
+
+    /// Returns the variables set by [`Self::set_env`].
+    fn env(&self) -> &[(String, Option<String>)] {
+        &self.env
+    }
+
+    /// Runs `configure` if it exists, via the shell set by
+    /// [`Self::set_shell`], if any.
     fn configure(&self) -> Result<(), BuildError> {
         // Run configure if it exists.
         if let Ok(ok) = fs::exists(self.dir().as_ref().join("configure")) {
@@ -82,36 +365,178 @@ impl<P: AsRef<Path>> Pipeline<P> for Pgxs<P> {
                 info!("running configure");
                 // "." will not work on VMS or MacOS Classic.
                 let cmd = Path::new(".").join("configure").display().to_string();
-                return self.run(&cmd, [""; 0], false);
+                return match &self.shell {
+                    Some(sh) => self.run(sh, [cmd.as_str()], false),
+                    None => self.run(&cmd, [""; 0], false),
+                };
             }
         }
 
         Ok(())
     }
 
+    /// Compares the modification times of C source and header files and
+    /// the Makefile to those of the shared libraries built from them.
+    /// Returns true if no shared library exists or any source file is
+    /// newer.
+    fn needs_rebuild(&self) -> bool {
+        let dir = self.dir().as_ref();
+        let sources = pipeline::find_files(dir, &|p| {
+            pipeline::has_extension(p, &SOURCE_EXTENSIONS) || makefile_name(p)
+        });
+        let artifacts = pipeline::find_files(dir, &|p| {
+            pipeline::has_extension(p, &pipeline::SHLIB_EXTENSIONS)
+        });
+        pipeline::is_stale(&sources, &artifacts)
+    }
+
+    /// Returns the shared libraries and LLVM bitcode files in the directory
+    /// and its subdirectories, together with the existing files listed in
+    /// the Makefile's `DATA_built` variable, such as SQL scripts generated
+    /// from templates. Returns the files sorted.
+    fn artifacts(&self) -> Result<Vec<PathBuf>, BuildError> {
+        let dir = self.dir().as_ref();
+        let mut files = pipeline::find_files(dir, &|p| {
+            pipeline::has_extension(p, &pipeline::SHLIB_EXTENSIONS)
+                || pipeline::has_extension(p, &["bc"])
+        });
+        if let Some(file) = makefile(dir) {
+            files.extend(
+                data_built(&file)
+                    .into_iter()
+                    .map(|f| dir.join(f))
+                    .filter(|p| p.is_file()),
+            );
+        }
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    /// Runs `make all`, adding `COPT=-Werror` if warnings should be
+    /// treated as errors, followed by the arguments set by
+    /// [`Self::set_make_args`].
     fn compile(&self) -> Result<(), BuildError> {
         info!("building extension");
-        self.run("make", ["all"], false)?;
+        if self.werror {
+            self.make(self.with_make_args(["all", "COPT=-Werror"]), false)?;
+        } else {
+            self.make(self.with_make_args(["all"]), false)?;
+        }
         Ok(())
     }
 
+    /// Runs `make installcheck` with the arguments set by
+    /// [`Self::set_make_args`] to run the tests selected by
+    /// [`Self::set_test_mode`]. Runs `pg_regress` tests unless the Makefile
+    /// defines neither a non-empty `REGRESS` variable nor an `installcheck`
+    /// target, and TAP tests if the `t` directory contains `.pl` files, as
+    /// `prove` expects; a `t` directory of pgTAP SQL files does not enable
+    /// TAP tests. Passes
+    /// `TAP_TESTS=1` to run TAP tests, `TAP_TESTS=` to skip them in
+    /// [`TestMode::Regress`], and `REGRESS=` to skip `pg_regress` tests. Returns
+    /// [`BuildError::NotFound`] if it would run TAP tests but cannot find
+    /// `prove` in the `PATH`, unless commands run in a wrapper. If there
+    /// are no tests to run, logs so and returns success.
     fn test(&self) -> Result<(), BuildError> {
-        info!("testing extension");
-        self.run("make", ["installcheck"], false)?;
+        let dir = self.dir().as_ref();
+        let regress = self.test_mode != TestMode::Tap
+            && makefile(dir).is_none_or(|file| defines_tests(&file));
+        let tap = self.test_mode != TestMode::Regress && has_tap_tests(dir);
+        if !regress && !tap {
+            info!("no tests defined");
+            return Ok(());
+        }
+
+        let mut args = vec!["installcheck"];
+        if !regress {
+            args.push("REGRESS=");
+        }
+        if tap {
+            if self.wrapper().is_empty() {
+                pipeline::find_in_path("prove")?;
+            }
+            args.push("TAP_TESTS=1");
+        } else if self.test_mode == TestMode::Regress {
+            args.push("TAP_TESTS=");
+        }
+
+        info!(regress, tap; "testing extension");
+        self.make_env(
+            args.into_iter()
+                .map(String::from)
+                .chain(self.make_args.iter().cloned()),
+            false,
+            &self.test_env,
+        )?;
         Ok(())
     }
 
+    /// Runs `make install` with the arguments set by
+    /// [`Self::set_make_args`], with `sudo` if the `pkglibdir` reported by
+    /// the PgConfig isn't writeable by the current user. If
+    /// [`Self::set_destdir`] set a staging directory, instead passes its
+    /// absolute path as `DESTDIR` and never uses `sudo`.
     fn install(&self) -> Result<(), BuildError> {
-        info!("installing extension");
-        self.run("make", ["install"], true)?;
+        match &self.destdir {
+            Some(dir) => {
+                // Make runs in the build directory, so pass an absolute path.
+                let dir = std::path::absolute(dir)?;
+                info!(destdir:display = dir.display(); "staging extension");
+                let destdir = format!("DESTDIR={}", dir.display());
+                self.make(self.with_make_args(["install", &destdir]), false)?;
+            }
+            None => {
+                info!("installing extension");
+                self.make(self.with_make_args(["install"]), true)?;
+            }
+        }
         Ok(())
     }
+
+    /// Runs `make uninstall`, with `sudo` if the `pkglibdir` reported by the
+    /// PgConfig isn't writeable by the current user. Returns
+    /// [`BuildError::Unsupported`] without running `make` if there is no
+    /// Makefile or it neither includes PGXS, which provides the target, nor
+    /// defines an `uninstall` target itself.
+    fn uninstall(&self) -> Result<(), BuildError> {
+        if !makefile(self.dir().as_ref()).is_some_and(|f| defines_uninstall(&f)) {
+            return Err(BuildError::Unsupported(
+                "pgxs",
+                "uninstall without a Makefile that includes PGXS or defines an uninstall target",
+            ));
+        }
+        info!("uninstalling extension");
+        self.make(["uninstall"], true)
+    }
+
+    /// Runs `make clean`, unless the directory has no Makefile, in which
+    /// case there is nothing to clean.
+    fn clean(&self) -> Result<(), BuildError> {
+        if makefile(self.dir().as_ref()).is_none() {
+            info!("no Makefile; nothing to clean");
+            return Ok(());
+        }
+        info!("cleaning extension");
+        self.make(["clean"], false)
+    }
+}
+
+/// File extensions for source files compiled into PGXS modules.
+const SOURCE_EXTENSIONS: [&str; 7] = ["c", "h", "cc", "cpp", "hpp", "l", "y"];
+
+/// Makefile names recognized by `make`, in order of precedence.
+const MAKEFILES: [&str; 3] = ["GNUmakefile", "makefile", "Makefile"];
+
+/// Returns true if `path` names a Makefile.
+fn makefile_name(path: &Path) -> bool {
+    MAKEFILES.contains(&crate::filename(path).as_str())
 }
 
 /// Returns the path to a Makefile in `dir`, or [`None`] if no Makefile
 /// exists.
 fn makefile(dir: &Path) -> Option<PathBuf> {
-    for makefile in ["GNUmakefile", "makefile", "Makefile"] {
+    for makefile in MAKEFILES {
         let file = dir.join(makefile);
         if file.exists() {
             return Some(file);
@@ -120,5 +545,101 @@ fn makefile(dir: &Path) -> Option<PathBuf> {
     None
 }
 
+/// Returns true if `cfg` describes a Postgres built with Microsoft Visual
+/// C++. Such builds record no compiler and lack the PGXS infrastructure.
+fn is_msvc(cfg: &PgConfig) -> bool {
+    match cfg.get("cc") {
+        Some(cc) => {
+            cc == "not recorded"
+                || cc.split_whitespace().next().is_some_and(|c| {
+                    c.eq_ignore_ascii_case("cl") || c.eq_ignore_ascii_case("cl.exe")
+                })
+        }
+        None => false,
+    }
+}
+
+/// Returns the logical lines of `makefile` that may define variables,
+/// targets, or includes: joins lines continued with a trailing backslash,
+/// strips leading whitespace, and skips blank lines, comments, and recipe
+/// lines, which start with a tab. Returns an empty list if `makefile`
+/// cannot be read.
+fn makefile_lines(makefile: &Path) -> Vec<String> {
+    let file = match File::open(makefile) {
+        Ok(f) => f,
+        Err(_) => return Vec::new(),
+    };
+    let mut lines = Vec::new();
+    let mut push = |line: &str| {
+        let trimmed = line.trim_start();
+        if !line.starts_with('\t') && !trimmed.is_empty() && !trimmed.starts_with('#') {
+            lines.push(trimmed.to_string());
+        }
+    };
+    let mut logical = String::new();
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        match line.strip_suffix('\\') {
+            Some(part) => {
+                logical.push_str(part);
+                logical.push(' ');
+            }
+            None => {
+                logical.push_str(&line);
+                push(&logical);
+                logical.clear();
+            }
+        }
+    }
+    // A continuation on the last line.
+    push(&logical);
+    lines
+}
+
+/// Returns true if `makefile` assigns a non-empty value to `REGRESS` or
+/// declares an `installcheck` target, and false if it does neither or
+/// cannot be read.
+fn defines_tests(makefile: &Path) -> bool {
+    let rx = Regex::new(r"^(?:REGRESS\s*[:+?]?=\s*\S|installcheck\s*:)").unwrap();
+    makefile_lines(makefile)
+        .iter()
+        .any(|line| rx.is_match(line))
+}
+
+/// Returns true if the `t` subdirectory of `dir` contains any `.pl` files,
+/// the TAP tests PGXS runs with `prove`.
+fn has_tap_tests(dir: &Path) -> bool {
+    fs::read_dir(dir.join("t")).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|e| pipeline::has_extension(&e.path(), &["pl"]) && e.path().is_file())
+    })
+}
+
+/// Returns true if `makefile` includes PGXS or declares an `uninstall`
+/// target, and false if it does neither or cannot be read.
+fn defines_uninstall(makefile: &Path) -> bool {
+    let rx = Regex::new(r"^(?:-?include\s+\$[({]PGXS[)}]|uninstall\s*:)").unwrap();
+    makefile_lines(makefile)
+        .iter()
+        .any(|line| rx.is_match(line))
+}
+
+/// Returns the file names assigned to the `DATA_built` variable in
+/// `makefile`, skipping any that reference other variables. Returns an
+/// empty list if it assigns none or cannot be read.
+fn data_built(makefile: &Path) -> Vec<String> {
+    let rx = Regex::new(r"^DATA_built\s*[:+?]?=(.*)$").unwrap();
+    makefile_lines(makefile)
+        .iter()
+        .filter_map(|line| Some(rx.captures(line)?[1].to_string()))
+        .flat_map(|vals| {
+            vals.split_whitespace()
+                .filter(|v| !v.contains('$'))
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests;
@@ -53,6 +53,64 @@ fn confidence() -> Result<(), BuildError> {
     Ok(())
 }
 
+#[test]
+fn confidence_lines() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let makefile = tmp.as_ref().join("Makefile");
+    for (name, body, exp) in [
+        ("commented PG_CONFIG", "# PG_CONFIG = pg_config\n", 127),
+        ("indented comment", "  # PG_CONFIG = pg_config\n", 127),
+        (
+            "continued comment",
+            "# Set these: \\\nPG_CONFIG = pg_config\n",
+            127,
+        ),
+        ("indented PG_CONFIG", "  PG_CONFIG = pg_config\n", 255),
+        ("continued MODULES", "MODULES = a \\\n    b\n", 200),
+        (
+            "continued value",
+            "EXTENSION = pair\nPG_CONFIG \\\n  = pg_config\n",
+            255,
+        ),
+        (
+            "recipe line",
+            "all:\n\tPG_CONFIG=pg_config $(MAKE) -C sub\n",
+            127,
+        ),
+        (
+            "continued recipe",
+            "all:\n\techo \\\nPG_CONFIG = pg_config\n",
+            127,
+        ),
+        ("trailing continuation", "MODULES = a \\", 200),
+    ] {
+        fs::write(&makefile, body)?;
+        assert_eq!(exp, Pgxs::confidence(tmp.as_ref()), "{name}");
+    }
+
+    // Join continued lines.
+    fs::write(
+        &makefile,
+        "# Data\nDATA_built = a.sql \\\n\tb.sql\n\ninstall:\n\tDATA_built = c.sql\n",
+    )?;
+    assert_eq!(vec!["a.sql", "b.sql"], data_built(&makefile));
+
+    Ok(())
+}
+
+#[test]
+fn confidence_ignored() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    fs::write(tmp.as_ref().join("Makefile"), "PG_CONFIG = pg_config\n")?;
+    assert_eq!(255, Pgxs::confidence(tmp.as_ref()));
+
+    // Ignore the Makefile.
+    fs::write(tmp.as_ref().join(".pgxnignore"), "/Makefile\n")?;
+    assert_eq!(0, Pgxs::confidence(tmp.as_ref()));
+
+    Ok(())
+}
+
 #[test]
 fn new() {
     let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
@@ -122,6 +180,34 @@ fn configure() -> Result<(), BuildError> {
     Ok(())
 }
 
+#[test]
+#[cfg(target_family = "unix")]
+fn configure_shell() -> Result<(), BuildError> {
+    // Write a configure script that is not executable.
+    let tmp = tempdir()?;
+    fs::write(
+        tmp.path().join("configure"),
+        "#! /nonesuch/sh\n\necho configuring something...\n",
+    )?;
+    let mut pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    assert!(pipe.configure().is_err());
+
+    // Run it through the shell.
+    pipe.set_shell(Some("sh".to_string()));
+    if let Err(e) = pipe.configure() {
+        panic!("configure via sh failed: {e}");
+    }
+
+    // Fail with a nonexistent shell.
+    pipe.set_shell(Some("/nonesuch/sh".to_string()));
+    match pipe.configure() {
+        Ok(_) => panic!("configure via nonexistent shell unexpectedly succeeded"),
+        Err(e) => assert_ends_with!(e.to_string(), "\"./configure\"`: entity not found"),
+    }
+
+    Ok(())
+}
+
 #[test]
 fn compile() -> Result<(), BuildError> {
     let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
@@ -131,13 +217,600 @@ fn compile() -> Result<(), BuildError> {
 }
 
 #[test]
-fn test() -> Result<(), BuildError> {
-    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+#[cfg(target_family = "unix")]
+fn compile_werror() -> Result<(), BuildError> {
+    // Makefile fails if COPT is set.
+    let tmp = tempdir()?;
+    fs::write(
+        tmp.as_ref().join("Makefile"),
+        "all:\n\ttest -z \"$(COPT)\"\n",
+    )?;
+    let mut pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    assert!(!pipe.werror);
+    pipe.compile()?;
+
+    pipe.warnings_as_errors(true);
+    assert!(pipe.werror);
+    match pipe.compile() {
+        Ok(_) => panic!("compile with COPT unexpectedly succeeded"),
+        Err(e) => assert!(e.to_string().contains("\"COPT=-Werror\""), "{e}"),
+    }
+
+    pipe.warnings_as_errors(false);
+    pipe.compile()?;
+    Ok(())
+}
+
+#[test]
+fn make_program() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let mut pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+
+    // Require GNU make; the mock gmake just echoes its arguments.
+    let bin = tmp.as_ref().join("bin");
+    fs::create_dir(&bin)?;
+    let gmake = bin.join(format!("gmake{EXE_SUFFIX}"));
+    crate::tests::compile_mock("echo", &gmake.display().to_string());
+    temp_env::with_var("PATH", Some(&bin), || {
+        match pipe.make_program() {
+            Ok(make) => panic!("found non-GNU {make}"),
+            Err(e) => assert_eq!(
+                format!("cannot find GNU make in PATH; searched {}", bin.display()),
+                e.to_string(),
+            ),
+        }
+        assert!(pipe.compile().is_err());
+    });
+
+    // Use make in a wrapper.
+    pipe.set_wrapper(vec!["env".to_string()]);
+    temp_env::with_var("PATH", Some(&bin), || {
+        assert_eq!("make", pipe.make_program().unwrap());
+    });
+
+    // Use the program set explicitly.
+    pipe.set_make(Some("/opt/bin/gmake".to_string()));
+    assert_eq!("/opt/bin/gmake", pipe.make_program()?);
+
+    Ok(())
+}
+
+#[test]
+fn make_program_once() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    let bin = tmp.as_ref().join("bin");
+    fs::create_dir(&bin)?;
+    let gmake = bin.join(format!("gmake{EXE_SUFFIX}"));
+    crate::tests::compile_mock("gnu_make", &gmake.display().to_string());
+    temp_env::with_var("PATH", Some(&bin), || -> Result<(), BuildError> {
+        pipe.compile()?;
+        pipe.compile()
+    })?;
+
+    // Checks the version only once.
+    assert_eq!("--version\n", fs::read_to_string(bin.join("probes"))?);
+
+    // The last output is from the build, not the version check.
+    let last = pipe.last_output.borrow().clone().unwrap();
+    assert_eq!("all USE_PGXS=1", last.stdout.trim_end());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn make_vars() -> Result<(), BuildError> {
+    // Makefile fails unless PG_CONFIG and USE_PGXS are set.
+    let tmp = tempdir()?;
+    fs::write(
+        tmp.as_ref().join("Makefile"),
+        "all:\n\ttest -n \"$(PG_CONFIG)\" && test \"$(USE_PGXS)\" = 1\n",
+    )?;
+    let pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    match pipe.compile() {
+        Ok(_) => panic!("compile without PG_CONFIG unexpectedly succeeded"),
+        Err(e) => {
+            assert_contains!(e.to_string(), "make\" \"all\" \"USE_PGXS=1\"`");
+            assert_not_contains!(e.to_string(), "PG_CONFIG=");
+        }
+    }
+
+    // Pass PG_CONFIG from bindir.
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "bindir".to_string(),
+        "/pg/bin".to_string(),
+    )]));
+    let mut pipe = Pgxs::new(&tmp, cfg);
+    pipe.compile()?;
+
+    // Make variables follow the targets.
+    fs::write(tmp.as_ref().join("Makefile"), "all:\n\tfalse\n")?;
+    pipe.warnings_as_errors(true);
+    match pipe.compile() {
+        Ok(_) => panic!("compile unexpectedly succeeded"),
+        Err(e) => assert_contains!(
+            e.to_string(),
+            "make\" \"all\" \"COPT=-Werror\" \"USE_PGXS=1\" \"PG_CONFIG=/pg/bin/pg_config\"`"
+        ),
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn clean() -> Result<(), BuildError> {
+    // Nothing to clean without a Makefile.
+    let tmp = tempdir()?;
+    let pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    pipe.clean()?;
+
+    // Run make clean.
+    fs::write(tmp.as_ref().join("Makefile"), "clean:\n\trm -f pair.so\n")?;
+    fs::write(tmp.as_ref().join("pair.so"), "")?;
+    pipe.clean()?;
+    assert!(!tmp.as_ref().join("pair.so").exists());
+
+    // Report make failure.
+    fs::write(tmp.as_ref().join("Makefile"), "clean:\n\tfalse\n")?;
+    match pipe.clean() {
+        Ok(_) => panic!("clean unexpectedly succeeded"),
+        Err(e) => assert_contains!(e.to_string(), "make\" \"clean\""),
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn uninstall() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let makefile = tmp.as_ref().join("Makefile");
+    let pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    let unsupported = "pgxs pipeline does not support uninstall without a Makefile that includes PGXS or defines an uninstall target";
+
+    // No Makefile.
+    match pipe.uninstall() {
+        Ok(_) => panic!("uninstall unexpectedly succeeded"),
+        Err(e) => assert_eq!(unsupported, e.to_string()),
+    }
+
+    // No uninstall target.
+    fs::write(&makefile, "all:\n\ttrue\n")?;
+    match pipe.uninstall() {
+        Ok(_) => panic!("uninstall unexpectedly succeeded"),
+        Err(e) => assert_eq!(unsupported, e.to_string()),
+    }
+
+    // Run make uninstall.
+    fs::write(&makefile, "uninstall:\n\ttrue\n")?;
+    pipe.uninstall()?;
+    fs::write(&makefile, "uninstall:\n\tfalse\n")?;
+    match pipe.uninstall() {
+        Ok(_) => panic!("uninstall unexpectedly succeeded"),
+        Err(e) => assert_contains!(e.to_string(), "make\" \"uninstall\""),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn uninstall_target() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let makefile = tmp.as_ref().join("Makefile");
+    for (name, body, exp) in [
+        ("empty", "", false),
+        ("no target", "all:\n\ttrue\n", false),
+        ("target", "uninstall:\n\ttrue\n", true),
+        ("target deps", "uninstall: clean\n", true),
+        (
+            "pgxs",
+            "PG_CONFIG ?= pg_config\nPGXS := $(shell $(PG_CONFIG) --pgxs)\ninclude $(PGXS)\n",
+            true,
+        ),
+        ("pgxs braces", "include ${PGXS}\n", true),
+        ("optional pgxs", "-include $(PGXS)\n", true),
+        ("other include", "include common.mk\n", false),
+    ] {
+        fs::write(&makefile, body)?;
+        assert_eq!(exp, defines_uninstall(&makefile), "{name}");
+    }
+    assert!(!defines_uninstall(&tmp.as_ref().join("nonesuch")));
+
+    Ok(())
+}
+
+#[test]
+fn artifacts() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
     let pipe = Pgxs::new(dir, PgConfig::from_map(HashMap::new()));
+    assert!(pipe.artifacts()?.is_empty());
+
+    // Find shared libraries and bitcode, but not sources.
+    fs::create_dir_all(dir.join("src").join("pair"))?;
+    fs::create_dir(dir.join("sql"))?;
+    for file in [
+        "pair.c",
+        "pair.so",
+        "src/pair/pair.bc",
+        "sql/pair.sql",
+        "sql/pair--1.0.sql",
+    ] {
+        fs::write(dir.join(file), "")?;
+    }
+    assert_eq!(
+        vec![
+            dir.join("pair.so"),
+            dir.join("src").join("pair").join("pair.bc")
+        ],
+        pipe.artifacts()?
+    );
+
+    // Add built data files that exist.
+    fs::write(
+        dir.join("Makefile"),
+        "EXTENSION = pair\nDATA_built = sql/pair--1.0.sql sql/nonesuch.sql\nDATA_built += $(wildcard x/*.sql)\n",
+    )?;
+    assert_eq!(
+        vec![
+            dir.join("pair.so"),
+            dir.join("sql").join("pair--1.0.sql"),
+            dir.join("src").join("pair").join("pair.bc")
+        ],
+        pipe.artifacts()?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn data_built_vars() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let makefile = tmp.path().join("Makefile");
+    for (name, body, exp) in [
+        ("empty", "", vec![]),
+        ("one", "DATA_built = a.sql\n", vec!["a.sql"]),
+        (
+            "append",
+            "DATA_built := a.sql b.sql\nDATA_built += c.sql\n",
+            vec!["a.sql", "b.sql", "c.sql"],
+        ),
+        (
+            "variable",
+            "DATA_built = $(EXTENSION).sql a.sql\n",
+            vec!["a.sql"],
+        ),
+        ("other var", "DATA = a.sql\n", vec![]),
+    ] {
+        fs::write(&makefile, body)?;
+        assert_eq!(exp, data_built(&makefile), "{name}");
+    }
+    assert!(data_built(&tmp.path().join("nonesuch")).is_empty());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn make_args() -> Result<(), BuildError> {
+    // Makefile fails unless PG_CPPFLAGS has both flags.
+    let tmp = tempdir()?;
+    fs::write(
+        tmp.as_ref().join("Makefile"),
+        "all installcheck install:\n\ttest \"$(PG_CPPFLAGS)\" = \"-DFOO -DBAR\"\n",
+    )?;
+    let mut pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    assert!(pipe.compile().is_err());
+
+    // Pass the value with spaces as a single argument.
+    pipe.set_make_args(vec!["PG_CPPFLAGS=-DFOO -DBAR".to_string()]);
+    pipe.compile()?;
+    pipe.install()?;
+
+    // Append after the target.
+    fs::write(
+        tmp.as_ref().join("Makefile"),
+        "REGRESS = pair\ninstallcheck:\n\tfalse\n",
+    )?;
+    match pipe.test() {
+        Ok(_) => panic!("test unexpectedly succeeded"),
+        Err(e) => assert_contains!(
+            e.to_string(),
+            "make\" \"installcheck\" \"PG_CPPFLAGS=-DFOO -DBAR\" \"USE_PGXS=1\"`"
+        ),
+    }
+
+    // Don't override PG_CONFIG or USE_PGXS.
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "bindir".to_string(),
+        "/pg/bin".to_string(),
+    )]));
+    let mut pipe = Pgxs::new(&tmp, cfg);
+    pipe.set_make_args(vec![
+        "PG_CONFIG=/opt/pg/bin/pg_config".to_string(),
+        "USE_PGXS=".to_string(),
+    ]);
+    match pipe.test() {
+        Ok(_) => panic!("test unexpectedly succeeded"),
+        Err(e) => assert_contains!(
+            e.to_string(),
+            "make\" \"installcheck\" \"PG_CONFIG=/opt/pg/bin/pg_config\" \"USE_PGXS=\"`"
+        ),
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn destdir() -> Result<(), BuildError> {
+    // Makefile records DESTDIR.
+    let tmp = tempdir()?;
+    fs::write(
+        tmp.as_ref().join("Makefile"),
+        "install:\n\techo '$(DESTDIR)' > out.txt\n",
+    )?;
+    let out = tmp.as_ref().join("out.txt");
+    let stage = tmp.as_ref().join("stage");
+
+    // Never use sudo for a staged install.
+    let mut pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    pipe.set_wrapper(vec!["nonesuch".to_string()]);
+    pipe.set_destdir(Some(stage.clone()));
+    assert_eq!(Some(stage.as_path()), pipe.destdir());
+    let exp = format!(
+        "\"nonesuch\" \"make\" \"install\" \"DESTDIR={}\"",
+        stage.display()
+    );
+    match pipe.install() {
+        Ok(_) => panic!("install unexpectedly succeeded"),
+        Err(e) => assert_contains!(e.to_string(), exp.as_str()),
+    }
+
+    // Install into DESTDIR.
+    pipe.set_wrapper(Vec::new());
+    pipe.install()?;
+    assert_eq!(
+        stage.display().to_string(),
+        fs::read_to_string(&out)?.trim_end()
+    );
+
+    // Install without DESTDIR.
+    pipe.set_destdir(None);
+    assert_eq!(None, pipe.destdir());
+    pipe.install()?;
+    assert_eq!("", fs::read_to_string(&out)?.trim_end());
+
+    Ok(())
+}
+
+#[test]
+fn test() -> Result<(), BuildError> {
+    // Declares tests but lacks the PGXS installcheck target.
+    let tmp = tempdir()?;
+    fs::write(tmp.as_ref().join("Makefile"), "REGRESS = pair\n")?;
+    let pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
     assert!(pipe.test().is_err());
     Ok(())
 }
 
+#[test]
+#[cfg(target_family = "unix")]
+fn test_mode() -> Result<(), BuildError> {
+    // Makefile records the variables passed to installcheck.
+    let tmp = tempdir()?;
+    let out = tmp.as_ref().join("out.txt");
+    fs::write(
+        tmp.as_ref().join("Makefile"),
+        "REGRESS = pair\ninstallcheck:\n\techo '$(REGRESS)|$(TAP_TESTS)' > out.txt\n",
+    )?;
+
+    // Put a mock prove in the PATH.
+    let bin = tmp.as_ref().join("bin");
+    fs::create_dir(&bin)?;
+    crate::tests::compile_mock("echo", &bin.join("prove").display().to_string());
+    let path = std::env::join_paths(
+        std::iter::once(bin.clone())
+            .chain(std::env::split_paths(&std::env::var_os("PATH").unwrap())),
+    )
+    .unwrap();
+
+    let mut pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    temp_env::with_var("PATH", Some(&path), || -> Result<(), BuildError> {
+        // Without a t directory, run only regression tests.
+        for (mode, exp) in [(TestMode::Both, "pair|"), (TestMode::Regress, "pair|")] {
+            pipe.set_test_mode(mode);
+            pipe.test()?;
+            assert_eq!(exp, fs::read_to_string(&out)?.trim_end(), "{mode:?}");
+        }
+        pipe.set_test_mode(TestMode::Tap);
+        fs::remove_file(&out)?;
+        pipe.test()?;
+        assert!(!out.exists());
+
+        // A t directory without .pl files, such as pgTAP SQL, is not TAP.
+        fs::create_dir(tmp.as_ref().join("t"))?;
+        fs::write(tmp.as_ref().join("t").join("pair.sql"), "SELECT 1;\n")?;
+        for (mode, exp) in [(TestMode::Both, "pair|"), (TestMode::Regress, "pair|")] {
+            pipe.set_test_mode(mode);
+            pipe.test()?;
+            assert_eq!(exp, fs::read_to_string(&out)?.trim_end(), "{mode:?}");
+        }
+        pipe.set_test_mode(TestMode::Tap);
+        fs::remove_file(&out)?;
+        pipe.test()?;
+        assert!(!out.exists());
+
+        // With .pl files in the t directory, run the selected tests.
+        fs::write(tmp.as_ref().join("t").join("001_pair.pl"), "")?;
+        for (mode, exp) in [
+            (TestMode::Both, "pair|1"),
+            (TestMode::Regress, "pair|"),
+            (TestMode::Tap, "|1"),
+        ] {
+            pipe.set_test_mode(mode);
+            pipe.test()?;
+            assert_eq!(exp, fs::read_to_string(&out)?.trim_end(), "{mode:?}");
+        }
+        Ok(())
+    })?;
+
+    // Require prove for TAP tests.
+    temp_env::with_var("PATH", Some(tmp.as_ref()), || {
+        for mode in [TestMode::Both, TestMode::Tap] {
+            pipe.set_test_mode(mode);
+            match pipe.test() {
+                Ok(_) => panic!("{mode:?} unexpectedly succeeded"),
+                Err(e) => assert_eq!(
+                    format!(
+                        "cannot find prove in PATH; searched {}",
+                        tmp.as_ref().display()
+                    ),
+                    e.to_string(),
+                ),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_env() -> Result<(), BuildError> {
+    // Makefile records the connection variables for compile and test.
+    let tmp = tempdir()?;
+    fs::write(
+        tmp.as_ref().join("Makefile"),
+        "REGRESS = pair\nall:\n\techo \"$$PGHOST|$$PGPORT\" > all.txt\ninstallcheck:\n\techo \"$$PGHOST|$$PGPORT\" > out.txt\n",
+    )?;
+
+    let mut pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    pipe.set_env("PGHOST".to_string(), Some("db".to_string()));
+    pipe.set_env("PGPORT".to_string(), Some("5432".to_string()));
+    pipe.set_test_env("PGPORT".to_string(), Some("5433".to_string()));
+    pipe.set_test_env("PGHOST".to_string(), None);
+    pipe.compile()?;
+    pipe.test()?;
+
+    // Only the test command sees the test variables.
+    let read = |name: &str| fs::read_to_string(tmp.as_ref().join(name));
+    assert_eq!("db|5432", read("all.txt")?.trim_end());
+    assert_eq!("|5433", read("out.txt")?.trim_end());
+
+    Ok(())
+}
+
+#[test]
+fn no_tests() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    let makefile = tmp.as_ref().join("Makefile");
+
+    // No tests declared; should succeed without running make.
+    for (name, body) in [
+        ("empty", ""),
+        ("empty REGRESS", "EXTENSION = pair\nREGRESS =\n"),
+        ("REGRESS_OPTS only", "REGRESS_OPTS = --inputdir=test\n"),
+    ] {
+        fs::write(&makefile, body)?;
+        assert!(!defines_tests(&makefile), "{name}");
+        if let Err(e) = pipe.test() {
+            panic!("{name}: {e}");
+        }
+    }
+
+    // Tests declared.
+    for (name, body) in [
+        ("REGRESS", "REGRESS = pair\n"),
+        (
+            "REGRESS ?=",
+            "REGRESS ?= $(patsubst test/sql/%.sql,%,$(TESTS))\n",
+        ),
+        ("installcheck", "installcheck:\n\techo ok\n"),
+    ] {
+        fs::write(&makefile, body)?;
+        assert!(defines_tests(&makefile), "{name}");
+    }
+
+    // Missing file.
+    assert!(!defines_tests(&tmp.as_ref().join("nonesuch")));
+
+    Ok(())
+}
+
+#[test]
+fn probe() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let mut pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+    assert_eq!(Probe::default(), pipe.probe());
+    assert!(pipe.is_writeable(&tmp));
+
+    // Use a custom probe.
+    let probe = Probe {
+        prefix: "x-".to_string(),
+        suffix: ".so".to_string(),
+    };
+    pipe.set_probe(probe.clone());
+    assert_eq!(probe, pipe.probe());
+    assert!(pipe.is_writeable(&tmp));
+
+    // A probe that cannot be written should fail.
+    pipe.set_probe(Probe {
+        prefix: "x-".to_string(),
+        suffix: "/nonesuch".to_string(),
+    });
+    assert!(!pipe.is_writeable(&tmp));
+
+    Ok(())
+}
+
+#[test]
+fn msvc() -> Result<(), BuildError> {
+    for (cc, exp) in [
+        (None, false),
+        (Some("gcc"), false),
+        (Some("clang -std=gnu99"), false),
+        (Some("x86_64-w64-mingw32-gcc"), false),
+        (Some("not recorded"), true),
+        (Some("cl"), true),
+        (Some("CL.EXE /nologo"), true),
+    ] {
+        let map = match cc {
+            Some(cc) => HashMap::from([("cc".to_string(), cc.to_string())]),
+            None => HashMap::new(),
+        };
+        assert_eq!(exp, is_msvc(&PgConfig::from_map(map)), "{cc:?}");
+    }
+
+    // Every make step should fail with a clear error.
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "cc".to_string(),
+        "not recorded".to_string(),
+    )]));
+    fs::write(tmp.as_ref().join("Makefile"), "REGRESS = pair\n")?;
+    let pipe = Pgxs::new(&tmp, cfg);
+    for (step, res) in [
+        ("compile", pipe.compile()),
+        ("test", pipe.test()),
+        ("install", pipe.install()),
+    ] {
+        match res {
+            Ok(_) => panic!("{step} unexpectedly succeeded"),
+            Err(e) => assert_eq!(
+                "pgxs pipeline does not support Postgres built with MSVC; use a MinGW build of Postgres",
+                e.to_string(),
+                "{step}"
+            ),
+        }
+    }
+
+    Ok(())
+}
+
 #[test]
 fn install() -> Result<(), BuildError> {
     let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
@@ -145,3 +818,42 @@ fn install() -> Result<(), BuildError> {
     assert!(pipe.install().is_err());
     Ok(())
 }
+
+#[test]
+fn needs_rebuild() -> Result<(), BuildError> {
+    use std::time::{Duration, SystemTime};
+    let tmp = tempdir()?;
+    let touch = |name: &str, secs: u64| -> Result<(), BuildError> {
+        let file = File::create(tmp.path().join(name))?;
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))?;
+        Ok(())
+    };
+    let pipe = Pgxs::new(&tmp, PgConfig::from_map(HashMap::new()));
+
+    // Never built.
+    touch("Makefile", 100)?;
+    touch("pair.c", 100)?;
+    touch("README.md", 100)?;
+    assert!(pipe.needs_rebuild());
+
+    // Built since.
+    touch("pair.so", 200)?;
+    assert!(!pipe.needs_rebuild());
+
+    // Non-source files do not matter.
+    touch("README.md", 300)?;
+    assert!(!pipe.needs_rebuild());
+
+    // Modified source file.
+    fs::create_dir(tmp.path().join("src"))?;
+    touch("src/util.h", 300)?;
+    assert!(pipe.needs_rebuild());
+    touch("src/util.h", 100)?;
+    assert!(!pipe.needs_rebuild());
+
+    // Modified Makefile.
+    touch("Makefile", 300)?;
+    assert!(pipe.needs_rebuild());
+
+    Ok(())
+}
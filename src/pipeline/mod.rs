@@ -1,8 +1,34 @@
 //! Build Pipeline interface definition.
 
 use crate::{error::BuildError, pg_config::PgConfig};
-use log::debug;
-use std::{io::Write, path::Path, process::Command};
+use log::{debug, warn};
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Names the temporary file that [`Pipeline::is_writeable`] creates to test
+/// write access to a directory. The file name starts with `prefix`, ends
+/// with `suffix`, and has random characters in between.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Probe {
+    pub(crate) prefix: String,
+    pub(crate) suffix: String,
+}
+
+impl Default for Probe {
+    fn default() -> Self {
+        Probe {
+            prefix: "pgxn-".to_string(),
+            suffix: ".test".to_string(),
+        }
+    }
+}
 
 /// Defines the interface for build pipelines to configure, compile, and test
 /// PGXN distributions.
@@ -28,35 +54,140 @@ pub(crate) trait Pipeline<P: AsRef<Path>> {
     /// Tests a distribution a particular platform and Postgres version.
     fn test(&self) -> Result<(), BuildError>;
 
+    /// Removes the files installed by [`install`](Self::install).
+    fn uninstall(&self) -> Result<(), BuildError>;
+
+    /// Removes the files produced by compiling a distribution, so that the
+    /// next compile starts fresh. Succeeds without running anything if
+    /// there is nothing to clean.
+    fn clean(&self) -> Result<(), BuildError>;
+
+    /// Returns the files produced by [`compile`](Self::compile) that
+    /// [`install`](Self::install) would install, such as shared libraries,
+    /// so that they may be inspected or signed before installation. Returns
+    /// an empty list if the distribution has not been compiled.
+    fn artifacts(&self) -> Result<Vec<PathBuf>, BuildError>;
+
+    /// Returns true if the distribution needs to be compiled, because
+    /// it has not been compiled or a source file has changed since it was.
+    fn needs_rebuild(&self) -> bool;
+
     /// Returns the directory passed to [`new`].
     fn dir(&self) -> &P;
 
     /// Returns the PgConfig passed to [`new`].
     fn pg_config(&self) -> &PgConfig;
 
-    // maybe_sudo returns a Command that starts with the sudo command if
-    // `sudo` is true and the `pkglibdir` returned by pg_config isn't
-    // writeable by the current user.
+    /// Returns the Probe [`is_writeable`](Self::is_writeable) uses to test
+    /// write access. Defaults to a file named `pgxn-*.test`.
+    fn probe(&self) -> Probe {
+        Probe::default()
+    }
+
+    /// Returns the wrapper command and arguments with which to run every
+    /// command, such as `prlimit` or `systemd-run`, to sandbox or limit the
+    /// resources available to a build, or `docker run` or `chroot`, to
+    /// isolate it. [`maybe_sudo`](Self::maybe_sudo) replaces [`DIR_VAR`]
+    /// in each item with the build directory. Defaults to no wrapper.
+    fn wrapper(&self) -> &[String] {
+        &[]
+    }
+
+    /// Returns true if commands should run in the C locale, so that
+    /// compiler and `make` messages are not localized. Defaults to true.
+    fn c_locale(&self) -> bool {
+        true
+    }
+
+    /// Returns the environment variables to set for every command, such as
+    /// `CARGO_TARGET_DIR` or `PATH`, and the inherited variables to remove,
+    /// for which the value is [`None`]. Applied after the C locale
+    /// variables, so may override them. Defaults to no changes.
+    fn env(&self) -> &[(String, Option<String>)] {
+        &[]
+    }
+
+    /// Returns the maximum time a command may run before
+    /// [`run`](Self::run) kills it. Defaults to [`None`], for no limit.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns the program with which [`maybe_sudo`](Self::maybe_sudo)
+    /// runs commands with elevated privileges, such as `doas`. Defaults to
+    /// `sudo`.
+    fn sudo_program(&self) -> &str {
+        "sudo"
+    }
+
+    /// Returns true if [`maybe_sudo`](Self::maybe_sudo) should pass `-n`
+    /// to the [`sudo_program`](Self::sudo_program), so that it fails
+    /// rather than prompts for a password. Defaults to false.
+    fn sudo_non_interactive(&self) -> bool {
+        false
+    }
+
+    /// Returns the maximum number of bytes of each output stream that
+    /// [`capture`](Self::capture) keeps. Defaults to [`MAX_OUTPUT`].
+    fn output_cap(&self) -> usize {
+        MAX_OUTPUT
+    }
+
+    /// Returns the cell in which [`capture`](Self::capture) records the
+    /// output of the most recent command that ran to completion, whether or
+    /// not it succeeded. Defaults to [`None`], to record nothing.
+    fn last_output(&self) -> Option<&RefCell<Option<Captured>>> {
+        None
+    }
+
+    // maybe_sudo returns a Command that starts with the sudo program, and
+    // `-n` if sudo_non_interactive returns true, if `sudo` is true and the
+    // `pkglibdir` returned by pg_config isn't writeable by the current
+    // user. Either way, the command starts with
+    // the wrapper, if any, with DIR_VAR replaced by the build directory,
+    // sets `LC_ALL` and `LANG` to `C` if c_locale returns true, and then
+    // sets or removes the variables returned by env.
     fn maybe_sudo(&self, program: &str, sudo: bool) -> Command {
+        let dir = self.dir().as_ref().display().to_string();
+        let mut argv: Vec<String> = self
+            .wrapper()
+            .iter()
+            .map(|a| a.replace(DIR_VAR, &dir))
+            .collect();
         if sudo {
-            if let Some(dir) = self.pg_config().get("pkglibdir") {
+            if let Some(dir) = self.pg_config().pkglibdir() {
                 if !self.is_writeable(dir) {
-                    let mut c = Command::new("sudo");
-                    c.arg(program);
-                    return c;
+                    argv.push(self.sudo_program().to_string());
+                    if self.sudo_non_interactive() {
+                        argv.push("-n".to_string());
+                    }
                 }
             }
         }
-        Command::new(program)
+        argv.push(program.to_string());
+        let mut c = Command::new(&argv[0]);
+        c.args(&argv[1..]);
+        if self.c_locale() {
+            c.env("LC_ALL", "C").env("LANG", "C");
+        }
+        for (key, val) in self.env() {
+            match val {
+                Some(val) => c.env(key, val),
+                None => c.env_remove(key),
+            };
+        }
+        c
     }
 
-    /// Attempts to write a temporary file to `dir` and returns `true` on
-    /// success and `false` on failure. The temporary file will be deleted.
+    /// Attempts to write a temporary file named for [`probe`](Self::probe)
+    /// to `dir` and returns `true` on success and `false` on failure. The
+    /// temporary file will be deleted.
     fn is_writeable<D: AsRef<Path>>(&self, dir: D) -> bool {
         debug!(dir:display = crate::filename(&dir); "testing write access");
+        let probe = self.probe();
         match tempfile::Builder::new()
-            .prefix("pgxn-")
-            .suffix(".test")
+            .prefix(&probe.prefix)
+            .suffix(&probe.suffix)
             .tempfile_in(dir)
         {
             Ok(f) => write!(&f, "ok").is_ok(),
@@ -66,32 +197,363 @@ pub(crate) trait Pipeline<P: AsRef<Path>> {
 
     /// Run a command. Runs it with elevated privileges when `sudo` is true
     /// and `pg_config --pkglibdir` isn't writeable by the current user.
+    /// Kills it and returns [`BuildError::Timeout`] if it runs longer than
+    /// [`timeout`](Self::timeout).
     fn run<S, I>(&self, program: &str, args: I, sudo: bool) -> Result<(), BuildError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        self.capture(program, args, sudo).map(|_| ())
+    }
+
+    /// Runs a command like [`run`](Self::run) and returns its output. Keeps
+    /// only the last [`output_cap`](Self::output_cap) bytes of each output
+    /// stream, so that a noisy command cannot exhaust memory. Reads the output as raw bytes
+    /// and replaces invalid UTF-8, such as paths in a non-UTF-8 locale, with
+    /// U+FFFD, so that stray bytes never abort reading.
+    fn capture<S, I>(&self, program: &str, args: I, sudo: bool) -> Result<Captured, BuildError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        self.capture_env(program, args, sudo, &[])
+    }
+
+    /// Runs a command like [`capture`](Self::capture), but also sets or,
+    /// for [`None`] values, removes the variables in `env`, after those
+    /// returned by [`env`](Self::env).
+    fn capture_env<S, I>(
+        &self,
+        program: &str,
+        args: I,
+        sudo: bool,
+        env: &[(String, Option<String>)],
+    ) -> Result<Captured, BuildError>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<std::ffi::OsStr>,
     {
         // Use `sudo` if the param is set.
         let mut cmd = self.maybe_sudo(program, sudo);
+        for (key, val) in env {
+            match val {
+                Some(val) => cmd.env(key, val),
+                None => cmd.env_remove(key),
+            };
+        }
         cmd.args(args);
         cmd.current_dir(self.dir());
-        match cmd.output() {
-            Ok(out) => {
+        let start = Instant::now();
+        match output(&mut cmd, self.timeout(), self.output_cap()) {
+            Ok(Some(out)) => {
+                let captured = Captured {
+                    stdout: String::from_utf8_lossy(&out.stdout).to_string(),
+                    stderr: String::from_utf8_lossy(&out.stderr).to_string(),
+                };
+                if let Some(cell) = self.last_output() {
+                    cell.replace(Some(captured.clone()));
+                }
                 if !out.status.success() {
                     return Err(BuildError::Command(
                         format!("{:?}", cmd),
-                        String::from_utf8_lossy(&out.stderr).to_string(),
+                        captured.stderr,
+                        Some(out.status),
                     ));
                 }
-                Ok(())
+                Ok(captured)
             }
+            Ok(None) => Err(BuildError::Timeout(format!("{:?}", cmd), start.elapsed())),
             Err(e) => Err(BuildError::Command(
                 format!("{:?}", cmd),
                 e.kind().to_string(),
+                None,
             )),
         }
     }
 }
 
+/// The output of a build command, as returned by
+/// [`Builder::last_output`](crate::Builder::last_output). Holds at most the
+/// last [`Builder::output_cap`](crate::Builder::output_cap) bytes of each
+/// stream, with invalid UTF-8 replaced by U+FFFD.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Captured {
+    /// The standard output of the command.
+    pub stdout: String,
+    /// The standard error of the command.
+    pub stderr: String,
+}
+
+/// The default maximum number of bytes of each output stream that
+/// [`Pipeline::capture`] keeps: 1 MiB.
+pub(crate) const MAX_OUTPUT: usize = 1024 * 1024;
+
+/// How often [`output`] checks whether a command with a timeout has exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs `cmd` like [`Command::output`], but keeps only the last `cap` bytes
+/// of each output stream. If `timeout` is set, kills the command if it runs
+/// longer and returns [`None`]. In that case on Unix, runs the command in
+/// its own process group and kills the whole group, so that processes it
+/// started, such as the compilers run by `make`, die with it and release
+/// its output pipes. Waits for the threads reading the output to finish
+/// either way, unless it cannot kill the command, such as when `sudo` runs
+/// it as another user, in which case it leaves the command to exit in the
+/// background and returns [`None`] without waiting.
+fn output(cmd: &mut Command, timeout: Option<Duration>, cap: usize) -> io::Result<Option<Output>> {
+    output_with(cmd, timeout, cap, kill)
+}
+
+/// Implements [`output`], calling `kill` to kill a timed out command.
+fn output_with(
+    cmd: &mut Command,
+    timeout: Option<Duration>,
+    cap: usize,
+    kill: fn(&mut Child) -> bool,
+) -> io::Result<Option<Output>> {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    if timeout.is_some() {
+        std::os::unix::process::CommandExt::process_group(cmd, 0);
+    }
+    let mut child = ChildGuard::new(cmd.spawn()?);
+
+    // Read the output in threads so that full pipes don't block the child.
+    let stdout = child.stdout.take().map(|p| read_tail(p, cap));
+    let stderr = child.stderr.take().map(|p| read_tail(p, cap));
+
+    let status = match timeout {
+        None => Some(child.wait()?),
+        Some(timeout) => {
+            let start = Instant::now();
+            loop {
+                if let Some(status) = child.try_wait()? {
+                    break Some(status);
+                }
+                let elapsed = start.elapsed();
+                if elapsed >= timeout {
+                    if !kill(&mut child) {
+                        abandon(child);
+                        return Ok(None);
+                    }
+                    child.wait()?;
+                    break None;
+                }
+                thread::sleep(POLL_INTERVAL.min(timeout - elapsed));
+            }
+        }
+    };
+
+    child.done = true;
+
+    let join =
+        |h: Option<thread::JoinHandle<Vec<u8>>>| h.and_then(|h| h.join().ok()).unwrap_or_default();
+    let (stdout, stderr) = (join(stdout), join(stderr));
+    Ok(status.map(|status| Output {
+        status,
+        stdout,
+        stderr,
+    }))
+}
+
+/// Owns a running child process and kills it when dropped, unless `done`
+/// is true, so that the child does not keep running orphaned if waiting
+/// for it fails or the calling thread panics.
+struct ChildGuard {
+    child: Child,
+    done: bool,
+}
+
+impl ChildGuard {
+    fn new(child: Child) -> Self {
+        ChildGuard { child, done: false }
+    }
+}
+
+impl std::ops::Deref for ChildGuard {
+    type Target = Child;
+    fn deref(&self) -> &Child {
+        &self.child
+    }
+}
+
+impl std::ops::DerefMut for ChildGuard {
+    fn deref_mut(&mut self) -> &mut Child {
+        &mut self.child
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if !self.done {
+            debug!(pid = self.child.id(); "killing abandoned command");
+            let _ = self.child.kill();
+            // Reap it.
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// Reads `pipe` to the end in a new thread and returns its last `cap`
+/// bytes.
+fn read_tail<R: Read + Send + 'static>(mut pipe: R, cap: usize) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let mut chunk = [0; 8192];
+        loop {
+            let n = match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            buf.extend_from_slice(&chunk[..n]);
+            // Drain in bulk rather than on every read.
+            if buf.len() > cap * 2 {
+                buf.drain(..buf.len() - cap);
+            }
+        }
+        if buf.len() > cap {
+            buf.drain(..buf.len() - cap);
+        }
+        buf
+    })
+}
+
+/// Kills `child` and, on Unix, the other processes in its process group.
+/// Returns false if it cannot kill them, such as when they run as another
+/// user.
+fn kill(child: &mut Child) -> bool {
+    debug!(pid = child.id(); "killing timed out command");
+    #[cfg(unix)]
+    {
+        // The child leads its process group, so the group ID is its PID.
+        let pgid = child.id() as libc::pid_t;
+        // SAFETY: killpg only sends a signal; it has no memory safety
+        // requirements.
+        if unsafe { libc::killpg(pgid, libc::SIGKILL) } != 0 {
+            warn!(pid = child.id(), error:display = io::Error::last_os_error(); "cannot kill process group");
+            // Kill the child anyway, but the rest of the group may still
+            // hold its output pipes.
+            let _ = child.kill();
+            return false;
+        }
+    }
+    child.kill().is_ok()
+}
+
+/// Leaves `child`, which [`kill`] could not kill, to exit on its own, and
+/// reaps it in a background thread rather than blocking until it does.
+fn abandon(mut child: ChildGuard) {
+    warn!(pid = child.id(); "abandoning timed out command");
+    child.done = true;
+    thread::spawn(move || {
+        let _ = child.wait();
+    });
+}
+
+/// Placeholder for the build directory in [`Pipeline::wrapper`] items, for
+/// example to mount it into a container with `docker run -v {dir}:{dir}`.
+pub(crate) const DIR_VAR: &str = "{dir}";
+
+/// File extensions for shared libraries on supported platforms.
+pub(crate) const SHLIB_EXTENSIONS: [&str; 3] = ["so", "dylib", "dll"];
+
+/// Sets `key` to `val` in `env`, replacing any existing entry for `key`.
+/// A `val` of [`None`] removes `key` from the environment of commands.
+pub(crate) fn set_env(env: &mut Vec<(String, Option<String>)>, key: String, val: Option<String>) {
+    match env.iter_mut().find(|(k, _)| *k == key) {
+        Some(entry) => entry.1 = val,
+        None => env.push((key, val)),
+    }
+}
+
+/// Returns true if `path` has one of `exts` as its extension.
+pub(crate) fn has_extension(path: &Path, exts: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| exts.contains(&e))
+}
+
+/// Recursively collects the files in `dir` for which `pred` returns true,
+/// skipping hidden directories such as `.git`.
+pub(crate) fn find_files<F>(dir: &Path, pred: &F) -> Vec<PathBuf>
+where
+    F: Fn(&Path) -> bool,
+{
+    let mut files = Vec::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for path in entries.map_while(Result::ok).map(|e| e.path()) {
+            if path.is_dir() {
+                if !crate::filename(&path).starts_with('.') {
+                    files.extend(find_files(&path, pred));
+                }
+            } else if pred(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+/// Searches the directories in `PATH` for an executable named `name` and
+/// returns its path. Returns [`BuildError::NotFound`] with the list of
+/// searched directories if none is found.
+pub(crate) fn find_in_path(name: &'static str) -> Result<PathBuf, BuildError> {
+    let exe = format!("{name}{}", env::consts::EXE_SUFFIX);
+    let mut searched = Vec::new();
+    if let Some(path) = env::var_os("PATH") {
+        for dir in env::split_paths(&path) {
+            let file = dir.join(&exe);
+            if is_executable(&file) {
+                return Ok(file);
+            }
+            searched.push(dir.display().to_string());
+        }
+    }
+    Err(BuildError::NotFound(name, searched))
+}
+
+/// Returns true if `path` is a file that the current user may execute.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// Returns true if `path` is a file.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Returns true if `artifacts` is empty, if any file in `sources` was
+/// modified after the oldest file in `artifacts`, or if any modification
+/// time cannot be determined.
+pub(crate) fn is_stale(sources: &[PathBuf], artifacts: &[PathBuf]) -> bool {
+    let mtime = |p: &PathBuf| p.metadata().and_then(|m| m.modified()).ok();
+    let built = match artifacts.iter().map(mtime).collect::<Option<Vec<_>>>() {
+        Some(times) => match times.into_iter().min() {
+            Some(t) => t,
+            None => return true,
+        },
+        None => return true,
+    };
+    sources.iter().any(|src| match mtime(src) {
+        Some(t) => {
+            if t > built {
+                debug!(file:display = src.display(); "modified since build");
+                return true;
+            }
+            false
+        }
+        None => true,
+    })
+}
+
 #[cfg(test)]
 mod tests;
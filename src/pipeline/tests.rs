@@ -1,19 +1,70 @@
 use super::*;
 use crate::tests::compile_mock;
 use assertables::*;
-use std::{collections::HashMap, env};
+use std::{collections::HashMap, env, fs};
 use tempfile::tempdir;
 
 struct TestPipeline<P: AsRef<Path>> {
     dir: P,
     cfg: PgConfig,
+    wrapper: Vec<String>,
+    c_locale: bool,
+    sudo_program: String,
+    sudo_non_interactive: bool,
+    env: Vec<(String, Option<String>)>,
+    timeout: Option<Duration>,
+    output_cap: usize,
+    last_output: RefCell<Option<Captured>>,
 }
 
 // Create a mock version of the trait.
 #[cfg(test)]
 impl<P: AsRef<Path>> Pipeline<P> for TestPipeline<P> {
     fn new(dir: P, cfg: PgConfig) -> Self {
-        TestPipeline { dir, cfg }
+        TestPipeline {
+            dir,
+            cfg,
+            wrapper: Vec::new(),
+            c_locale: true,
+            sudo_program: "sudo".to_string(),
+            sudo_non_interactive: false,
+            env: Vec::new(),
+            timeout: None,
+            output_cap: MAX_OUTPUT,
+            last_output: RefCell::new(None),
+        }
+    }
+
+    fn wrapper(&self) -> &[String] {
+        &self.wrapper
+    }
+
+    fn c_locale(&self) -> bool {
+        self.c_locale
+    }
+
+    fn sudo_program(&self) -> &str {
+        &self.sudo_program
+    }
+
+    fn sudo_non_interactive(&self) -> bool {
+        self.sudo_non_interactive
+    }
+
+    fn env(&self) -> &[(String, Option<String>)] {
+        &self.env
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn output_cap(&self) -> usize {
+        self.output_cap
+    }
+
+    fn last_output(&self) -> Option<&RefCell<Option<Captured>>> {
+        Some(&self.last_output)
     }
 
     fn dir(&self) -> &P {
@@ -39,6 +90,18 @@ impl<P: AsRef<Path>> Pipeline<P> for TestPipeline<P> {
     fn test(&self) -> Result<(), BuildError> {
         Ok(())
     }
+    fn clean(&self) -> Result<(), BuildError> {
+        Ok(())
+    }
+    fn uninstall(&self) -> Result<(), BuildError> {
+        Ok(())
+    }
+    fn artifacts(&self) -> Result<Vec<PathBuf>, BuildError> {
+        Ok(Vec::new())
+    }
+    fn needs_rebuild(&self) -> bool {
+        true
+    }
 }
 
 #[test]
@@ -72,6 +135,7 @@ fn run() -> Result<(), BuildError> {
         Err(e) => {
             assert_starts_with!(e.to_string(), "executing");
             assert_ends_with!(e.to_string(), " DED: hi\n");
+            assert_eq!(Some(2), e.exit_code());
         }
     }
 
@@ -99,6 +163,292 @@ fn run() -> Result<(), BuildError> {
     Ok(())
 }
 
+#[test]
+fn c_locale() {
+    let tmp = tempdir().unwrap();
+    let mut pipe = TestPipeline::new(&tmp, PgConfig::from_map(HashMap::new()));
+    let env = |cmd: &Command| -> Vec<(String, Option<String>)> {
+        let mut env: Vec<_> = cmd
+            .get_envs()
+            .map(|(k, v)| {
+                (
+                    k.to_string_lossy().to_string(),
+                    v.map(|v| v.to_string_lossy().to_string()),
+                )
+            })
+            .collect();
+        env.sort();
+        env
+    };
+
+    // Set LC_ALL and LANG by default.
+    assert!(pipe.c_locale());
+    let cmd = pipe.maybe_sudo("foo", false);
+    assert_eq!(
+        vec![
+            ("LANG".to_string(), Some("C".to_string())),
+            ("LC_ALL".to_string(), Some("C".to_string())),
+        ],
+        env(&cmd)
+    );
+
+    // Leave the locale alone when disabled.
+    pipe.c_locale = false;
+    let cmd = pipe.maybe_sudo("foo", false);
+    assert!(env(&cmd).is_empty());
+}
+
+#[test]
+fn env() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let exe = tmp
+        .path()
+        .join(format!("env_err{}", env::consts::EXE_SUFFIX));
+    compile_mock("env_err", &exe.display().to_string());
+    let exe = exe.display().to_string();
+    let mut pipe = TestPipeline::new(&tmp, PgConfig::from_map(HashMap::new()));
+
+    // Replace existing values.
+    set_env(&mut pipe.env, "FOO".to_string(), Some("foo".to_string()));
+    set_env(&mut pipe.env, "FOO".to_string(), Some("bar".to_string()));
+    set_env(
+        &mut pipe.env,
+        "LC_ALL".to_string(),
+        Some("en_US".to_string()),
+    );
+    set_env(&mut pipe.env, "PGXN_INHERITED".to_string(), None);
+    assert_eq!(
+        vec![
+            ("FOO".to_string(), Some("bar".to_string())),
+            ("LC_ALL".to_string(), Some("en_US".to_string())),
+            ("PGXN_INHERITED".to_string(), None),
+        ],
+        pipe.env
+    );
+
+    // Set, override, and remove variables in the child.
+    temp_env::with_var("PGXN_INHERITED", Some("yes"), || {
+        match pipe.run(&exe, ["FOO", "LC_ALL", "LANG", "PGXN_INHERITED"], false) {
+            Ok(_) => panic!("env_err unexpectedly succeeded"),
+            Err(e) => assert_ends_with!(
+                e.to_string(),
+                "FOO=bar\nLC_ALL=en_US\nLANG=C\nPGXN_INHERITED unset\n"
+            ),
+        }
+    });
+
+    // Inherit by default.
+    pipe.env.clear();
+    temp_env::with_var("PGXN_INHERITED", Some("yes"), || {
+        match pipe.run(&exe, ["FOO", "PGXN_INHERITED"], false) {
+            Ok(_) => panic!("env_err unexpectedly succeeded"),
+            Err(e) => assert_ends_with!(e.to_string(), "FOO unset\nPGXN_INHERITED=yes\n"),
+        }
+    });
+
+    Ok(())
+}
+
+#[test]
+fn capture() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let mut pipe = TestPipeline::new(&tmp, PgConfig::from_map(HashMap::new()));
+    assert_eq!(None, *pipe.last_output.borrow());
+
+    // Capture stdout.
+    let exe = tmp.path().join(format!("echo{}", env::consts::EXE_SUFFIX));
+    compile_mock("echo", &exe.display().to_string());
+    let out = pipe.capture(&exe.display().to_string(), ["hello", "world"], false)?;
+    assert_eq!(
+        Captured {
+            stdout: "hello world\n".to_string(),
+            stderr: String::new(),
+        },
+        out
+    );
+    assert_eq!(Some(out), *pipe.last_output.borrow());
+
+    // Keep only the tail.
+    pipe.output_cap = 3;
+    let out = pipe.capture(&exe.display().to_string(), ["hello", "world"], false)?;
+    assert_eq!("ld\n", out.stdout);
+    pipe.output_cap = MAX_OUTPUT;
+
+    // Capture stderr.
+    let exe = tmp
+        .path()
+        .join(format!("env_err{}", env::consts::EXE_SUFFIX));
+    compile_mock("env_err", &exe.display().to_string());
+    match pipe.capture(&exe.display().to_string(), ["PGXN_NONESUCH"], false) {
+        Ok(_) => panic!("env_err unexpectedly succeeded"),
+        Err(e) => assert_ends_with!(e.to_string(), "`: PGXN_NONESUCH unset\n"),
+    }
+    assert_eq!(
+        Some(Captured {
+            stdout: String::new(),
+            stderr: "PGXN_NONESUCH unset\n".to_string(),
+        }),
+        *pipe.last_output.borrow()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn capture_invalid_utf8() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let pipe = TestPipeline::new(&tmp, PgConfig::from_map(HashMap::new()));
+    let exe = tmp
+        .path()
+        .join(format!("latin1{}", env::consts::EXE_SUFFIX));
+    compile_mock("latin1", &exe.display().to_string());
+    let exe = exe.display().to_string();
+
+    // Replace invalid bytes rather than dropping lines.
+    let out = pipe.capture(&exe, [""; 0], false)?;
+    assert_eq!("caf\u{FFFD}\nok\n", out.stdout);
+    assert_eq!("caf\u{FFFD}\n", out.stderr);
+
+    // Likewise in errors.
+    match pipe.run(&exe, ["fail"], false) {
+        Ok(_) => panic!("latin1 unexpectedly succeeded"),
+        Err(e) => assert_ends_with!(e.to_string(), "`: caf\u{FFFD}\n"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn read_tail() {
+    for (name, input, cap, exp) in [
+        ("empty", "", 4, ""),
+        ("under cap", "abc", 4, "abc"),
+        ("at cap", "abcd", 4, "abcd"),
+        ("over cap", "abcdefghij", 4, "ghij"),
+        ("zero cap", "abc", 0, ""),
+    ] {
+        let out = super::read_tail(std::io::Cursor::new(input.as_bytes().to_vec()), cap);
+        assert_eq!(exp.as_bytes(), out.join().unwrap(), "{name}");
+    }
+
+    // Keep the tail of output much larger than the cap.
+    let input: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+    let out = super::read_tail(std::io::Cursor::new(input.clone()), 1000);
+    assert_eq!(&input[99_000..], out.join().unwrap());
+
+    // Keep reading after an interrupted read.
+    struct Interrupted(bool, std::io::Cursor<&'static [u8]>);
+    impl std::io::Read for Interrupted {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0 = !self.0;
+            if self.0 {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            self.1.read(buf)
+        }
+    }
+    let input = Interrupted(false, std::io::Cursor::new(b"hello"));
+    assert_eq!(b"hello", &super::read_tail(input, 10).join().unwrap()[..]);
+}
+
+#[test]
+fn timeout() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let mut pipe = TestPipeline::new(&tmp, PgConfig::from_map(HashMap::new()));
+    pipe.timeout = Some(Duration::from_secs(30));
+
+    // Succeed within the timeout.
+    let exe = tmp.path().join(format!("echo{}", env::consts::EXE_SUFFIX));
+    compile_mock("echo", &exe.display().to_string());
+    pipe.run(&exe.display().to_string(), ["hello"], false)?;
+
+    // Report failure within the timeout.
+    let exe = tmp
+        .path()
+        .join(format!("exit_err{}", env::consts::EXE_SUFFIX));
+    compile_mock("exit_err", &exe.display().to_string());
+    match pipe.run(&exe.display().to_string(), ["hi"], false) {
+        Ok(_) => panic!("exit_err unexpectedly succeeded"),
+        Err(e) => assert_ends_with!(e.to_string(), "DED: hi\n"),
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn timeout_kill() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let mut pipe = TestPipeline::new(&tmp, PgConfig::from_map(HashMap::new()));
+    pipe.timeout = Some(Duration::from_millis(200));
+
+    // Kill the command and the background process holding its output.
+    let start = std::time::Instant::now();
+    match pipe.run("sh", ["-c", "sleep 10 & sleep 10"], false) {
+        Ok(_) => panic!("sh unexpectedly succeeded"),
+        Err(e) => {
+            assert!(matches!(e, BuildError::Timeout(_, _)), "{e}");
+            assert_contains!(e.to_string(), "\"sleep 10 & sleep 10\"`: timed out after");
+        }
+    }
+    assert_lt!(start.elapsed(), Duration::from_secs(5));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn timeout_unkillable() -> Result<(), BuildError> {
+    // Return without waiting for a command it cannot kill, e.g. under sudo.
+    let start = std::time::Instant::now();
+    let mut cmd = Command::new("sleep");
+    cmd.arg("3");
+    let res = super::output_with(&mut cmd, Some(Duration::from_millis(200)), 1024, |_| false)?;
+    assert!(res.is_none());
+    assert_lt!(start.elapsed(), Duration::from_secs(2));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn child_guard() -> Result<(), BuildError> {
+    let running = |pid: u32| {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    };
+
+    // Kill the child when dropped.
+    let guard = ChildGuard::new(Command::new("sleep").arg("10").spawn()?);
+    let pid = guard.id();
+    assert!(running(pid));
+    drop(guard);
+    assert!(!running(pid));
+
+    // Kill the child when a panic unwinds.
+    let (tx, rx) = std::sync::mpsc::channel();
+    let res = std::thread::spawn(move || {
+        let guard = ChildGuard::new(Command::new("sleep").arg("10").spawn().unwrap());
+        tx.send(guard.id()).unwrap();
+        panic!("abandon the command");
+    })
+    .join();
+    assert!(res.is_err());
+    assert!(!running(rx.recv().unwrap()));
+
+    // Leave it alone when done.
+    let mut guard = ChildGuard::new(Command::new("true").spawn()?);
+    assert!(guard.wait()?.success());
+    guard.done = true;
+    drop(guard);
+
+    Ok(())
+}
+
 #[test]
 fn is_writeable() -> Result<(), BuildError> {
     let tmp = tempdir()?;
@@ -143,5 +493,110 @@ fn maybe_sudo() -> Result<(), BuildError> {
     let cmd = pipe.maybe_sudo("foo", false);
     assert_eq!("foo", cmd.get_program().to_str().unwrap());
 
+    // Use another sudo program non-interactively.
+    let mut pipe = pipe;
+    pipe.sudo_program = "doas".to_string();
+    pipe.sudo_non_interactive = true;
+    let cmd = pipe.maybe_sudo("foo", true);
+    assert_eq!("doas", cmd.get_program().to_str().unwrap());
+    let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+    assert_eq!(args, &["-n", "foo"]);
+    pipe.sudo_program = "sudo".to_string();
+    pipe.sudo_non_interactive = false;
+
+    // Start with the wrapper.
+    pipe.wrapper = vec!["prlimit".to_string(), "--as=1000000".to_string()];
+    let cmd = pipe.maybe_sudo("foo", true);
+    assert_eq!("prlimit", cmd.get_program().to_str().unwrap());
+    let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+    assert_eq!(args, &["--as=1000000", "sudo", "foo"]);
+
+    let cmd = pipe.maybe_sudo("foo", false);
+    assert_eq!("prlimit", cmd.get_program().to_str().unwrap());
+    let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+    assert_eq!(args, &["--as=1000000", "foo"]);
+
+    // Replace the build directory placeholder.
+    pipe.wrapper = ["docker", "run", "-v", "{dir}:{dir}", "-w", "{dir}", "pgxn"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let dir = tmp.as_ref().display().to_string();
+    let cmd = pipe.maybe_sudo("foo", false);
+    assert_eq!("docker", cmd.get_program().to_str().unwrap());
+    let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+    assert_eq!(
+        args,
+        &[
+            "run",
+            "-v",
+            &format!("{dir}:{dir}"),
+            "-w",
+            &dir,
+            "pgxn",
+            "foo"
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn stale() -> Result<(), BuildError> {
+    use std::time::{Duration, SystemTime};
+    let tmp = tempdir()?;
+    let path = |name: &str| tmp.path().join(name);
+    for (name, secs) in [
+        ("src.c", 100),
+        ("src.h", 50),
+        ("old.so", 90),
+        ("new.so", 200),
+    ] {
+        let file = fs::File::create(path(name))?;
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))?;
+    }
+
+    // No artifacts.
+    assert!(is_stale(&[path("src.c")], &[]));
+
+    // Sources older than artifacts.
+    assert!(!is_stale(
+        &[path("src.c"), path("src.h")],
+        &[path("new.so")]
+    ));
+    assert!(!is_stale(&[], &[path("new.so")]));
+
+    // A source newer than the oldest artifact.
+    assert!(is_stale(&[path("src.c"), path("src.h")], &[path("old.so")]));
+    assert!(is_stale(
+        &[path("src.h"), path("src.c")],
+        &[path("new.so"), path("old.so")]
+    ));
+
+    // Missing files.
+    assert!(is_stale(&[path("src.c")], &[path("nonesuch.so")]));
+    assert!(is_stale(&[path("nonesuch.c")], &[path("new.so")]));
+
+    Ok(())
+}
+
+#[test]
+fn find() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    for dir in ["src", ".git", "sql"] {
+        fs::create_dir(tmp.path().join(dir))?;
+    }
+    for file in ["Makefile", "src/a.c", "src/a.so", ".git/b.c", "sql/a.sql"] {
+        fs::File::create(tmp.path().join(file))?;
+    }
+
+    let mut files = find_files(tmp.path(), &|p| has_extension(p, &["c", "so"]));
+    files.sort();
+    assert_eq!(
+        vec![tmp.path().join("src/a.c"), tmp.path().join("src/a.so")],
+        files,
+    );
+    assert!(find_files(&tmp.path().join("nonesuch"), &|_| true).is_empty());
+
     Ok(())
 }
@@ -0,0 +1,144 @@
+//! Build plans for multiple distributions.
+//!
+//! A [`BuildPlan`] takes several [`Builder`]s, orders them so that each
+//! distribution builds after the distributions it requires, and then builds
+//! and installs them one at a time.
+
+use crate::{error::BuildError, Builder};
+use log::info;
+use std::{collections::HashMap, path::Path};
+
+/// The prefix for PGXN [purls] in release metadata dependencies.
+///
+/// [purls]: https://github.com/package-url/purl-spec
+const PGXN_PURL: &str = "pkg:pgxn/";
+
+/// BuildPlan builds and installs a set of PGXN releases in dependency order.
+#[derive(Debug, PartialEq)]
+pub struct BuildPlan<P: AsRef<Path>> {
+    builders: Vec<Builder<P>>,
+}
+
+impl<P: AsRef<Path>> BuildPlan<P> {
+    /// Creates a new BuildPlan from `builders`, ordering them so that every
+    /// builder follows the builders for the PGXN packages it requires.
+    /// Returns an error if a builder requires a PGXN package that no other
+    /// builder provides or if the dependencies form a cycle.
+    pub fn new<I>(builders: I) -> Result<Self, BuildError>
+    where
+        I: IntoIterator<Item = Builder<P>>,
+    {
+        let builders: Vec<Builder<P>> = builders.into_iter().collect();
+
+        // Map the distribution and extension names to the builder indexes.
+        let mut provides: HashMap<&str, usize> = HashMap::new();
+        for (i, b) in builders.iter().enumerate() {
+            provides.insert(b.meta.name(), i);
+            if let Some(exts) = b.meta.contents().extensions() {
+                for ext in exts.keys() {
+                    provides.insert(ext.as_str(), i);
+                }
+            }
+        }
+
+        // Collect the indexes of the builders each builder depends on.
+        let mut deps: Vec<Vec<usize>> = Vec::with_capacity(builders.len());
+        for (i, b) in builders.iter().enumerate() {
+            let mut list = Vec::new();
+            for name in requires(&b.meta) {
+                match provides.get(name) {
+                    // Ignore self-references.
+                    Some(&j) if j == i => {}
+                    Some(&j) => {
+                        if !list.contains(&j) {
+                            list.push(j)
+                        }
+                    }
+                    None => {
+                        return Err(BuildError::MissingDependency(
+                            b.meta.name().to_string(),
+                            name.to_string(),
+                        ))
+                    }
+                }
+            }
+            deps.push(list);
+        }
+
+        // Sort topologically, preserving the original order where possible.
+        let mut order: Vec<usize> = Vec::with_capacity(builders.len());
+        let mut done = vec![false; builders.len()];
+        while order.len() < builders.len() {
+            let next = (0..builders.len()).find(|&i| !done[i] && deps[i].iter().all(|&j| done[j]));
+            match next {
+                Some(i) => {
+                    done[i] = true;
+                    order.push(i);
+                }
+                None => {
+                    // Everything left is part of or depends on a cycle.
+                    let names = (0..builders.len())
+                        .filter(|&i| !done[i])
+                        .map(|i| builders[i].meta.name().to_string())
+                        .collect();
+                    return Err(BuildError::DependencyCycle(names));
+                }
+            }
+        }
+
+        // Rearrange the builders into build order.
+        let mut slots: Vec<Option<Builder<P>>> = builders.into_iter().map(Some).collect();
+        let builders = order
+            .into_iter()
+            .map(|i| slots[i].take().unwrap())
+            .collect();
+
+        Ok(BuildPlan { builders })
+    }
+
+    /// Borrows the builders in build order.
+    pub fn builders(&self) -> &[Builder<P>] {
+        self.builders.as_slice()
+    }
+
+    /// Configures, compiles, installs, and tests each distribution in build
+    /// order. Installs before testing, because the PGXS tests run by `make
+    /// installcheck` require the extension to be installed. Stops and
+    /// returns the error from the first step to fail.
+    pub fn run(&self) -> Result<(), BuildError> {
+        for b in &self.builders {
+            info!(dist = b.meta.name(), version:display = b.meta.version(); "building");
+            b.configure()?;
+            b.compile()?;
+            b.install()?;
+            b.test()?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the names of the PGXN packages required by any build phase in
+/// `meta`.
+fn requires(meta: &pgxn_meta::release::Release) -> Vec<&str> {
+    let mut names = Vec::new();
+    if let Some(pkgs) = meta.dependencies().and_then(|d| d.packages()) {
+        for phase in [pkgs.configure(), pkgs.build(), pkgs.test(), pkgs.run()]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(reqs) = phase.requires() {
+                for purl in reqs.keys() {
+                    if let Some(name) = purl.strip_prefix(PGXN_PURL) {
+                        names.push(name);
+                    }
+                }
+            }
+        }
+    }
+    // HashMap keys have no order; sort for consistent error reporting.
+    names.sort_unstable();
+    names
+}
+
+#[cfg(test)]
+mod tests;
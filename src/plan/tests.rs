@@ -0,0 +1,182 @@
+use super::*;
+use crate::{
+    pg_config::PgConfig,
+    tests::{release_meta, with_mock_cargo},
+};
+use pgxn_meta::release::Release;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tempfile::tempdir;
+
+// Returns release metadata for `name` using the pgrx pipeline (which builds
+// with a mock cargo in these tests) and requiring the PGXN packages in `requires`.
+fn meta(name: &str, requires: &[&str]) -> Release {
+    let mut meta = release_meta("pgrx");
+    let obj = meta.as_object_mut().unwrap();
+    obj.insert("name".to_string(), json!(name));
+    obj.insert(
+        "contents".to_string(),
+        json!({"extensions": {name: {"sql": "sql/x.sql", "control": "x.control"}}}),
+    );
+    if !requires.is_empty() {
+        let reqs: serde_json::Map<String, Value> = requires
+            .iter()
+            .map(|r| (format!("pkg:{r}"), json!(0)))
+            .collect();
+        obj.get_mut("dependencies")
+            .unwrap()
+            .as_object_mut()
+            .unwrap()
+            .insert("packages".to_string(), json!({"run": {"requires": reqs}}));
+    }
+    Release::try_from(meta).unwrap()
+}
+
+fn names<P: AsRef<Path>>(plan: &BuildPlan<P>) -> Vec<&str> {
+    plan.builders().iter().map(|b| b.meta.name()).collect()
+}
+
+#[test]
+fn build_order() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    for (name, metas, exp) in [
+        ("empty", vec![], vec![]),
+        ("one", vec![meta("alpha", &[])], vec!["alpha"]),
+        (
+            "independent",
+            vec![meta("alpha", &[]), meta("beta", &[])],
+            vec!["alpha", "beta"],
+        ),
+        (
+            "reversed",
+            vec![meta("alpha", &["pgxn/beta"]), meta("beta", &[])],
+            vec!["beta", "alpha"],
+        ),
+        (
+            "chain",
+            vec![
+                meta("alpha", &["pgxn/beta"]),
+                meta("beta", &["pgxn/gamma"]),
+                meta("gamma", &[]),
+            ],
+            vec!["gamma", "beta", "alpha"],
+        ),
+        (
+            "diamond",
+            vec![
+                meta("alpha", &["pgxn/beta", "pgxn/gamma"]),
+                meta("beta", &["pgxn/delta"]),
+                meta("gamma", &["pgxn/delta"]),
+                meta("delta", &[]),
+            ],
+            vec!["delta", "beta", "gamma", "alpha"],
+        ),
+        (
+            "ignore non-pgxn",
+            vec![meta("alpha", &["postgres/plpgsql", "generic/python"])],
+            vec!["alpha"],
+        ),
+        (
+            "ignore self",
+            vec![meta("alpha", &["pgxn/alpha"])],
+            vec!["alpha"],
+        ),
+    ] {
+        let builders = metas
+            .into_iter()
+            .map(|m| Builder::new(tmp.as_ref(), m, cfg.clone()).unwrap());
+        let plan = BuildPlan::new(builders)?;
+        assert_eq!(exp, names(&plan), "{name}");
+        with_mock_cargo("echo", || assert!(plan.run().is_ok(), "{name} run"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn build_order_err() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    for (name, metas, err) in [
+        (
+            "missing",
+            vec![meta("alpha", &["pgxn/nonesuch"])],
+            "alpha requires nonesuch, which is not in the build plan",
+        ),
+        (
+            "cycle",
+            vec![meta("alpha", &["pgxn/beta"]), meta("beta", &["pgxn/alpha"])],
+            "dependency cycle among alpha, beta",
+        ),
+        (
+            "downstream of cycle",
+            vec![
+                meta("alpha", &[]),
+                meta("beta", &["pgxn/gamma"]),
+                meta("gamma", &["pgxn/delta"]),
+                meta("delta", &["pgxn/beta"]),
+                meta("epsilon", &["pgxn/alpha", "pgxn/delta"]),
+            ],
+            "dependency cycle among beta, gamma, delta, epsilon",
+        ),
+    ] {
+        let builders = metas
+            .into_iter()
+            .map(|m| Builder::new(tmp.as_ref(), m, cfg.clone()).unwrap());
+        match BuildPlan::new(builders) {
+            Ok(_) => panic!("{name} unexpectedly succeeded"),
+            Err(e) => assert_eq!(err, e.to_string(), "{name}"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn run_installs_before_testing() -> Result<(), BuildError> {
+    // The installcheck target fails unless install has run.
+    let tmp = tempdir()?;
+    let stage = tmp.path().join("stage");
+    std::fs::write(
+        tmp.path().join("Makefile"),
+        format!(
+            concat!(
+                "REGRESS = alpha\n",
+                "all:\n\ttouch alpha.so\n",
+                "install:\n\tmkdir -p $(DESTDIR)/lib\n\tcp alpha.so $(DESTDIR)/lib/\n",
+                "installcheck:\n\ttest -f {}/lib/alpha.so\n",
+            ),
+            stage.display(),
+        ),
+    )?;
+    let mut meta = release_meta("pgxs");
+    meta.as_object_mut()
+        .unwrap()
+        .insert("name".to_string(), json!("alpha"));
+    let cfg = PgConfig::from_map(HashMap::new());
+    let builder = Builder::new(tmp.as_ref(), Release::try_from(meta).unwrap(), cfg)?;
+    let plan = BuildPlan::new([builder.destdir(&stage)])?;
+    plan.run()?;
+    assert!(stage.join("lib").join("alpha.so").exists());
+
+    Ok(())
+}
+
+#[test]
+fn run_err() -> Result<(), BuildError> {
+    // PGXS compile fails without a Makefile.
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+    let mut meta = release_meta("pgxs");
+    meta.as_object_mut()
+        .unwrap()
+        .insert("name".to_string(), json!("alpha"));
+    let builder = Builder::new(tmp.as_ref(), Release::try_from(meta).unwrap(), cfg)?;
+    let plan = BuildPlan::new([builder])?;
+    assert!(plan.run().is_err());
+
+    Ok(())
+}
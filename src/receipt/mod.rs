@@ -0,0 +1,196 @@
+//! Install receipts.
+//!
+//! An install receipt records the files that installing a distribution
+//! created or replaced, along with the distribution name and version and
+//! the Postgres version, so that they can later be audited or removed.
+//! Neither `make install` nor `cargo pgrx install` reports the files it
+//! writes reliably, so the receipt compares snapshots taken before and
+//! after installing. For a staged install, the snapshots cover the whole
+//! `DESTDIR`. Otherwise they cover only the entries of the installation
+//! directories named for the distribution, so that they neither walk every
+//! file in a shared installation nor attribute to the distribution files
+//! that something else installed at the same time.
+
+use crate::{error::BuildError, pg_config::PgConfig, ExtensionInfo, InstallDirs};
+use log::info;
+use pgxn_meta::release::Release;
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// The modification times of the files under a set of directories.
+pub(crate) type Snapshot = HashMap<PathBuf, Option<SystemTime>>;
+
+/// Returns the names of the files and directories that installing the
+/// distribution `dist` from `dir` may create: the name of the distribution
+/// and of each of its `extensions`, and the module and directory named by
+/// the `module_pathname` and `directory` parameters of their control files.
+pub(crate) fn names(dir: &Path, dist: &str, extensions: &[ExtensionInfo]) -> Vec<String> {
+    let mut names = vec![dist.to_string()];
+    for ext in extensions {
+        names.push(ext.name().to_string());
+        let Ok(control) = fs::read_to_string(dir.join(ext.control())) else {
+            continue;
+        };
+        for line in control.lines() {
+            let Some((key, val)) = line.split_once('=') else {
+                continue;
+            };
+            let val = val
+                .split('#')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .trim_matches('\'');
+            let name = match key.trim() {
+                "module_pathname" => Path::new(val).file_name(),
+                "directory" if Path::new(val).is_relative() => Some(val.as_ref()),
+                _ => None,
+            };
+            if let Some(name) = name.and_then(|n| n.to_str()).filter(|n| !n.is_empty()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Returns a snapshot of the files that installing a distribution into
+/// `dirs` may write. For a staged install, includes every file under the
+/// `DESTDIR`. Otherwise, includes the files in the `pkglibdir` and its
+/// `bitcode` subdirectory, the `bindir`, the `sharedir`, and the
+/// `extension` and `contrib` subdirectories of the `sharedir`, `docdir`,
+/// and `includedir-server` whose names equal one of `names` or start with one of them followed by
+/// a character other than a letter or digit, such as `pair.so` and
+/// `pair--1.0.sql` for `pair`, and every file under matching directories.
+/// Ignores directories that do not exist.
+pub(crate) fn snapshot(dirs: &InstallDirs, names: &[String]) -> Result<Snapshot, BuildError> {
+    let mut snap = Snapshot::new();
+    if let Some(dir) = dirs.destdir() {
+        walk(dir, &mut snap)?;
+        return Ok(snap);
+    }
+
+    let mut all: Vec<PathBuf> = [dirs.pkglibdir(), dirs.bindir(), dirs.sharedir()]
+        .into_iter()
+        .flatten()
+        .map(Path::to_path_buf)
+        .collect();
+    if let Some(dir) = dirs.pkglibdir() {
+        all.push(dir.join("bitcode"));
+    }
+    for dir in [dirs.sharedir(), dirs.docdir(), dirs.includedir_server()]
+        .into_iter()
+        .flatten()
+    {
+        all.push(dir.join("extension"));
+        all.push(dir.join("contrib"));
+    }
+    for dir in all {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(BuildError::File(
+                    "reading",
+                    dir.display().to_string(),
+                    e.kind(),
+                ))
+            }
+        };
+        for entry in entries {
+            let entry = entry
+                .map_err(|e| BuildError::File("reading", dir.display().to_string(), e.kind()))?;
+            let file = entry.file_name();
+            if names.iter().any(|n| named_for(&file.to_string_lossy(), n)) {
+                record(&entry.path(), &mut snap)?;
+            }
+        }
+    }
+    Ok(snap)
+}
+
+/// Returns true if `file` equals `name` or starts with `name` followed by
+/// a character other than a letter or digit.
+fn named_for(file: &str, name: &str) -> bool {
+    match file.strip_prefix(name) {
+        Some(rest) => !rest.starts_with(|c: char| c.is_alphanumeric()),
+        None => false,
+    }
+}
+
+/// Records the file at `path` and its modification time in `snap`, or
+/// every file under it if it is a directory.
+fn record(path: &Path, snap: &mut Snapshot) -> Result<(), BuildError> {
+    let meta = fs::symlink_metadata(path)
+        .map_err(|e| BuildError::File("reading", path.display().to_string(), e.kind()))?;
+    if meta.is_dir() {
+        walk(path, snap)
+    } else {
+        snap.insert(path.to_path_buf(), meta.modified().ok());
+        Ok(())
+    }
+}
+
+/// Records the files under `dir` and their modification times in `snap`,
+/// descending into subdirectories.
+fn walk(dir: &Path, snap: &mut Snapshot) -> Result<(), BuildError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(BuildError::File(
+                "reading",
+                dir.display().to_string(),
+                e.kind(),
+            ))
+        }
+    };
+    for entry in entries {
+        let entry =
+            entry.map_err(|e| BuildError::File("reading", dir.display().to_string(), e.kind()))?;
+        record(&entry.path(), snap)?;
+    }
+    Ok(())
+}
+
+/// Returns the files in `after` that are missing from or have a different
+/// modification time in `before`, sorted by path.
+pub(crate) fn installed(before: &Snapshot, after: &Snapshot) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = after
+        .iter()
+        .filter(|(path, time)| before.get(*path) != Some(time))
+        .map(|(path, _)| path.clone())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Writes the receipt for installing `files` from `meta` into the Postgres
+/// described by `cfg` to the file at `path`.
+pub(crate) fn write(
+    path: &Path,
+    meta: &Release,
+    cfg: &PgConfig,
+    files: &[PathBuf],
+) -> Result<(), BuildError> {
+    let receipt = json!({
+        "dist": meta.name(),
+        "version": meta.version().to_string(),
+        "pg_version": cfg.get("version"),
+        "files": files,
+    });
+    info!(file:display = path.display(), files = files.len(); "writing install receipt");
+    let data = serde_json::to_vec_pretty(&receipt)?;
+    fs::write(path, data)
+        .map_err(|e| BuildError::File("writing", path.display().to_string(), e.kind()))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,183 @@
+use super::*;
+use crate::tests::release_meta;
+use serde_json::Value;
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn installed() {
+    let now = SystemTime::now();
+    let later = now + Duration::from_secs(1);
+    let before = Snapshot::from([
+        (PathBuf::from("same"), Some(now)),
+        (PathBuf::from("changed"), Some(now)),
+        (PathBuf::from("removed"), Some(now)),
+    ]);
+    let after = Snapshot::from([
+        (PathBuf::from("same"), Some(now)),
+        (PathBuf::from("changed"), Some(later)),
+        (PathBuf::from("new"), None),
+    ]);
+    assert_eq!(
+        vec![PathBuf::from("changed"), PathBuf::from("new")],
+        super::installed(&before, &after),
+    );
+    assert!(super::installed(&after, &after).is_empty());
+}
+
+#[test]
+fn names() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    fs::write(
+        tmp.path().join("pair.control"),
+        concat!(
+            "# pair extension\n",
+            "comment = 'A key = value pair'\n",
+            "module_pathname = '$libdir/pair_lib' # the module\n",
+            "directory = 'pairs'\n",
+        ),
+    )?;
+    let ext = |name: &str| ExtensionInfo {
+        name: name.to_string(),
+        sql: format!("sql/{name}.sql"),
+        control: format!("{name}.control"),
+    };
+
+    // Ignores missing control files.
+    assert_eq!(
+        vec!["other", "pair", "pair_lib", "pairdist", "pairs"],
+        super::names(tmp.path(), "pairdist", &[ext("pair"), ext("other")]),
+    );
+    assert_eq!(vec!["pair"], super::names(tmp.path(), "pair", &[]));
+
+    Ok(())
+}
+
+#[test]
+fn snapshot() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let root = tmp.path();
+    let files = [
+        "bin/pair_dump",
+        "bin/psql",
+        "doc/extension/README.other",
+        "doc/extension/pair.md",
+        "include/server/extension/pair/pair.h",
+        "include/server/postgres.h",
+        "lib/bitcode/other.index.bc",
+        "lib/bitcode/pair.index.bc",
+        "lib/bitcode/pair/pair.bc",
+        "lib/other.so",
+        "lib/pair.so",
+        "lib/pairing.so",
+        "share/extension/other.control",
+        "share/extension/pair--1.0.sql",
+        "share/extension/pair.control",
+        "share/pairs/pair.sql",
+        "share/timezone/UTC",
+    ];
+    for file in files {
+        let path = root.join(file);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, "")?;
+    }
+    let cfg = PgConfig::from_map(HashMap::from([
+        (
+            "pkglibdir".to_string(),
+            root.join("lib").display().to_string(),
+        ),
+        (
+            "sharedir".to_string(),
+            root.join("share").display().to_string(),
+        ),
+        ("bindir".to_string(), root.join("bin").display().to_string()),
+        ("docdir".to_string(), root.join("doc").display().to_string()),
+        (
+            "includedir-server".to_string(),
+            root.join("include").join("server").display().to_string(),
+        ),
+    ]));
+    let keys = |snap: &Snapshot| {
+        let mut keys: Vec<PathBuf> = snap.keys().cloned().collect();
+        keys.sort();
+        keys
+    };
+
+    // Includes only files named for the distribution.
+    let names = vec!["pair".to_string(), "pairs".to_string()];
+    let snap = super::snapshot(&InstallDirs::new(&cfg), &names)?;
+    assert_eq!(
+        [
+            "bin/pair_dump",
+            "doc/extension/pair.md",
+            "include/server/extension/pair/pair.h",
+            "lib/bitcode/pair/pair.bc",
+            "lib/bitcode/pair.index.bc",
+            "lib/pair.so",
+            "share/extension/pair--1.0.sql",
+            "share/extension/pair.control",
+            "share/pairs/pair.sql",
+        ]
+        .iter()
+        .map(|f| root.join(f))
+        .collect::<Vec<_>>(),
+        keys(&snap),
+    );
+
+    // Ignores missing directories.
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "sharedir".to_string(),
+        root.join("nonesuch").display().to_string(),
+    )]));
+    assert!(super::snapshot(&InstallDirs::new(&cfg), &names)?.is_empty());
+
+    // Includes every file in a staged install.
+    let snap = super::snapshot(&InstallDirs::staged(&cfg, &root.join("lib")), &names)?;
+    assert_eq!(
+        [
+            "lib/bitcode/other.index.bc",
+            "lib/bitcode/pair/pair.bc",
+            "lib/bitcode/pair.index.bc",
+            "lib/other.so",
+            "lib/pair.so",
+            "lib/pairing.so",
+        ]
+        .iter()
+        .map(|f| root.join(f))
+        .collect::<Vec<_>>(),
+        keys(&snap),
+    );
+
+    Ok(())
+}
+
+#[test]
+fn write() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let path = tmp.path().join("receipt.json");
+    let meta = Release::try_from(release_meta("pgxs")).unwrap();
+    let cfg = PgConfig::from_map(HashMap::new());
+    super::write(&path, &meta, &cfg, &[PathBuf::from("/pg/lib/pair.so")])?;
+    let receipt: Value = serde_json::from_slice(&fs::read(&path)?)?;
+    assert_eq!(
+        json!({
+            "dist": "pair",
+            "version": "0.1.8",
+            "pg_version": null,
+            "files": ["/pg/lib/pair.so"],
+        }),
+        receipt,
+    );
+
+    // Report write errors.
+    let path = tmp.path().join("nonesuch").join("receipt.json");
+    match super::write(&path, &meta, &cfg, &[]) {
+        Ok(_) => panic!("write unexpectedly succeeded"),
+        Err(e) => assert_eq!(
+            format!("writing {}: entity not found", path.display()),
+            e.to_string()
+        ),
+    }
+
+    Ok(())
+}
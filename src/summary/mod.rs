@@ -0,0 +1,208 @@
+//! Build summaries.
+//!
+//! A [`BuildSummary`] records the result of building a distribution with
+//! [`Builder::build_all`](crate::Builder::build_all): what was built, with
+//! which pipeline, on which platform and Postgres version, how long each
+//! step took and whether it succeeded, the digests of the compiled
+//! artifacts, and where they and the other installed files landed. CI systems and package
+//! managers can persist its JSON representation as the record of a build.
+
+use crate::{error::BuildError, InstallDirs};
+use serde_json::{json, Value};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Records the outcome and duration of a single build step.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StepSummary {
+    pub(crate) name: &'static str,
+    pub(crate) duration: Duration,
+    pub(crate) error: Option<String>,
+}
+
+impl StepSummary {
+    /// Returns the name of the step, such as `configure` or `install`.
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Returns the time it took to run the step.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Borrows the error message if the step failed, or [`None`] if it
+    /// succeeded.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Returns true if the step succeeded.
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Records a file produced by compiling a distribution and its digest.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArtifactSummary {
+    pub(crate) path: PathBuf,
+    pub(crate) sha256: String,
+}
+
+impl ArtifactSummary {
+    /// Borrows the path to the artifact.
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Borrows the hex-encoded SHA-256 digest of the artifact.
+    pub fn sha256(&self) -> &str {
+        self.sha256.as_str()
+    }
+}
+
+/// Summarizes a build of a PGXN distribution.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BuildSummary {
+    pub(crate) dist: String,
+    pub(crate) version: String,
+    pub(crate) pipeline: &'static str,
+    pub(crate) pg_version: Option<String>,
+    pub(crate) os: &'static str,
+    pub(crate) arch: &'static str,
+    pub(crate) steps: Vec<StepSummary>,
+    pub(crate) artifacts: Vec<ArtifactSummary>,
+    pub(crate) installed: Option<InstallDirs>,
+    pub(crate) files: Vec<PathBuf>,
+}
+
+impl BuildSummary {
+    /// Borrows the name of the distribution.
+    pub fn dist(&self) -> &str {
+        self.dist.as_str()
+    }
+
+    /// Borrows the version of the distribution.
+    pub fn version(&self) -> &str {
+        self.version.as_str()
+    }
+
+    /// Returns the name of the pipeline that built the distribution, such
+    /// as `pgxs` or `pgrx`.
+    pub fn pipeline(&self) -> &str {
+        self.pipeline
+    }
+
+    /// Borrows the Postgres version reported by `pg_config`, such as
+    /// `PostgreSQL 17.2`, or [`None`] if it reports none.
+    pub fn pg_version(&self) -> Option<&str> {
+        self.pg_version.as_deref()
+    }
+
+    /// Returns the name of the operating system on which the distribution
+    /// was built, such as `linux` or `macos`.
+    pub fn os(&self) -> &str {
+        self.os
+    }
+
+    /// Returns the name of the CPU architecture on which the distribution
+    /// was built, such as `x86_64` or `aarch64`.
+    pub fn arch(&self) -> &str {
+        self.arch
+    }
+
+    /// Borrows the steps that ran, in order. The build stops at the first
+    /// failed step, so only the last step can have failed.
+    pub fn steps(&self) -> &[StepSummary] {
+        self.steps.as_slice()
+    }
+
+    /// Borrows the artifacts produced by compiling the distribution, along
+    /// with their digests, collected before installing them. Empty if the
+    /// build failed before installing.
+    pub fn artifacts(&self) -> &[ArtifactSummary] {
+        self.artifacts.as_slice()
+    }
+
+    /// Borrows the directories into which the distribution was installed,
+    /// or [`None`] if the build failed before installing.
+    pub fn installed(&self) -> Option<&InstallDirs> {
+        self.installed.as_ref()
+    }
+
+    /// Borrows the paths to the files the distribution installed, sorted.
+    /// Empty if the build failed before installing.
+    pub fn files(&self) -> &[PathBuf] {
+        self.files.as_slice()
+    }
+
+    /// Returns true if every step succeeded.
+    pub fn succeeded(&self) -> bool {
+        self.steps.iter().all(StepSummary::succeeded)
+    }
+
+    /// Runs `step`, recording its duration and outcome as `name`. Returns
+    /// true if it succeeded.
+    pub(crate) fn step<F>(&mut self, name: &'static str, step: F) -> bool
+    where
+        F: FnOnce(&mut Self) -> Result<(), BuildError>,
+    {
+        let start = Instant::now();
+        let res = step(self);
+        self.steps.push(StepSummary {
+            name,
+            duration: start.elapsed(),
+            error: res.as_ref().err().map(ToString::to_string),
+        });
+        res.is_ok()
+    }
+
+    /// Returns the JSON representation of the summary.
+    pub fn to_json(&self) -> Value {
+        let steps: Vec<Value> = self
+            .steps
+            .iter()
+            .map(|s| {
+                json!({
+                    "name": s.name,
+                    "duration_ms": s.duration.as_millis() as u64,
+                    "success": s.succeeded(),
+                    "error": s.error,
+                })
+            })
+            .collect();
+        let artifacts: Vec<Value> = self
+            .artifacts
+            .iter()
+            .map(|a| json!({"path": a.path, "sha256": a.sha256}))
+            .collect();
+        json!({
+            "dist": self.dist,
+            "version": self.version,
+            "pipeline": self.pipeline,
+            "pg_version": self.pg_version,
+            "platform": {
+                "os": self.os,
+                "arch": self.arch,
+            },
+            "success": self.succeeded(),
+            "steps": steps,
+            "artifacts": artifacts,
+            "installed": self.installed.as_ref().map(|d| json!({
+                "pkglibdir": d.pkglibdir(),
+                "sharedir": d.sharedir(),
+                "bindir": d.bindir(),
+                "docdir": d.docdir(),
+                "includedir_server": d.includedir_server(),
+                "destdir": d.destdir(),
+                "files": self.files,
+            })),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,96 @@
+use super::*;
+use crate::pg_config::PgConfig;
+use std::collections::HashMap;
+
+#[test]
+fn summary() {
+    let cfg = PgConfig::from_map(HashMap::from([
+        ("pkglibdir".to_string(), "/pg/lib".to_string()),
+        ("sharedir".to_string(), "/pg/share".to_string()),
+        ("bindir".to_string(), "/pg/bin".to_string()),
+        ("docdir".to_string(), "/pg/doc".to_string()),
+        (
+            "includedir-server".to_string(),
+            "/pg/include/server".to_string(),
+        ),
+    ]));
+    let mut summary = BuildSummary {
+        dist: "pair".to_string(),
+        version: "0.1.8".to_string(),
+        pipeline: "pgxs",
+        pg_version: Some("PostgreSQL 17.2".to_string()),
+        os: "freebsd",
+        arch: "aarch64",
+        steps: vec![
+            StepSummary {
+                name: "configure",
+                duration: Duration::from_millis(12),
+                error: None,
+            },
+            StepSummary {
+                name: "compile",
+                duration: Duration::from_millis(1500),
+                error: None,
+            },
+        ],
+        artifacts: vec![ArtifactSummary {
+            path: PathBuf::from("pair.so"),
+            sha256: "abc123".to_string(),
+        }],
+        installed: Some(InstallDirs::new(&cfg)),
+        files: vec![
+            PathBuf::from("/pg/lib/pair.so"),
+            PathBuf::from("/pg/share/extension/pair.control"),
+        ],
+    };
+    assert!(summary.succeeded());
+    assert_eq!(Duration::from_millis(1500), summary.steps()[1].duration());
+    assert_eq!("freebsd", summary.os());
+    assert_eq!("aarch64", summary.arch());
+    assert_eq!(2, summary.files().len());
+    assert_eq!(
+        json!({
+            "dist": "pair",
+            "version": "0.1.8",
+            "pipeline": "pgxs",
+            "pg_version": "PostgreSQL 17.2",
+            "platform": {"os": "freebsd", "arch": "aarch64"},
+            "success": true,
+            "steps": [
+                {"name": "configure", "duration_ms": 12, "success": true, "error": null},
+                {"name": "compile", "duration_ms": 1500, "success": true, "error": null},
+            ],
+            "artifacts": [{"path": "pair.so", "sha256": "abc123"}],
+            "installed": {
+                "pkglibdir": "/pg/lib",
+                "sharedir": "/pg/share",
+                "bindir": "/pg/bin",
+                "docdir": "/pg/doc",
+                "includedir_server": "/pg/include/server",
+                "destdir": null,
+                "files": ["/pg/lib/pair.so", "/pg/share/extension/pair.control"],
+            },
+        }),
+        summary.to_json(),
+    );
+
+    // Record a failure.
+    summary.steps.push(StepSummary {
+        name: "test",
+        duration: Duration::from_secs(2),
+        error: Some("tests failed".to_string()),
+    });
+    summary.artifacts.clear();
+    summary.installed = None;
+    summary.files.clear();
+    assert!(!summary.succeeded());
+    assert_eq!(Some("tests failed"), summary.steps()[2].error());
+    let json = summary.to_json();
+    assert_eq!(json!(false), json["success"]);
+    assert_eq!(
+        json!({"name": "test", "duration_ms": 2000, "success": false, "error": "tests failed"}),
+        json["steps"][2],
+    );
+    assert_eq!(json!([]), json["artifacts"]);
+    assert_eq!(Value::Null, json["installed"]);
+}
@@ -3,7 +3,7 @@ use serde_json::{json, Value};
 use std::{collections::HashMap, fs::File, io::Write, path::PathBuf, process::Command};
 use tempfile::tempdir;
 
-fn release_meta(pipeline: &str) -> Value {
+pub fn release_meta(pipeline: &str) -> Value {
     json!({
       "name": "pair",
       "abstract": "A key/value pair data type",
@@ -47,13 +47,21 @@ fn pgxs() {
     let exp = Builder {
         pipeline: Build::Pgxs(Pgxs::new(tmp.as_ref(), cfg)),
         meta: rel,
+        frozen: None,
+        receipt: None,
+        patches: Vec::new(),
     };
     assert_eq!(exp, builder, "pgxs");
+    assert_eq!("pgxs", builder.pipeline_name());
+    assert_eq!(dist::Pipeline::Pgxs, builder.pipeline_kind());
     assert!(builder.configure().is_ok());
     assert!(builder.compile().is_err());
     assert!(builder.test().is_err());
     assert!(builder.test().is_err());
     assert!(builder.install().is_err());
+    assert!(builder.clean().is_ok());
+    assert!(builder.artifacts().unwrap().is_empty());
+    assert!(builder.uninstall().is_err());
 }
 
 #[test]
@@ -68,12 +76,841 @@ fn pgrx() {
     let exp = Builder {
         pipeline: Build::Pgrx(Pgrx::new(tmp.as_ref(), cfg.clone())),
         meta: rel,
+        frozen: None,
+        receipt: None,
+        patches: Vec::new(),
     };
     assert_eq!(exp, builder, "pgrx");
-    assert!(builder.configure().is_ok());
-    assert!(builder.compile().is_ok());
-    assert!(builder.test().is_ok());
-    assert!(builder.install().is_ok());
+    assert_eq!("pgrx", builder.pipeline_name());
+    assert_eq!(dist::Pipeline::Pgrx, builder.pipeline_kind());
+    assert!(builder.needs_rebuild());
+    with_mock_cargo("echo", || {
+        assert!(builder.configure().is_ok());
+        assert!(builder.compile().is_ok());
+        assert!(builder.test().is_ok());
+        assert!(builder.install().is_ok());
+        assert!(builder.clean().is_ok());
+    });
+    assert!(builder.artifacts().unwrap().is_empty());
+    assert!(builder.uninstall().is_err());
+}
+
+#[test]
+fn install_dirs() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let rel = || Release::try_from(release_meta("pgrx")).unwrap();
+    let lib = tmp.path().join("lib");
+    let share = tmp.path().join("share");
+    std::fs::create_dir(&lib)?;
+
+    // Report the directories from pg_config.
+    let cfg = PgConfig::from_map(HashMap::from([
+        ("pkglibdir".to_string(), lib.display().to_string()),
+        ("sharedir".to_string(), share.display().to_string()),
+        ("bindir".to_string(), "/pg/bin".to_string()),
+        ("docdir".to_string(), "/pg/doc".to_string()),
+        (
+            "includedir-server".to_string(),
+            "/pg/include/server".to_string(),
+        ),
+    ]));
+    let builder = Builder::new(tmp.as_ref(), rel(), cfg)?;
+    let dirs = with_mock_cargo("echo", || builder.install())?;
+    assert_eq!(Some(lib.as_path()), dirs.pkglibdir());
+    assert_eq!(Some(share.as_path()), dirs.sharedir());
+    assert_eq!(Some(share.join("extension")), dirs.extension_dir());
+    assert_eq!(Some(Path::new("/pg/bin")), dirs.bindir());
+    assert_eq!(Some(Path::new("/pg/doc")), dirs.docdir());
+    assert_eq!(
+        Some(Path::new("/pg/include/server")),
+        dirs.includedir_server()
+    );
+
+    // Report nothing when pg_config lacks the directories.
+    let cfg = PgConfig::from_map(HashMap::new());
+    let builder = Builder::new(tmp.as_ref(), rel(), cfg)?;
+    let dirs = with_mock_cargo("echo", || builder.install())?;
+    assert_eq!(None, dirs.pkglibdir());
+    assert_eq!(None, dirs.sharedir());
+    assert_eq!(None, dirs.extension_dir());
+    assert_eq!(None, dirs.bindir());
+    assert_eq!(None, dirs.docdir());
+    assert_eq!(None, dirs.includedir_server());
+    assert_eq!(None, dirs.destdir());
+
+    Ok(())
+}
+
+#[test]
+fn build_all() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::from([(
+        "version".to_string(),
+        "PostgreSQL 17.2".to_string(),
+    )]));
+
+    // Summarize a successful build.
+    let release = tmp.as_ref().join("target").join("release");
+    std::fs::create_dir_all(&release)?;
+    let lib = release.join("pair.so");
+    std::fs::write(&lib, "hello\n")?;
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?;
+    let summary = with_mock_cargo("echo", || builder.build_all());
+    assert!(summary.succeeded());
+    assert_eq!("pair", summary.dist());
+    assert_eq!("0.1.8", summary.version());
+    assert_eq!("pgrx", summary.pipeline());
+    assert_eq!(Some("PostgreSQL 17.2"), summary.pg_version());
+    assert_eq!(
+        vec!["configure", "compile", "artifacts", "install", "test"],
+        summary.steps().iter().map(|s| s.name()).collect::<Vec<_>>(),
+    );
+    assert_eq!(1, summary.artifacts().len());
+    assert_eq!(lib, summary.artifacts()[0].path());
+    assert_eq!(
+        "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03",
+        summary.artifacts()[0].sha256(),
+    );
+    assert!(summary.installed().is_some());
+    assert_eq!(std::env::consts::OS, summary.os());
+    assert_eq!(std::env::consts::ARCH, summary.arch());
+
+    // PGXS tests run after installing, and the summary lists the files.
+    let tmp = tempdir()?;
+    let stage = tmp.path().join("stage");
+    std::fs::write(
+        tmp.path().join("Makefile"),
+        format!(
+            concat!(
+                "REGRESS = pair\n",
+                "all:\n\ttouch pair.so\n",
+                "install:\n",
+                "\tmkdir -p $(DESTDIR)/pg/lib $(DESTDIR)/pg/share/extension\n",
+                "\tcp pair.so $(DESTDIR)/pg/lib/\n",
+                "\ttouch $(DESTDIR)/pg/share/extension/pair.control\n",
+                "installcheck:\n\ttest -f {}/pg/lib/pair.so\n",
+            ),
+            stage.display(),
+        ),
+    )?;
+    let cfg = PgConfig::from_map(HashMap::from([
+        ("pkglibdir".to_string(), "/pg/lib".to_string()),
+        ("sharedir".to_string(), "/pg/share".to_string()),
+    ]));
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.destdir(&stage);
+    let summary = builder.build_all();
+    assert!(summary.succeeded(), "{:?}", summary.steps());
+    assert_eq!(
+        vec![
+            stage.join("pg").join("lib").join("pair.so"),
+            stage
+                .join("pg")
+                .join("share")
+                .join("extension")
+                .join("pair.control"),
+        ],
+        summary.files(),
+    );
+
+    // Stop at the first failure: PGXS compile fails without a Makefile.
+    let tmp = tempdir()?;
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg)?;
+    let summary = builder.build_all();
+    assert!(!summary.succeeded());
+    assert_eq!("pgxs", summary.pipeline());
+    let steps = summary.steps();
+    assert_eq!(2, steps.len());
+    assert!(steps[0].succeeded());
+    assert_eq!("compile", steps[1].name());
+    assert!(steps[1].error().is_some());
+    assert!(summary.artifacts().is_empty());
+    assert!(summary.installed().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn build_release() -> Result<(), BuildError> {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    let api = api::Api::new(&format!("file://{}", corpus.display()), None)?;
+    let version = semver::Version::new(0, 1, 7);
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // PGXS compile fails without make in the path; keep the download and
+    // the unpacked directory.
+    let tmp = tempdir()?;
+    let empty = tempdir()?;
+    let summary = temp_env::with_var("PATH", Some(empty.path()), || {
+        super::build_release(&api, "pair", &version, cfg.clone(), tmp.as_ref())
+    })?;
+    assert!(!summary.succeeded());
+    assert_eq!("pgxs", summary.pipeline());
+    assert_eq!("compile", summary.steps().last().unwrap().name());
+    assert!(tmp.path().join("pair-0.1.7.zip").exists());
+    assert!(tmp.path().join("pair-0.1.7").join("Makefile").exists());
+
+    // Unknown version.
+    let version = semver::Version::new(0, 0, 1);
+    assert!(super::build_release(&api, "pair", &version, cfg, tmp.as_ref()).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn destdir() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let stage = tmp.path().join("stage");
+    let cfg = PgConfig::from_map(HashMap::from([
+        ("pkglibdir".to_string(), "/pg/lib".to_string()),
+        ("sharedir".to_string(), "/pg/share".to_string()),
+    ]));
+
+    // PGXS returns the staged directories.
+    std::fs::write(
+        tmp.path().join("Makefile"),
+        "install:\n\tmkdir -p $(DESTDIR)/pg/lib\n",
+    )?;
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.destdir(&stage);
+    let dirs = builder.install()?;
+    assert!(stage.join("pg").join("lib").is_dir());
+    assert_eq!(Some(stage.as_path()), dirs.destdir());
+    assert_eq!(
+        Some(stage.join("pg").join("lib").as_path()),
+        dirs.pkglibdir()
+    );
+    assert_eq!(
+        Some(stage.join("pg").join("share").as_path()),
+        dirs.sharedir()
+    );
+
+    // pgrx does not support staged installs.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg)?.destdir(&stage);
+    match builder.install() {
+        Ok(_) => panic!("pgrx install unexpectedly succeeded"),
+        Err(e) => assert_eq!(
+            "pgrx pipeline does not support staged installs; cargo-pgrx install has no DESTDIR option",
+            e.to_string(),
+        ),
+    }
+
+    // PGXS does not support cargo pgrx package.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, PgConfig::from_map(HashMap::new()))?;
+    match builder.package() {
+        Ok(_) => panic!("pgxs package unexpectedly succeeded"),
+        Err(e) => assert_eq!(
+            "pgxs pipeline does not support package; stage the install with destdir instead",
+            e.to_string(),
+        ),
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn receipt() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let stage = tmp.path().join("stage");
+    let path = tmp.path().join("receipt.json");
+    let cfg = PgConfig::from_map(HashMap::from([
+        ("pkglibdir".to_string(), "/pg/lib".to_string()),
+        ("sharedir".to_string(), "/pg/share".to_string()),
+        ("bindir".to_string(), "/pg/bin".to_string()),
+        ("docdir".to_string(), "/pg/doc".to_string()),
+        (
+            "includedir-server".to_string(),
+            "/pg/include/server".to_string(),
+        ),
+        ("version".to_string(), "PostgreSQL 17.2".to_string()),
+    ]));
+
+    // Files already installed should be omitted.
+    let ext = stage.join("pg").join("share").join("extension");
+    std::fs::create_dir_all(&ext)?;
+    std::fs::write(ext.join("other.control"), "")?;
+
+    std::fs::write(
+        tmp.path().join("Makefile"),
+        concat!(
+            "install:\n",
+            "\tmkdir -p $(DESTDIR)/pg/lib $(DESTDIR)/pg/share/extension $(DESTDIR)/pg/bin $(DESTDIR)/pg/doc $(DESTDIR)/pg/include/server\n",
+            "\ttouch $(DESTDIR)/pg/lib/pair.so $(DESTDIR)/pg/share/extension/pair.control\n",
+            "\ttouch $(DESTDIR)/pg/bin/pair_dump $(DESTDIR)/pg/doc/pair.md $(DESTDIR)/pg/include/server/pair.h\n",
+        ),
+    )?;
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg)?
+        .destdir(&stage)
+        .receipt(&path);
+    builder.install()?;
+
+    let receipt: Value = serde_json::from_slice(&std::fs::read(&path)?)?;
+    assert_eq!(
+        json!({
+            "dist": "pair",
+            "version": "0.1.8",
+            "pg_version": "PostgreSQL 17.2",
+            "files": [
+                stage.join("pg").join("bin").join("pair_dump"),
+                stage.join("pg").join("doc").join("pair.md"),
+                stage.join("pg").join("include").join("server").join("pair.h"),
+                stage.join("pg").join("lib").join("pair.so"),
+                ext.join("pair.control"),
+            ],
+        }),
+        receipt,
+    );
+
+    Ok(())
+}
+
+#[test]
+fn frozen() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let path = tmp.as_ref().join("manifest.json");
+    let rel = || Release::try_from(release_meta("pgrx")).unwrap();
+    let cfg = |v: &str| PgConfig::from_map(HashMap::from([("version".to_string(), v.to_string())]));
+
+    // Configure with a mock cargo for the pgrx preflight check.
+    with_mock_cargo("echo", || -> Result<(), BuildError> {
+        // The first run records the manifest.
+        let builder = Builder::new(tmp.as_ref(), rel(), cfg("PostgreSQL 17.2"))?.frozen(&path);
+        assert_eq!(Some(path.clone()), builder.frozen);
+        match &builder.pipeline {
+            Build::Pgrx(pgrx) => assert_eq!(
+                [("CARGO_NET_OFFLINE".to_string(), Some("true".to_string()))],
+                pgrx.env()
+            ),
+            Build::Pgxs(_) => panic!("expected pgrx pipeline"),
+        }
+        builder.configure()?;
+        assert!(path.exists());
+
+        // The second run verifies it.
+        builder.configure()?;
+
+        // A different pg_config fails.
+        let builder = Builder::new(tmp.as_ref(), rel(), cfg("PostgreSQL 16.4"))?.frozen(&path);
+        match builder.configure() {
+            Ok(_) => panic!("frozen build unexpectedly succeeded"),
+            Err(e) => assert_eq!(
+                format!(
+                    "build inputs differ from manifest {}: pg_config.version",
+                    path.display()
+                ),
+                e.to_string()
+            ),
+        }
+
+        // Not frozen by default.
+        let builder = Builder::new(tmp.as_ref(), rel(), cfg("PostgreSQL 16.4"))?;
+        assert_eq!(None, builder.frozen);
+        match &builder.pipeline {
+            Build::Pgrx(pgrx) => assert!(pgrx.env().is_empty()),
+            Build::Pgxs(_) => panic!("expected pgrx pipeline"),
+        }
+        builder.configure()?;
+
+        Ok(())
+    })
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn apply_patch() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let src = tmp.path().join("src");
+    std::fs::create_dir(&src)?;
+    std::fs::write(src.join("pair.sql"), "SELECT 1;\n")?;
+    let patch = tmp.path().join("fix.patch");
+    std::fs::write(
+        &patch,
+        "--- a/pair.sql\n+++ b/pair.sql\n@@ -1 +1 @@\n-SELECT 1;\n+SELECT 2;\n",
+    )?;
+    let manifest = tmp.path().join("manifest.json");
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let cfg = PgConfig::from_map(HashMap::new());
+    let mut builder = Builder::new(src.as_path(), rel, cfg)?.frozen(&manifest);
+
+    // Apply the patch and record it in the manifest.
+    builder.apply_patch(&patch)?;
+    assert_eq!(
+        "SELECT 2;\n",
+        std::fs::read_to_string(src.join("pair.sql"))?
+    );
+    assert_eq!(1, builder.patches.len());
+    assert_eq!("fix.patch", builder.patches[0].file);
+    builder.configure()?;
+    let val: serde_json::Value = serde_json::from_slice(&std::fs::read(&manifest)?)?;
+    assert_eq!(
+        json!([{"file": "fix.patch", "sha256": builder.patches[0].sha256}]),
+        val["patches"]
+    );
+
+    // Applying it again fails and records nothing.
+    match builder.apply_patch(&patch) {
+        Ok(_) => panic!("apply_patch unexpectedly succeeded"),
+        Err(e) => assert!(
+            e.to_string()
+                .contains("\"patch\" \"-p1\" \"--forward\" \"-i\""),
+            "{e}"
+        ),
+    }
+    assert_eq!(1, builder.patches.len());
+
+    // Missing patch file.
+    match builder.apply_patch(tmp.path().join("nonesuch.patch")) {
+        Ok(_) => panic!("apply_patch nonesuch unexpectedly succeeded"),
+        Err(e) => assert!(
+            e.to_string().ends_with("nonesuch.patch: entity not found"),
+            "{e}"
+        ),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn warnings_as_errors() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // PGXS passes it to the pipeline.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.warnings_as_errors(true);
+    let mut exp = Pgxs::new(tmp.as_ref(), cfg.clone());
+    exp.warnings_as_errors(true);
+    assert_eq!(Build::Pgxs(exp), builder.pipeline);
+
+    // pgrx ignores it.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.warnings_as_errors(true);
+    assert_eq!(Build::Pgrx(Pgrx::new(tmp.as_ref(), cfg)), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn configure_shell() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // PGXS passes it to the pipeline.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.configure_shell("sh");
+    let mut exp = Pgxs::new(tmp.as_ref(), cfg.clone());
+    exp.set_shell(Some("sh".to_string()));
+    assert_eq!(Build::Pgxs(exp), builder.pipeline);
+
+    // pgrx ignores it.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.configure_shell("sh");
+    assert_eq!(Build::Pgrx(Pgrx::new(tmp.as_ref(), cfg)), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn skip_pgrx_init() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // pgrx passes it to the pipeline.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.skip_pgrx_init(true);
+    let mut exp = Pgrx::new(tmp.as_ref(), cfg.clone());
+    exp.skip_init(true);
+    assert_eq!(Build::Pgrx(exp), builder.pipeline);
+
+    // PGXS ignores it.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.skip_pgrx_init(true);
+    assert_eq!(Build::Pgxs(Pgxs::new(tmp.as_ref(), cfg)), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn pgrx_package() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // pgrx passes it to the pipeline.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.pgrx_package("alpha");
+    let mut exp = Pgrx::new(tmp.as_ref(), cfg.clone());
+    exp.set_package(Some("alpha".to_string()));
+    assert_eq!(Build::Pgrx(exp), builder.pipeline);
+
+    // PGXS ignores it.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.pgrx_package("alpha");
+    assert_eq!(Build::Pgxs(Pgxs::new(tmp.as_ref(), cfg)), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn pgrx_features_profile() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // pgrx passes them to the pipeline.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?
+        .pgrx_features(["a", "b"])
+        .pgrx_profile("dev");
+    let mut exp = Pgrx::new(tmp.as_ref(), cfg.clone());
+    exp.set_features(vec!["a".to_string(), "b".to_string()]);
+    exp.set_profile(Some("dev".to_string()));
+    assert_eq!(Build::Pgrx(exp), builder.pipeline);
+
+    // PGXS ignores them.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?
+        .pgrx_features(["a"])
+        .pgrx_profile("dev");
+    assert_eq!(Build::Pgxs(Pgxs::new(tmp.as_ref(), cfg)), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn c_locale() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    for pipe in ["pgxs", "pgrx"] {
+        let rel = Release::try_from(release_meta(pipe)).unwrap();
+        let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?;
+        match &builder.pipeline {
+            Build::Pgxs(pgxs) => assert!(pgxs.c_locale(), "{pipe}"),
+            Build::Pgrx(pgrx) => assert!(pgrx.c_locale(), "{pipe}"),
+        }
+        match builder.c_locale(false).pipeline {
+            Build::Pgxs(pgxs) => assert!(!pgxs.c_locale(), "{pipe}"),
+            Build::Pgrx(pgrx) => assert!(!pgrx.c_locale(), "{pipe}"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn sudo() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    for pipe in ["pgxs", "pgrx"] {
+        let rel = Release::try_from(release_meta(pipe)).unwrap();
+        let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?;
+        match &builder.pipeline {
+            Build::Pgxs(pgxs) => {
+                assert_eq!("sudo", pgxs.sudo_program(), "{pipe}");
+                assert!(!pgxs.sudo_non_interactive(), "{pipe}");
+            }
+            Build::Pgrx(pgrx) => {
+                assert_eq!("sudo", pgrx.sudo_program(), "{pipe}");
+                assert!(!pgrx.sudo_non_interactive(), "{pipe}");
+            }
+        }
+        let builder = builder.sudo_program("doas").sudo_non_interactive(true);
+        match &builder.pipeline {
+            Build::Pgxs(pgxs) => {
+                assert_eq!("doas", pgxs.sudo_program(), "{pipe}");
+                assert!(pgxs.sudo_non_interactive(), "{pipe}");
+            }
+            Build::Pgrx(pgrx) => {
+                assert_eq!("doas", pgrx.sudo_program(), "{pipe}");
+                assert!(pgrx.sudo_non_interactive(), "{pipe}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn env() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+    let exp = [
+        (
+            "CARGO_TARGET_DIR".to_string(),
+            Some("/tmp/target".to_string()),
+        ),
+        (
+            "PG_CONFIG".to_string(),
+            Some("/pg/bin/pg_config".to_string()),
+        ),
+        ("USE_PGXS".to_string(), Some("1".to_string())),
+        ("PGDATA".to_string(), None),
+    ];
+
+    for pipe in ["pgxs", "pgrx"] {
+        let rel = Release::try_from(release_meta(pipe)).unwrap();
+        let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?
+            .env("CARGO_TARGET_DIR", "/tmp/nonesuch")
+            .envs([("PG_CONFIG", "/pg/bin/pg_config"), ("USE_PGXS", "1")])
+            .env("CARGO_TARGET_DIR", "/tmp/target")
+            .env("PGDATA", "/pg/data")
+            .env_remove("PGDATA");
+        match &builder.pipeline {
+            Build::Pgxs(pgxs) => assert_eq!(exp, pgxs.env(), "{pipe}"),
+            Build::Pgrx(pgrx) => assert_eq!(exp, pgrx.env(), "{pipe}"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn timeout() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+    let timeout = Duration::from_secs(90);
+
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.timeout(timeout);
+    let mut exp = Pgxs::new(tmp.as_ref(), cfg.clone());
+    exp.set_timeout(Some(timeout));
+    assert_eq!(Build::Pgxs(exp), builder.pipeline);
+
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.timeout(timeout);
+    let mut exp = Pgrx::new(tmp.as_ref(), cfg);
+    exp.set_timeout(Some(timeout));
+    assert_eq!(Build::Pgrx(exp), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn last_output() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+    let rel = || Release::try_from(release_meta("pgrx")).unwrap();
+
+    // Nothing until a command runs.
+    std::fs::create_dir(tmp.path().join("target"))?;
+    let builder = Builder::new(tmp.as_ref(), rel(), cfg.clone())?;
+    assert_eq!(None, builder.last_output());
+
+    // Capture the output of the most recent command.
+    with_mock_cargo("echo", || builder.clean())?;
+    assert_eq!(
+        Some(Captured {
+            stdout: "clean\n".to_string(),
+            stderr: String::new(),
+        }),
+        builder.last_output()
+    );
+
+    // Keep only the tail.
+    let builder = Builder::new(tmp.as_ref(), rel(), cfg.clone())?.output_cap(3);
+    with_mock_cargo("echo", || builder.clean())?;
+    assert_eq!("an\n", builder.last_output().unwrap().stdout);
+
+    // PGXS takes the cap, too.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.output_cap(42);
+    let mut exp = Pgxs::new(tmp.as_ref(), cfg);
+    exp.set_output_cap(42);
+    assert_eq!(Build::Pgxs(exp), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn make_args() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // PGXS passes them to the pipeline.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.make_args(["PG_CPPFLAGS=-DFOO"]);
+    let mut exp = Pgxs::new(tmp.as_ref(), cfg.clone());
+    exp.set_make_args(vec!["PG_CPPFLAGS=-DFOO".to_string()]);
+    assert_eq!(Build::Pgxs(exp), builder.pipeline);
+
+    // pgrx ignores them.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.make_args(["PG_CPPFLAGS=-DFOO"]);
+    assert_eq!(Build::Pgrx(Pgrx::new(tmp.as_ref(), cfg)), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn pgxs_test_mode() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // PGXS passes it to the pipeline.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.pgxs_test_mode(TestMode::Tap);
+    let mut exp = Pgxs::new(tmp.as_ref(), cfg.clone());
+    exp.set_test_mode(TestMode::Tap);
+    assert_eq!(Build::Pgxs(exp), builder.pipeline);
+
+    // pgrx ignores it.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.pgxs_test_mode(TestMode::Tap);
+    assert_eq!(Build::Pgrx(Pgrx::new(tmp.as_ref(), cfg)), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn pgxs_make() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // PGXS passes it to the pipeline.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.pgxs_make("gmake");
+    let mut exp = Pgxs::new(tmp.as_ref(), cfg.clone());
+    exp.set_make(Some("gmake".to_string()));
+    assert_eq!(Build::Pgxs(exp), builder.pipeline);
+
+    // pgrx ignores it.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.pgxs_make("gmake");
+    assert_eq!(Build::Pgrx(Pgrx::new(tmp.as_ref(), cfg)), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn pgxs_test_env() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // PGXS passes it to the pipeline.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.pgxs_test_env("PGPORT", "5433");
+    let mut exp = Pgxs::new(tmp.as_ref(), cfg.clone());
+    exp.set_test_env("PGPORT".to_string(), Some("5433".to_string()));
+    assert_eq!(Build::Pgxs(exp), builder.pipeline);
+
+    // pgrx ignores it.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?.pgxs_test_env("PGPORT", "5433");
+    assert_eq!(Build::Pgrx(Pgrx::new(tmp.as_ref(), cfg)), builder.pipeline);
+
+    Ok(())
+}
+
+#[test]
+fn command_wrapper() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+    let wrapper = vec!["prlimit".to_string(), "--as=1000000".to_string()];
+
+    for pipe in ["pgxs", "pgrx"] {
+        let rel = Release::try_from(release_meta(pipe)).unwrap();
+        let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?;
+        let builder = builder.command_wrapper(["prlimit", "--as=1000000"]);
+        match builder.pipeline {
+            Build::Pgxs(pgxs) => assert_eq!(&wrapper, pgxs.wrapper(), "{pipe}"),
+            Build::Pgrx(pgrx) => assert_eq!(&wrapper, pgrx.wrapper(), "{pipe}"),
+        }
+    }
+
+    // Wrap commands.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    std::fs::write(tmp.path().join("Makefile"), "all:\n\ttrue\n")?;
+    let builder = Builder::new(tmp.as_ref(), rel, cfg)?.command_wrapper(["__nonesuch_nope__"]);
+    match builder.compile() {
+        Ok(_) => panic!("compile unexpectedly succeeded"),
+        Err(e) => assert!(
+            e.to_string()
+                .contains("\"__nonesuch_nope__\" \"make\" \"all\""),
+            "{e}"
+        ),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn write_probe() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+    let probe = Probe {
+        prefix: "x-".to_string(),
+        suffix: ".so".to_string(),
+    };
+
+    for pipe in ["pgxs", "pgrx"] {
+        let rel = Release::try_from(release_meta(pipe)).unwrap();
+        let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?;
+        let got = match &builder.pipeline {
+            Build::Pgxs(p) => p.probe(),
+            Build::Pgrx(p) => p.probe(),
+        };
+        assert_eq!(Probe::default(), got, "{pipe} default");
+
+        let builder = builder.write_probe("x-", ".so");
+        let got = match &builder.pipeline {
+            Build::Pgxs(p) => p.probe(),
+            Build::Pgrx(p) => p.probe(),
+        };
+        assert_eq!(probe, got, "{pipe}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn extensions() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+
+    // One extension.
+    let rel = Release::try_from(release_meta("pgrx")).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg.clone())?;
+    let exts = builder.extensions();
+    assert_eq!(1, exts.len());
+    assert_eq!("pair", exts[0].name());
+    assert_eq!("sql/pair.sql", exts[0].sql());
+    assert_eq!("pair.control", exts[0].control());
+
+    // Several extensions, sorted by name.
+    let mut meta = release_meta("pgrx");
+    meta.as_object_mut().unwrap().insert(
+        "contents".to_string(),
+        json!({"extensions": {
+            "pair": {"sql": "sql/pair.sql", "control": "pair.control"},
+            "duo": {"sql": "sql/duo.sql", "control": "duo.control"},
+        }}),
+    );
+    let rel = Release::try_from(meta).unwrap();
+    let builder = Builder::new(tmp.as_ref(), rel, cfg)?;
+    assert_eq!(
+        vec![
+            ExtensionInfo {
+                name: "duo".to_string(),
+                sql: "sql/duo.sql".to_string(),
+                control: "duo.control".to_string(),
+            },
+            ExtensionInfo {
+                name: "pair".to_string(),
+                sql: "sql/pair.sql".to_string(),
+                control: "pair.control".to_string(),
+            },
+        ],
+        builder.extensions(),
+    );
+
+    Ok(())
 }
 
 #[test]
@@ -88,6 +925,91 @@ fn unsupported_pipeline() {
     );
 }
 
+#[test]
+fn from_dir() -> Result<(), BuildError> {
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+    let path = tmp.path().join("META.json");
+
+    // No META.json.
+    match Builder::from_dir(tmp.as_ref(), cfg.clone()) {
+        Ok(_) => panic!("from_dir unexpectedly succeeded without META.json"),
+        Err(e) => assert_eq!(
+            format!("reading {}: entity not found", path.display()),
+            e.to_string()
+        ),
+    }
+
+    // Invalid JSON.
+    std::fs::write(&path, "{")?;
+    assert!(Builder::from_dir(tmp.as_ref(), cfg.clone()).is_err());
+
+    // Valid metadata, detecting the pipeline.
+    let mut meta = release_meta("");
+    meta.as_object_mut().unwrap().remove("dependencies");
+    std::fs::write(&path, meta.to_string())?;
+    File::create(tmp.path().join("Makefile"))?;
+    let builder = Builder::from_dir(tmp.as_ref(), cfg.clone())?;
+    assert_eq!("pgxs", builder.pipeline_name());
+    assert_eq!(Release::try_from(meta).unwrap(), builder.meta);
+
+    // Pipeline from the metadata.
+    std::fs::write(&path, release_meta("pgrx").to_string())?;
+    let builder = Builder::from_dir(tmp.as_ref(), cfg)?;
+    assert_eq!("pgrx", builder.pipeline_name());
+
+    Ok(())
+}
+
+#[test]
+fn with_pipeline() -> Result<(), BuildError> {
+    // The metadata specifies pgxs, but force pgrx.
+    let tmp = tempdir()?;
+    let cfg = PgConfig::from_map(HashMap::new());
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    let builder = Builder::with_pipeline(tmp.as_ref(), rel, cfg.clone(), dist::Pipeline::Pgrx)?;
+    assert_eq!(
+        Build::Pgrx(Pgrx::new(tmp.as_ref(), cfg.clone())),
+        builder.pipeline
+    );
+
+    // Skips detection, too.
+    let mut meta = release_meta("");
+    meta.as_object_mut().unwrap().remove("dependencies");
+    let rel = Release::try_from(meta).unwrap();
+    let builder = Builder::with_pipeline(tmp.as_ref(), rel, cfg.clone(), dist::Pipeline::Pgxs)?;
+    assert_eq!("pgxs", builder.pipeline_name());
+
+    // Unsupported pipeline.
+    let rel = Release::try_from(release_meta("pgxs")).unwrap();
+    match Builder::with_pipeline(tmp.as_ref(), rel, cfg, dist::Pipeline::Cmake) {
+        Ok(_) => panic!("cmake unexpectedly succeeded"),
+        Err(e) => assert_eq!("unknown build pipeline `cmake`", e.to_string()),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn detect_scores() -> Result<(), BuildError> {
+    use dist::Pipeline::{Pgrx, Pgxs};
+    let tmp = tempdir()?;
+    let dir = tmp.as_ref();
+    assert_eq!(vec![(Pgxs, 0), (Pgrx, 0)], Builder::detect_scores(dir));
+
+    File::create(dir.join("Makefile"))?;
+    assert_eq!(vec![(Pgxs, 127), (Pgrx, 0)], Builder::detect_scores(dir));
+
+    let mut cargo_toml = File::create(dir.join("Cargo.toml"))?;
+    assert_eq!(vec![(Pgxs, 127), (Pgrx, 1)], Builder::detect_scores(dir));
+
+    writeln!(&cargo_toml, "[dependencies]\npgrx = \"0.12.6\"")?;
+    cargo_toml.flush()?;
+    assert_eq!(vec![(Pgxs, 127), (Pgrx, 255)], Builder::detect_scores(dir));
+
+    Ok(())
+}
+
 #[test]
 fn detect_pipeline() -> Result<(), BuildError> {
     let mut metas = [release_meta(""), release_meta("")];
@@ -137,7 +1059,10 @@ fn detect_pipeline() -> Result<(), BuildError> {
     }
     for meta in &metas {
         match Builder::new(dir, no_pipe(meta), cfg.clone()) {
-            Ok(b) => assert_eq!(Build::Pgxs(Pgxs::new(dir, cfg.clone())), b.pipeline),
+            Ok(b) => {
+                assert_eq!("pgxs", b.pipeline_name());
+                assert_eq!(Build::Pgxs(Pgxs::new(dir, cfg.clone())), b.pipeline);
+            }
             Err(e) => panic!("Unexpectedly errored with Makefile: {e}"),
         }
     }
@@ -149,7 +1074,10 @@ fn detect_pipeline() -> Result<(), BuildError> {
     }
     for meta in &metas {
         match Builder::new(dir, no_pipe(meta), cfg.clone()) {
-            Ok(b) => assert_eq!(Build::Pgxs(Pgxs::new(dir, cfg.clone())), b.pipeline),
+            Ok(b) => {
+                assert_eq!("pgxs", b.pipeline_name());
+                assert_eq!(Build::Pgxs(Pgxs::new(dir, cfg.clone())), b.pipeline);
+            }
             Err(e) => panic!("Unexpectedly errored with Cargo.toml: {e}"),
         }
     }
@@ -163,7 +1091,10 @@ fn detect_pipeline() -> Result<(), BuildError> {
     }
     for meta in &metas {
         match Builder::new(dir, no_pipe(meta), cfg.clone()) {
-            Ok(b) => assert_eq!(Build::Pgrx(Pgrx::new(dir, cfg.clone())), b.pipeline),
+            Ok(b) => {
+                assert_eq!("pgrx", b.pipeline_name());
+                assert_eq!(Build::Pgrx(Pgrx::new(dir, cfg.clone())), b.pipeline);
+            }
             Err(e) => panic!("Unexpectedly errored with pgrx dependency: {e}"),
         }
     }
@@ -211,6 +1142,25 @@ pub fn compile_mock(name: &str, dest: &str) {
     }
 }
 
+/// Compiles the `name` mock as `cargo` and calls `f` with the mock first in
+/// the `PATH`.
+pub fn with_mock_cargo<R, F: FnOnce() -> R>(name: &str, f: F) -> R {
+    let tmp = tempdir().unwrap();
+    temp_env::with_var("PATH", Some(mock_cargo_path(name, tmp.path())), f)
+}
+
+/// Compiles the `name` mock as `cargo` in `dir` and returns a `PATH` value
+/// that searches `dir` first.
+pub fn mock_cargo_path(name: &str, dir: &Path) -> std::ffi::OsString {
+    let cargo = dir.join(format!("cargo{}", std::env::consts::EXE_SUFFIX));
+    compile_mock(name, &cargo.display().to_string());
+    let path = std::env::var("PATH").unwrap();
+    let path = [dir.to_path_buf()]
+        .into_iter()
+        .chain(std::env::split_paths(&path));
+    std::env::join_paths(path).unwrap()
+}
+
 #[test]
 fn filename() {
     assert_eq!("string", crate::filename("string"));